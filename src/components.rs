@@ -11,9 +11,11 @@ use serde::{Serialize, Deserialize};
 /// This function is defined here so that we can more easily keep track of the components that we 
 /// have placed in this module.
 pub fn register_components(ecs: &mut World) {
+    ecs.register::<Encumbrance>();
     ecs.register::<EntityMoved>();
     ecs.register::<InBackpack>();
-    ecs.register::<InCombat>();
+    ecs.register::<MyTurn>();
+    ecs.register::<OtherLevelPosition>();
     ecs.register::<SimpleMarker<SerializeMe>>();
     ecs.register::<SerializationHelper>();
     ecs.register::<SufferDamage>();
@@ -22,6 +24,58 @@ pub fn register_components(ecs: &mut World) {
     ecs.register::<WantsToUseItem>();
     ecs.register::<WantsToDropItem>();
     ecs.register::<WantsToRemoveItem>();
+    ecs.register::<WantsToShoot>();
+}
+
+/// Encumbrance is how weighed down an Entity is by everything it carries. The
+/// encumbrance_system works it out again every tick, so it is never saved.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Encumbrance {
+    pub carried : f32,
+    pub tier : EncumbranceTier,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncumbranceTier { Unburdened, Burdened, Strained }
+
+impl EncumbranceTier {
+    /// Up to the carry capacity is no burden, up to half as much again is Burdened and beyond
+    /// that Strained.
+    pub fn for_load(carried : f32, capacity : f32) -> EncumbranceTier {
+        if carried <= capacity {
+            EncumbranceTier::Unburdened
+        } else if carried <= capacity * 1.5 {
+            EncumbranceTier::Burdened
+        } else {
+            EncumbranceTier::Strained
+        }
+    }
+
+    /// The speed an Actor with base speed has under this load.
+    pub fn adjust_speed(self, speed : i32) -> i32 {
+        match self {
+            EncumbranceTier::Unburdened => speed,
+            EncumbranceTier::Burdened => speed * 3 / 4,
+            EncumbranceTier::Strained => speed / 2,
+        }
+    }
+
+    /// How much harder it is to land a blow, or dodge one, under this load.
+    pub fn combat_penalty(self) -> i32 {
+        match self {
+            EncumbranceTier::Unburdened => 0,
+            EncumbranceTier::Burdened => 1,
+            EncumbranceTier::Strained => 3,
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            EncumbranceTier::Unburdened => "unburdened",
+            EncumbranceTier::Burdened => "burdened",
+            EncumbranceTier::Strained => "strained",
+        }
+    }
 }
 
 /// The EntityMoved component is inserted on the player so that the trigger system can 
@@ -36,20 +90,27 @@ pub struct InBackpack {
     pub owner : Entity
 }
 
-/// InCombat is used by the melee_combat_system to indicate which entities are currently 
-/// fighting each other.
-/// 
-/// the initiative variable is a not yet developed system for determining who moves first. 
-/// Currently damage is being added up as though everbody move at the same time.
-#[derive(Component, PartialEq, ConvertSaveload, Debug, Clone)]
-pub struct InCombat {
-    pub initiative: i32,
+/// MyTurn is given to an Actor by the initiative_system once it has gathered enough energy to 
+/// act, and taken away again by the turn_system after every system has had its run.
+#[derive(Component, Serialize, Deserialize, Debug, Clone)]
+pub struct MyTurn {}
+
+/// OtherLevelPosition replaces the Position of an Entity left behind on another level, so that
+/// the systems leave it alone until the player returns to that depth.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct OtherLevelPosition {
+    pub x : i32,
+    pub y : i32,
+    pub depth : i32,
 }
 
 // Special component that exists to help serialize and save the game data to disk.
 #[derive(Component, Serialize, Deserialize, Clone)]
 pub struct SerializationHelper {
-    pub map : crate::map_builders::map::Map
+    pub map : crate::map_builders::map::Map,
+    pub dungeon_master : crate::map_builders::dungeon::MasterDungeonMap,
+    #[serde(default)]
+    pub identification : crate::spawner::item::Identification,
 }
 
 /// The SerializeMe structure is used as a marker to indicate what things are to be recorded 
@@ -57,20 +118,21 @@ pub struct SerializationHelper {
 pub struct SerializeMe;
 
 /// SuferDamage contains a vector of integer numbers to be added up and taken away from the
-/// entity it is attached to when the damage_system runs.
+/// entity it is attached to when the damage_system runs. Each amount is paired with whether the
+/// player dealt it, so that the player can be given the experience for a kill.
 #[derive(Component, ConvertSaveload, Clone, Debug)]
 pub struct SufferDamage {
-    pub amount : Vec<i32>
+    pub amount : Vec<(i32, bool)>
 }
 
 impl SufferDamage {
     /// We have implemented a new_damage routine to simplify and contain haow damage is added 
     /// to an entity for ease of management.
-    pub fn new_damage(store: &mut WriteStorage<SufferDamage>, victim: Entity, amount: i32) {
+    pub fn new_damage(store: &mut WriteStorage<SufferDamage>, victim: Entity, amount: i32, from_player: bool) {
         if let Some(suffering) = store.get_mut(victim) {
-            suffering.amount.push(amount);
+            suffering.amount.push((amount, from_player));
         } else {
-            let dmg = SufferDamage { amount : vec![amount] };
+            let dmg = SufferDamage { amount : vec![(amount, from_player)] };
             store.insert(victim, dmg).expect("Unable to insert damage");
         }
     }
@@ -113,6 +175,13 @@ pub struct WantsToRemoveItem {
     pub item : Entity
 }
 
+/// WantsToShoot tells the ranged_combat_system that the Entity it is attached to is firing its
+/// RangedWeapon at the target tile.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct WantsToShoot {
+    pub target : rltk::Point
+}
+
 // Tests go after this point.
 
 #[cfg(tests)]
@@ -0,0 +1,257 @@
+use rltk::{Point, RandomNumberGenerator};
+use specs::prelude::*;
+
+use crate::map_builders::{map::{Map, TileType}};
+use crate::spawner::components::{CombatStats, Position};
+use crate::spawner::item::Item;
+use crate::spawner::monster::Monster;
+use crate::spawner::player::{can_level_up, level_up, perform_command, LevelUpChoice, PlayerCommand};
+use crate::state_machine::{RunState, State};
+use crate::systems::melee_combat_system::delete_the_dead;
+use crate::systems::particle_system::cull_dead_particles;
+
+/// Particles are aged by this many milliseconds for every step of a headless game, standing in
+/// for the frame time Rltk would normally supply.
+const HEADLESS_FRAME_MS: f32 = 100.0;
+
+/// The outcome of a headless game, suitable for printing or comparing in tests.
+#[derive(PartialEq, Clone, Debug)]
+pub struct HeadlessReport {
+    pub seed: u64,
+    pub turns: u32,
+    pub depth: i32,
+    pub hp: i32,
+    pub alive: bool,
+}
+
+/// HeadlessGame steps the ECS with scripted PlayerCommands instead of keyboard input, and
+/// never touches an Rltk context, so it can run on machines without a display.
+pub struct HeadlessGame {
+    pub gs: State,
+    pub seed: u64,
+    pub turns: u32,
+}
+
+impl HeadlessGame {
+    pub fn new(seed: u64) -> HeadlessGame {
+        let gs = State::new(seed);
+        {
+            let mut runstate = gs.ecs.write_resource::<RunState>();
+            *runstate = RunState::PreRun;
+        }
+        let mut game = HeadlessGame { gs, seed, turns: 0 };
+        game.advance();
+        game
+    }
+
+    pub fn runstate(&self) -> RunState {
+        *self.gs.ecs.fetch::<RunState>()
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.runstate() == RunState::GameOver
+    }
+
+    /// Performs one player command and runs the game until the player is asked for input again
+    /// or has died.
+    pub fn step(&mut self, command: PlayerCommand) -> RunState {
+        if self.runstate() != RunState::AwaitingInput {
+            return self.runstate();
+        }
+
+        let newrunstate = match perform_command(&mut self.gs.ecs, command) {
+            // Menus and saving only make sense with a screen; they cost nothing here.
+            RunState::ShowInventory
+            | RunState::ShowDropItem
+            | RunState::ShowRemoveItem
+            | RunState::ShowEquipment
+            | RunState::ShowFireTargeting { .. }
+            | RunState::SaveGame => RunState::AwaitingInput,
+            newrunstate => newrunstate,
+        };
+        if newrunstate != RunState::AwaitingInput {
+            self.turns += 1;
+        }
+        self.set_runstate(newrunstate);
+        self.advance();
+
+        self.runstate()
+    }
+
+    /// Plays until the player dies or max_turns commands have been carried out, asking bot for
+    /// every command.
+    pub fn run<F>(&mut self, max_turns: u32, mut bot: F) -> HeadlessReport
+    where
+        F: FnMut(&World) -> PlayerCommand,
+    {
+        while !self.is_over() && self.turns < max_turns {
+            let command = bot(&self.gs.ecs);
+            self.step(command);
+        }
+        self.report()
+    }
+
+    pub fn report(&self) -> HeadlessReport {
+        let player_entity = *self.gs.ecs.fetch::<Entity>();
+        let hp = self
+            .gs
+            .ecs
+            .read_storage::<CombatStats>()
+            .get(player_entity)
+            .map_or(0, |stats| stats.hp);
+        HeadlessReport {
+            seed: self.seed,
+            turns: self.turns,
+            depth: self.gs.ecs.fetch::<Map>().depth,
+            hp,
+            alive: !self.is_over(),
+        }
+    }
+
+    fn set_runstate(&mut self, newrunstate: RunState) {
+        let mut runwriter = self.gs.ecs.write_resource::<RunState>();
+        *runwriter = newrunstate;
+    }
+
+    /// Runs the game, with State::run_turn, through every state that does not need input.
+    fn advance(&mut self) {
+        loop {
+            let newrunstate = match self.runstate() {
+                RunState::AwaitingInput | RunState::GameOver => return,
+                RunState::LevelUp => {
+                    // Without a menu to choose from, headless characters always take constitution.
+                    level_up(&mut self.gs.ecs, LevelUpChoice::Constitution);
+                    if can_level_up(&self.gs.ecs) { RunState::LevelUp } else { RunState::AwaitingInput }
+                }
+                runstate @ (RunState::PreRun | RunState::Ticking | RunState::NextLevel | RunState::PreviousLevel) => {
+                    self.gs.run_turn(runstate)
+                }
+                _ => RunState::AwaitingInput,
+            };
+            self.set_runstate(newrunstate);
+            delete_the_dead(&mut self.gs.ecs);
+            cull_dead_particles(&mut self.gs.ecs, HEADLESS_FRAME_MS);
+        }
+    }
+}
+
+/// A simple scripted player: it takes the stairs when standing on them, picks up anything at
+/// its feet, attacks adjacent monsters and otherwise heads for the down stairs with the odd
+/// random step thrown in.
+pub fn wandering_bot(ecs: &World) -> PlayerCommand {
+    let player_pos = *ecs.fetch::<Point>();
+    let map = ecs.fetch::<Map>();
+    let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+    let positions = ecs.read_storage::<Position>();
+    let monsters = ecs.read_storage::<Monster>();
+    let items = ecs.read_storage::<Item>();
+
+    let player_idx = map.xy_idx(player_pos.x, player_pos.y);
+    if map.tiles[player_idx] == TileType::DownStairs {
+        return PlayerCommand::Descend;
+    }
+
+    for (_item, pos) in (&items, &positions).join() {
+        if pos.get_x() == player_pos.x && pos.get_y() == player_pos.y {
+            return PlayerCommand::PickUp;
+        }
+    }
+
+    for (_monster, pos) in (&monsters, &positions).join() {
+        let delta_x = pos.get_x() - player_pos.x;
+        let delta_y = pos.get_y() - player_pos.y;
+        if delta_x.abs() <= 1 && delta_y.abs() <= 1 {
+            return PlayerCommand::Move { delta_x, delta_y };
+        }
+    }
+
+    if rng.roll_dice(1, 4) > 1 {
+        if let Some(stairs_idx) = map.tiles.iter().position(|t| *t == TileType::DownStairs) {
+            let path = rltk::a_star_search(player_idx, stairs_idx, &*map);
+            if path.success && path.steps.len() > 1 {
+                let next_x = path.steps[1] as i32 % map.width;
+                let next_y = path.steps[1] as i32 / map.width;
+                return PlayerCommand::Move { delta_x: next_x - player_pos.x, delta_y: next_y - player_pos.y };
+            }
+        }
+    }
+
+    PlayerCommand::Move { delta_x: rng.range(-1, 2), delta_y: rng.range(-1, 2) }
+}
+
+/// Entry point for `--headless`: plays `--games` games of at most `--turns` turns each with the
+/// wandering_bot, starting from `--seed`, and prints one line per game.
+pub fn run_from_args(first_seed: u64, games: u64, max_turns: u32) {
+    let mut deaths = 0;
+    for seed in first_seed..first_seed.wrapping_add(games) {
+        let report = HeadlessGame::new(seed).run(max_turns, wandering_bot);
+        if !report.alive {
+            deaths += 1;
+        }
+        println!(
+            "seed {}: {} after {} turns on depth {} with {} hp",
+            report.seed,
+            if report.alive { "alive" } else { "dead" },
+            report.turns,
+            report.depth,
+            report.hp
+        );
+    }
+    println!("{} games played, {} deaths", games, deaths);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headless_games_run_without_a_window() {
+        for seed in 1..=3 {
+            let report = HeadlessGame::new(seed).run(150, wandering_bot);
+            assert!(report.turns > 0);
+            assert!(!report.alive || report.turns == 150);
+        }
+    }
+
+    #[test]
+    fn levels_are_kept_when_the_player_leaves_them() {
+        let mut game = HeadlessGame::new(5);
+        let (stairs_x, stairs_y) = game.gs.ecs.fetch::<Map>().get_downstairs().unwrap();
+        teleport_player(&mut game, stairs_x, stairs_y);
+        let tiles_before = game.gs.ecs.fetch::<Map>().tiles.clone();
+        let items_before = item_positions(&game);
+
+        assert!(game.step(PlayerCommand::Descend) == RunState::AwaitingInput);
+        assert_eq!(game.gs.ecs.fetch::<Map>().depth, 2);
+        assert!(item_positions(&game) != items_before);
+
+        assert!(game.step(PlayerCommand::Ascend) == RunState::AwaitingInput);
+        let map = game.gs.ecs.fetch::<Map>();
+        assert_eq!(map.depth, 1);
+        assert!(map.tiles == tiles_before);
+        assert_eq!(*game.gs.ecs.fetch::<Point>(), Point::new(stairs_x, stairs_y));
+        assert_eq!(item_positions(&game), items_before);
+    }
+
+    fn teleport_player(game: &mut HeadlessGame, x: i32, y: i32) {
+        let player_entity = *game.gs.ecs.fetch::<Entity>();
+        *game.gs.ecs.write_resource::<Point>() = Point::new(x, y);
+        let mut positions = game.gs.ecs.write_storage::<Position>();
+        let pos = positions.get_mut(player_entity).unwrap();
+        pos.set_x(x);
+        pos.set_y(y);
+    }
+
+    fn item_positions(game: &HeadlessGame) -> Vec<(i32, i32)> {
+        let items = game.gs.ecs.read_storage::<Item>();
+        let positions = game.gs.ecs.read_storage::<Position>();
+        (&items, &positions).join().map(|(_item, pos)| (pos.get_x(), pos.get_y())).collect()
+    }
+
+    #[test]
+    fn headless_games_are_reproducible() {
+        let first = HeadlessGame::new(11).run(100, wandering_bot);
+        let second = HeadlessGame::new(11).run(100, wandering_bot);
+        assert_eq!(first, second);
+    }
+}
@@ -2,10 +2,6 @@
 //! 
 //! This project began as a project to Learn the Rust Programming Language by following 
 //! the Brackets Tutorial to make a simple roguelike. 
-use specs::prelude::*;
-use specs::World;
-use specs::saveload::SimpleMarkerAllocator;
-use std::collections::HashMap;
 
 /// Top level components module.
 /// 
@@ -15,6 +11,8 @@ mod components;
 /// 
 /// Should probably be refactored into the User Interface System.
 mod gamelog;
+/// headless drives the game loop with scripted commands and no window, for automated runs.
+mod headless;
 /// map_builders contains all of the construction data and code to create maps.
 mod map_builders;
 /// spawner organizes and generates all of the Entities that are contained within the Map. 
@@ -23,12 +21,11 @@ mod spawner;
 mod state_machine;
 /// systems are contained here.
 mod systems;
+/// test_fixture builds the World most unit tests start from.
+#[cfg(test)]
+mod test_fixture;
 
-use components::SerializeMe;
-use spawner::{spawn, SpawnSeed};
-use spawner::components::register_spawns;
-use state_machine::{RunState, State};
-use systems::{particle_system, ui_system::MainMenuSelection};
+use state_machine::State;
 
 /// SHOW_MAPGEN_VISUALIZER is a feature flag to turn on/off the debug map display in the 
 /// Main Menu.
@@ -36,32 +33,45 @@ const SHOW_MAPGEN_VISUALIZER : bool = true;
 
 fn main() -> rltk::BError {
     use rltk::RltkBuilder;
+    let args: Vec<String> = std::env::args().collect();
+    let seed = arg_value(&args, "--seed").unwrap_or_else(|| rltk::RandomNumberGenerator::new().next_u64());
+
+    if args.iter().any(|arg| arg == "--headless") {
+        let games = arg_value(&args, "--games").unwrap_or(1);
+        let turns = arg_value(&args, "--turns").unwrap_or(1000);
+        headless::run_from_args(seed, games, turns);
+        return Ok(());
+    }
+
     let context = RltkBuilder::simple80x50()
         .with_title("Roguelike Tutorial")
         .build()?;
-    let mut gs = State {
-        ecs: World::new()
-    };
-
-    components::register_components(&mut gs.ecs);
-    register_spawns(&mut gs.ecs);
-
-    gs.ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
-
-    let map = map_builders::build_random_map(1);
+    let gs = State::new(seed);
 
-    gs.ecs.insert(rltk::RandomNumberGenerator::new());
-    gs.ecs.insert(RunState::MainMenu { menu_selection: MainMenuSelection::NewGame} );
-    gs.ecs.insert(gamelog::GameLog{ entries : vec!["Welcome to Rusty Roguelike".to_string()] });
-    gs.ecs.insert(particle_system::ParticleBuilder::new());
-    gs.ecs.insert(particle_system::Particles { particle : HashMap::new() });
-    gs.ecs.insert(map.clone());
-
-    spawn(&mut gs.ecs, &map, SpawnSeed::Player);
-
-    spawn(&mut gs.ecs, &map, SpawnSeed::Monster);
-
-    spawn(&mut gs.ecs, &map, SpawnSeed::Item);
- 
     rltk::main_loop(context, gs)
 }
+
+/// Reads the value following flag on the command line, e.g. `--seed <number>` so that a
+/// reported run can be replayed. A flag without a value, or with one that does not parse,
+/// ends the program with an error rather than quietly falling back to the default.
+fn arg_value<T>(args: &[String], flag: &str) -> Option<T>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    let position = args.iter().position(|arg| arg == flag)?;
+    let value = match args.get(position + 1) {
+        Some(value) => value,
+        None => {
+            eprintln!("{} needs a value", flag);
+            std::process::exit(2);
+        }
+    };
+    match value.parse() {
+        Ok(parsed) => Some(parsed),
+        Err(e) => {
+            eprintln!("Invalid value '{}' for {}: {}", value, flag, e);
+            std::process::exit(2);
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use rltk::RandomNumberGenerator;
+
+use super::map::{Map, TileType};
+use super::MetaMapBuilder;
+
+#[allow(dead_code)]
+#[derive(PartialEq, Copy, Clone)]
+pub enum XStart { Left, Center, Right }
+
+#[allow(dead_code)]
+#[derive(PartialEq, Copy, Clone)]
+pub enum YStart { Top, Center, Bottom }
+
+/// Puts the up stairs, and so the player, on the floor tile closest to one area of the Map.
+pub struct AreaStartingPoint {
+    x: XStart,
+    y: YStart,
+}
+
+impl AreaStartingPoint {
+    pub fn new(x: XStart, y: YStart) -> Box<AreaStartingPoint> {
+        Box::new(AreaStartingPoint { x, y })
+    }
+}
+
+impl MetaMapBuilder for AreaStartingPoint {
+    fn build_map(&mut self, map: &mut Map, _rng: &mut RandomNumberGenerator) {
+        let seed_x = match self.x {
+            XStart::Left => 1,
+            XStart::Center => map.width / 2,
+            XStart::Right => map.width - 2,
+        };
+        let seed_y = match self.y {
+            YStart::Top => 1,
+            YStart::Center => map.height / 2,
+            YStart::Bottom => map.height - 2,
+        };
+        let seed = rltk::Point::new(seed_x, seed_y);
+
+        // Ties go to the lowest index, so the choice never depends on anything but the tiles.
+        let mut closest: Option<(usize, f32)> = None;
+        for (idx, tile) in map.tiles.iter().enumerate() {
+            if *tile == TileType::Floor {
+                let tile_pos = rltk::Point::new(idx as i32 % map.width, idx as i32 / map.width);
+                let distance = rltk::DistanceAlg::PythagorasSquared.distance2d(tile_pos, seed);
+                if closest.is_none_or(|(_, best)| distance < best) {
+                    closest = Some((idx, distance));
+                }
+            }
+        }
+
+        let (start_idx, _) = closest.expect("AreaStartingPoint needs at least one floor tile");
+        map.upstairs = (start_idx as i32 % map.width, start_idx as i32 / map.width);
+        let upstairs_idx = map.xy_idx(map.upstairs.0, map.upstairs.1);
+        map.tiles[upstairs_idx] = TileType::UpStairs;
+        map.take_snapshot();
+    }
+}
@@ -13,8 +13,8 @@ pub struct BSPDungeonBuilder {
 
 impl MapBuilder for BSPDungeonBuilder {
 
-    fn new(new_depth: i32) -> Box<dyn MapBuilder> {
-        Box::new(BSPDungeonBuilder { map: Map::new(new_depth), rects: Vec::new()})
+    fn start(new_depth: i32, width: i32, height: i32) -> Box<dyn MapBuilder> {
+        Box::new(BSPDungeonBuilder { map: Map::new(new_depth, width, height), rects: Vec::new()})
     }
 
     #[allow(unused_variables)]
@@ -22,14 +22,7 @@ impl MapBuilder for BSPDungeonBuilder {
         return Box::new(self.clone());
     }
 
-    #[allow(unused_variables)]
-    fn add(&mut self, map: Map) -> Box<dyn MapBuilder> {
-        return Box::new(self.to_owned());
-    }
-
-    fn build(&mut self) -> Map {
-        let mut rng = RandomNumberGenerator::new();
-
+    fn build(&mut self, rng: &mut RandomNumberGenerator) -> Map {
         self.rects.clear();
         self.rects.push( Rect::new(2, 2, self.map.width-5, self.map.height-5));
         let first_room = self.rects[0];
@@ -39,8 +32,8 @@ impl MapBuilder for BSPDungeonBuilder {
         // room in there, we place it and add it to the rooms list.
         let mut n_rooms = 0;
         while n_rooms < 240 {
-            let rect = self.get_random_rect(&mut rng);
-            let candidate = self.get_random_sub_rect(rect, &mut rng);
+            let rect = self.get_random_rect(rng);
+            let candidate = self.get_random_sub_rect(rect, rng);
 
             if self.is_possible(candidate) {
                 apply_room_to_map(&candidate, &mut self.map);
@@ -65,18 +58,6 @@ impl MapBuilder for BSPDungeonBuilder {
             self.map.take_snapshot();
         }
 
-        // Convert to regions for spawners.
-        self.map.convert_rooms_to_regions();
-
-        // Don't forget the stairs
-        let down_stairs = self.map.rooms[self.map.rooms.len()-1].center();
-        let stairs_idx = xy_idx(down_stairs.0, down_stairs.1);
-        self.map.tiles[stairs_idx] = TileType::DownStairs;
-
-        self.map.upstairs = self.map.rooms[0].center();
-        let stairs_idx = xy_idx(self.map.upstairs.0, self.map.upstairs.1);
-        self.map.tiles[stairs_idx] = TileType::UpStairs;
-
         self.map.clone()
     }
 }
@@ -132,7 +113,7 @@ impl BSPDungeonBuilder {
                 if x < 1 { can_build = false; }
                 if y < 1 { can_build = false; }
                 if can_build {
-                    let idx = xy_idx(x, y);
+                    let idx = self.map.xy_idx(x, y);
                     if self.map.tiles[idx] != TileType::Wall { 
                         can_build = false; 
                     }
@@ -158,7 +139,7 @@ impl BSPDungeonBuilder {
                 y -= 1;
             }
     
-            let idx = xy_idx(x, y);
+            let idx = self.map.xy_idx(x, y);
             self.map.tiles[idx] = TileType::Floor;
         }
     }
@@ -1,6 +1,5 @@
 use rltk::RandomNumberGenerator;
 
-use super::common::*;
 use super::map::*;
 use super::{BuilderSettings, MapBuilder};
 use super::rect::Rect;
@@ -14,27 +13,20 @@ pub struct BSPInteriorBuilder {
 }
 
 impl MapBuilder for BSPInteriorBuilder {
-    fn new(new_depth: i32) -> Box<dyn MapBuilder> {
-        Box::new(BSPInteriorBuilder { map: Map::new(new_depth), rects: Vec::new()})
+    fn start(new_depth: i32, width: i32, height: i32) -> Box<dyn MapBuilder> {
+        Box::new(BSPInteriorBuilder { map: Map::new(new_depth, width, height), rects: Vec::new()})
     }
 
     #[allow(unused_variables)]
     fn with_settings (&mut self, settings: BuilderSettings) -> Box<dyn MapBuilder> {
         return Box::new(self.clone());
     }
-    
-    #[allow(unused_variables)]
-    fn add(&mut self, map: Map) -> Box<dyn MapBuilder> {
-        return Box::new(self.to_owned());
-    }
-
-    fn build(&mut self) -> Map {
-        let mut rng = RandomNumberGenerator::new();
 
+    fn build(&mut self, rng: &mut RandomNumberGenerator) -> Map {
         self.rects.clear();
         self.rects.push( Rect::new(1, 1, self.map.width-2, self.map.height-2) ); // Start with a single map-sized rectangle
         let first_room = self.rects[0];
-        self.add_subrects(first_room, &mut rng); // Divide the first room
+        self.add_subrects(first_room, rng); // Divide the first room
 
         let rooms = self.rects.clone();
         for r in rooms.iter() {
@@ -44,7 +36,7 @@ impl MapBuilder for BSPInteriorBuilder {
             self.map.rooms.push(room);
             for y in room.y1 .. room.y2 {
                 for x in room.x1 .. room.x2 {
-                    let idx = xy_idx(x, y);
+                    let idx = self.map.xy_idx(x, y);
                     if idx > 0 && idx < ((self.map.width * self.map.height)-1) as usize {
                         self.map.tiles[idx] = TileType::Floor;
                     }
@@ -65,19 +57,6 @@ impl MapBuilder for BSPInteriorBuilder {
             self.map.take_snapshot();
         }
 
-        // Convert to regions for spawners
-        self.map.convert_rooms_to_regions();
-
-        // Don't forget the downstairs
-        let downstairs = self.map.rooms[self.map.rooms.len()-1].center();
-        let stairs_idx = xy_idx(downstairs.0, downstairs.1);
-        self.map.tiles[stairs_idx] = TileType::DownStairs;
-
-        // Same for the upstairs
-        self.map.upstairs = self.map.rooms[self.map.rooms.len()-1].center();
-        let stairs_idx = xy_idx(self.map.upstairs.0, self.map.upstairs.1);
-        self.map.tiles[stairs_idx] = TileType::UpStairs;
-
         self.map.clone()
     }
 }
@@ -132,7 +111,7 @@ impl BSPInteriorBuilder {
                 y -= 1;
             }
     
-            let idx = xy_idx(x, y);
+            let idx = self.map.xy_idx(x, y);
             self.map.tiles[idx] = TileType::Floor;
         }
     }
@@ -1,5 +1,5 @@
 use std::cmp::{min, max};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use super::map::{Map, TileType};
 use super::rect::Rect;
@@ -8,26 +8,19 @@ use super::rect::Rect;
 #[derive(PartialEq, Copy, Clone)]
 pub enum Symmetry { None, Horizontal, Vertical, Both }
 
-/// Helper function to translate from Cartesian Coordinates to the Linear Vector
-/// that Map components are stored under.
-pub fn xy_idx(x: i32, y: i32) -> usize {
-    if !(x > 0) || !(y > 0) { return 0; }
-    (y as usize * 80) + x as usize
-} 
-
 /// Helper function that sets sets all tiles on the outside edge of the Map to 
 /// TileType::Wall.
 pub fn make_boundary_walls(map: &mut Map) {
     // Make the boundaries walls
     for x in 0..map.width {
-        let right = xy_idx(x, 0);
-        let left = xy_idx(x, map.height-1);
+        let right = map.xy_idx(x, 0);
+        let left = map.xy_idx(x, map.height-1);
         map.tiles[right] = TileType::Wall;
         map.tiles[left] = TileType::Wall;
     }
     for y in 0..map.height {
-        let top = xy_idx(0, y);
-        let bottom = xy_idx(map.width-1, y);
+        let top = map.xy_idx(0, y);
+        let bottom = map.xy_idx(map.width-1, y);
         map.tiles[top] = TileType::Wall;
         map.tiles[bottom] = TileType::Wall;
     }
@@ -39,7 +32,7 @@ pub fn apply_room_to_map(room : &Rect, map: &mut Map) {
         for x in room.x1 + 1 ..= room.x2 {
             let idx: usize;
             {
-                idx = xy_idx(x, y);
+                idx = map.xy_idx(x, y);
             }
             map.tiles[idx] = TileType::Floor;
         }
@@ -49,7 +42,7 @@ pub fn apply_room_to_map(room : &Rect, map: &mut Map) {
 /// Write a corridor onto the Map that is affixed to the X dimension.
 pub fn apply_horizontal_tunnel(map : &mut Map, x1:i32, x2:i32, y:i32) {
     for x in min(x1,x2) ..= max(x1,x2) {
-        let idx = xy_idx(x, y);
+        let idx = map.xy_idx(x, y);
         if idx > 0 && idx < map.width as usize * map.height as usize {
             map.tiles[idx] = TileType::Floor;
         }
@@ -59,7 +52,7 @@ pub fn apply_horizontal_tunnel(map : &mut Map, x1:i32, x2:i32, y:i32) {
 /// Write a corridor onto the Map that is afixed to the Y dimension.
 pub fn apply_vertical_tunnel(map : &mut Map, y1:i32, y2:i32, x:i32) {
     for y in min(y1,y2) ..= max(y1,y2) {
-        let idx = xy_idx(x, y);
+        let idx = map.xy_idx(x, y);
         if idx > 0 && idx < map.width as usize * map.height as usize {
             map.tiles[idx] = TileType::Floor;
         }
@@ -93,8 +86,8 @@ pub fn remove_unreachable_areas_returning_most_distant(map : &mut Map, start_idx
 
 /// Generates a Voronoi/cellular noise map of a region, and divides it into spawn regions.
 #[allow(clippy::map_entry)]
-pub fn generate_voronoi_spawn_regions(map: &Map, rng : &mut rltk::RandomNumberGenerator) -> HashMap<i32, Vec<usize>> {
-    let mut noise_areas : HashMap<i32, Vec<usize>> = HashMap::new();
+pub fn generate_voronoi_spawn_regions(map: &Map, rng : &mut rltk::RandomNumberGenerator) -> BTreeMap<i32, Vec<usize>> {
+    let mut noise_areas : BTreeMap<i32, Vec<usize>> = BTreeMap::new();
     let mut noise = rltk::FastNoise::seeded(rng.roll_dice(1, 65536) as u64);
     noise.set_noise_type(rltk::NoiseType::Cellular);
     noise.set_frequency(0.08);
@@ -102,7 +95,7 @@ pub fn generate_voronoi_spawn_regions(map: &Map, rng : &mut rltk::RandomNumberGe
 
     for y in 1 .. map.height-1 {
         for x in 1 .. map.width-1 {
-            let idx = xy_idx(x, y);
+            let idx = map.xy_idx(x, y);
             if map.tiles[idx] == TileType::Floor {
                 let cell_value_f = noise.get_noise(x as f32, y as f32) * 10240.0;
                 let cell_value = cell_value_f as i32;
@@ -162,7 +155,7 @@ pub fn paint(map: &mut Map, mode: Symmetry, brush_size: i32, x: i32, y:i32) {
 fn apply_paint(map: &mut Map, brush_size: i32, x: i32, y: i32) {
     match brush_size {
         1 => {
-            let digger_idx = xy_idx(x, y);
+            let digger_idx = map.xy_idx(x, y);
             map.tiles[digger_idx] = TileType::Floor;
         }
 
@@ -171,7 +164,7 @@ fn apply_paint(map: &mut Map, brush_size: i32, x: i32, y: i32) {
             for brush_y in y-half_brush_size .. y+half_brush_size {
                 for brush_x in x-half_brush_size .. x+half_brush_size {
                     if brush_x > 1 && brush_x < map.width-1 && brush_y > 1 && brush_y < map.height-1 {
-                        let idx = xy_idx(brush_x, brush_y);
+                        let idx = map.xy_idx(brush_x, brush_y);
                         map.tiles[idx] = TileType::Floor;
                     }
                 }
@@ -0,0 +1,22 @@
+use rltk::RandomNumberGenerator;
+
+use super::common::remove_unreachable_areas_returning_most_distant;
+use super::map::Map;
+use super::MetaMapBuilder;
+
+/// Walls off every floor tile that cannot be reached from the up stairs.
+pub struct CullUnreachable {}
+
+impl CullUnreachable {
+    pub fn new() -> Box<CullUnreachable> {
+        Box::new(CullUnreachable {})
+    }
+}
+
+impl MetaMapBuilder for CullUnreachable {
+    fn build_map(&mut self, map: &mut Map, _rng: &mut RandomNumberGenerator) {
+        let start_idx = map.xy_idx(map.upstairs.0, map.upstairs.1);
+        remove_unreachable_areas_returning_most_distant(map, start_idx);
+        map.take_snapshot();
+    }
+}
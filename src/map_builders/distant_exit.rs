@@ -0,0 +1,33 @@
+use rltk::RandomNumberGenerator;
+
+use super::map::{Map, TileType};
+use super::MetaMapBuilder;
+
+/// Places the down stairs on the reachable floor tile furthest from the up stairs.
+pub struct DistantExit {}
+
+impl DistantExit {
+    pub fn new() -> Box<DistantExit> {
+        Box::new(DistantExit {})
+    }
+}
+
+impl MetaMapBuilder for DistantExit {
+    fn build_map(&mut self, map: &mut Map, _rng: &mut RandomNumberGenerator) {
+        let map_starts : Vec<usize> = vec![map.xy_idx(map.upstairs.0, map.upstairs.1)];
+        let dijkstra_map = rltk::DijkstraMap::new(map.width as usize, map.height as usize, &map_starts , &*map, 200.0);
+        let mut exit_tile = (0, 0.0f32);
+        for (i, tile) in map.tiles.iter().enumerate() {
+            if *tile == TileType::Floor {
+                let distance_to_start = dijkstra_map.map[i];
+                if distance_to_start != f32::MAX && distance_to_start > exit_tile.1 {
+                    exit_tile.0 = i;
+                    exit_tile.1 = distance_to_start;
+                }
+            }
+        }
+
+        map.tiles[exit_tile.0] = TileType::DownStairs;
+        map.take_snapshot();
+    }
+}
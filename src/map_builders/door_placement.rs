@@ -0,0 +1,173 @@
+use rltk::RandomNumberGenerator;
+
+use super::map::{Map, TileType};
+use super::MetaMapBuilder;
+
+/// One door in this many is locked, as long as the way down does not lead through it.
+const LOCKED_ONE_IN: i32 = 4;
+
+/// Puts a Door in every doorway between a room and the corridor or room beyond it. Some of the
+/// doors are locked; those never stand between the up and the down stairs, and an Iron Key is
+/// left somewhere that can be reached without passing one. Only useful after a builder that
+/// creates rooms, and after anything else that places spawns.
+pub struct DoorPlacement {}
+
+impl DoorPlacement {
+    pub fn new() -> Box<DoorPlacement> {
+        Box::new(DoorPlacement {})
+    }
+}
+
+impl MetaMapBuilder for DoorPlacement {
+    fn build_map(&mut self, map: &mut Map, rng: &mut RandomNumberGenerator) {
+        let mut doors: Vec<usize> = Vec::new();
+        let mut locked: Vec<usize> = Vec::new();
+        for idx in 0..map.tiles.len() {
+            if !is_doorway(map, idx)
+                || doors.iter().any(|door| next_to(map, *door, idx))
+                || map.spawn_list.iter().any(|(spawn_idx, _name)| *spawn_idx == idx)
+            {
+                continue;
+            }
+            doors.push(idx);
+            if rng.roll_dice(1, LOCKED_ONE_IN) == 1 {
+                locked.push(idx);
+                if !stairs_connected(map, &locked) {
+                    locked.pop();
+                }
+            }
+        }
+
+        for idx in doors.iter() {
+            let name = if locked.contains(idx) { "Locked Door" } else { "Door" };
+            map.spawn_list.push((*idx, name.to_string()));
+        }
+        if !locked.is_empty() {
+            let distances = distances_from_upstairs(map, &locked);
+            let key_spots: Vec<usize> = (0..map.tiles.len())
+                .filter(|idx| map.tiles[*idx] == TileType::Floor && distances[*idx] < f32::MAX)
+                .filter(|idx| map.spawn_list.iter().all(|(spawn_idx, _name)| spawn_idx != idx))
+                .collect();
+            if !key_spots.is_empty() {
+                let spot = key_spots[rng.roll_dice(1, key_spots.len() as i32) as usize - 1];
+                map.spawn_list.push((spot, "Iron Key".to_string()));
+            }
+        }
+
+        // Nothing else may be spawned into a doorway.
+        for region in map.regions.values_mut() {
+            region.retain(|idx| !doors.contains(idx));
+        }
+    }
+}
+
+fn is_open(map: &Map, x: i32, y: i32) -> bool {
+    x >= 0 && x < map.width && y >= 0 && y < map.height && map.tiles[map.xy_idx(x, y)] != TileType::Wall
+}
+
+/// A tile is roomy when most of the tiles around it are open, as they are inside a room.
+fn is_roomy(map: &Map, x: i32, y: i32) -> bool {
+    let mut open = 0;
+    for delta_y in -1..=1 {
+        for delta_x in -1..=1 {
+            if (delta_x, delta_y) != (0, 0) && is_open(map, x + delta_x, y + delta_y) {
+                open += 1;
+            }
+        }
+    }
+    open >= 5
+}
+
+/// A doorway is a gap in a wall, walled on two opposite sides and open on the other two, with
+/// a room on at least one side of it.
+fn is_doorway(map: &Map, idx: usize) -> bool {
+    if map.tiles[idx] != TileType::Floor {
+        return false;
+    }
+    let x = idx as i32 % map.width;
+    let y = idx as i32 / map.width;
+    if x < 1 || x > map.width - 2 || y < 1 || y > map.height - 2 {
+        return false;
+    }
+    let sides = if !is_open(map, x, y - 1) && !is_open(map, x, y + 1) {
+        [(x - 1, y), (x + 1, y)]
+    } else if !is_open(map, x - 1, y) && !is_open(map, x + 1, y) {
+        [(x, y - 1), (x, y + 1)]
+    } else {
+        return false;
+    };
+    sides.iter().all(|(side_x, side_y)| is_open(map, *side_x, *side_y))
+        && sides.iter().any(|(side_x, side_y)| is_roomy(map, *side_x, *side_y))
+}
+
+fn next_to(map: &Map, a: usize, b: usize) -> bool {
+    let (a_x, a_y) = (a as i32 % map.width, a as i32 / map.width);
+    let (b_x, b_y) = (b as i32 % map.width, b as i32 / map.width);
+    i32::abs(a_x - b_x) <= 1 && i32::abs(a_y - b_y) <= 1
+}
+
+/// How far every tile is from the up stairs, with the locked doors treated as walls.
+fn distances_from_upstairs(map: &Map, locked: &[usize]) -> Vec<f32> {
+    let mut walled = map.clone();
+    for idx in locked.iter() {
+        walled.tiles[*idx] = TileType::Wall;
+    }
+    let start = map.xy_idx(map.upstairs.0, map.upstairs.1);
+    rltk::DijkstraMap::new(map.width as usize, map.height as usize, &[start], &walled, 400.0).map
+}
+
+fn stairs_connected(map: &Map, locked: &[usize]) -> bool {
+    match map.get_downstairs() {
+        None => false,
+        Some((x, y)) => distances_from_upstairs(map, locked)[map.xy_idx(x, y)] < f32::MAX,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map_builders::rect::Rect;
+    use crate::map_builders::common::{apply_horizontal_tunnel, apply_room_to_map};
+
+    #[test]
+    fn doors_go_in_doorways_and_never_lock_the_way_down() {
+        let mut map = Map::new(1, 80, 43);
+        let rooms = [Rect::new(2, 2, 8, 6), Rect::new(20, 2, 8, 6), Rect::new(40, 2, 8, 6)];
+        for room in rooms.iter() {
+            apply_room_to_map(room, &mut map);
+        }
+        apply_horizontal_tunnel(&mut map, 10, 21, 5);
+        apply_horizontal_tunnel(&mut map, 28, 41, 5);
+        map.upstairs = rooms[0].center();
+        let idx = map.xy_idx(rooms[2].center().0, rooms[2].center().1);
+        map.tiles[idx] = TileType::DownStairs;
+
+        for seed in 1..=20 {
+            let mut doored = map.clone();
+            DoorPlacement::new().build_map(&mut doored, &mut RandomNumberGenerator::seeded(seed));
+            let doors: Vec<usize> = doored.spawn_list.iter()
+                .filter(|(_idx, name)| name.ends_with("Door"))
+                .map(|(idx, _name)| *idx)
+                .collect();
+            // One door where each tunnel leaves a room and one where it enters the next.
+            assert_eq!(doors, vec![map.xy_idx(11, 5), map.xy_idx(20, 5), map.xy_idx(29, 5), map.xy_idx(40, 5)], "seed {}", seed);
+            assert!(doored.spawn_list.iter().all(|(_idx, name)| name != "Locked Door"), "seed {}", seed);
+        }
+
+        // With a side room the way down does not need, that room can be locked away.
+        let side_room = Rect::new(20, 20, 8, 6);
+        apply_room_to_map(&side_room, &mut map);
+        for y in 8..=20 {
+            let idx = map.xy_idx(24, y);
+            map.tiles[idx] = TileType::Floor;
+        }
+        let locked = (1..=20).find_map(|seed| {
+            let mut doored = map.clone();
+            DoorPlacement::new().build_map(&mut doored, &mut RandomNumberGenerator::seeded(seed));
+            doored.spawn_list.iter().any(|(_idx, name)| name == "Locked Door").then_some(doored)
+        });
+        let locked = locked.expect("no seed locked the side room");
+        assert!(locked.spawn_list.iter().any(|(_idx, name)| name == "Iron Key"));
+        assert!(stairs_connected(&locked, &[]));
+    }
+}
@@ -2,7 +2,7 @@ use rltk::RandomNumberGenerator;
 
 use super::common::*;
 use super::map::*;
-use super::{BuilderSettings, DrunkSpawnMode, MapBuilder}; //, PrefabMode};
+use super::{BuilderSettings, DrunkSpawnMode, MapBuilder, PrefabMode};
 
 use crate::spawner::components::Position;
 
@@ -13,9 +13,9 @@ pub struct DrunkardsWalkBuilder {
 }
 
 impl MapBuilder for DrunkardsWalkBuilder {
-    fn new(new_depth: i32) -> Box<dyn MapBuilder> {
+    fn start(new_depth: i32, width: i32, height: i32) -> Box<dyn MapBuilder> {
         Box::new(DrunkardsWalkBuilder {
-            map: Map::new(new_depth),
+            map: Map::new(new_depth, width, height),
             settings: BuilderSettings {
                 spawn_mode: Some(DrunkSpawnMode::StartingPoint),
                 lifetime: Some(400),
@@ -23,7 +23,7 @@ impl MapBuilder for DrunkardsWalkBuilder {
                 algorithm: None,
                 symmetry: Some(Symmetry::None),
                 brush_size: Some(1),
-                //mode: PrefabMode::None,
+                mode: PrefabMode::None,
             },
         })
     }
@@ -34,17 +34,10 @@ impl MapBuilder for DrunkardsWalkBuilder {
         return Box::new(self.clone());
     }
 
-    #[allow(unused_variables)]
-    fn add(&mut self, map: Map) -> Box<dyn MapBuilder> {
-        return Box::new(self.to_owned());
-    }
-
-    fn build(&mut self) -> Map {
-        let mut rng = RandomNumberGenerator::new();
-
+    fn build(&mut self, rng: &mut RandomNumberGenerator) -> Map {
         // Set a central starting point
         let starting_position = Position::new(self.map.width / 2, self.map.height / 2);
-        let start_idx = xy_idx(starting_position.get_x(), starting_position.get_y());
+        let start_idx = self.map.xy_idx(starting_position.get_x(), starting_position.get_y());
         self.map.tiles[start_idx] = TileType::Floor;
 
         let total_tiles = self.map.width * self.map.height;
@@ -81,7 +74,7 @@ impl MapBuilder for DrunkardsWalkBuilder {
             let mut drunk_life = self.settings.lifetime.unwrap();
 
             while drunk_life > 0 {
-                let drunk_idx = xy_idx(drunk_x, drunk_y);
+                let drunk_idx = self.map.xy_idx(drunk_x, drunk_y);
                 if self.map.tiles[drunk_idx] == TileType::Wall {
                     did_something = true;
                 }
@@ -137,20 +130,6 @@ impl MapBuilder for DrunkardsWalkBuilder {
             digger_count, active_digger_count
         ));
 
-        // Find all tiles we can reach from the starting point
-        let exit_tile = remove_unreachable_areas_returning_most_distant(&mut self.map, start_idx);
-        self.map.take_snapshot();
-
-        // Place the stairs
-        self.map.tiles[exit_tile] = TileType::DownStairs;
-        self.map.upstairs = (starting_position.get_x(), starting_position.get_y());
-        let up_idx = xy_idx(self.map.upstairs.0, self.map.upstairs.1);
-        self.map.tiles[up_idx] = TileType::UpStairs;
-        self.map.take_snapshot();
-
-        // Now we build a noise map for use in spawning entities later
-        self.map.regions = generate_voronoi_spawn_regions(&self.map, &mut rng);
-
         self.map.clone()
     }
 }
@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::map::Map;
+
+/// MasterDungeonMap remembers every level the player has left, keyed by depth, so that going
+/// back to a level finds it as it was rather than building a new one.
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct MasterDungeonMap {
+    maps: BTreeMap<i32, Map>,
+}
+
+impl MasterDungeonMap {
+    pub fn new() -> MasterDungeonMap {
+        MasterDungeonMap { maps: BTreeMap::new() }
+    }
+
+    /// Stores a copy of map under its depth, replacing whatever was kept for that depth.
+    pub fn store_map(&mut self, map: &Map) {
+        self.maps.insert(map.depth, map.clone());
+    }
+
+    /// Returns the stored level for depth, if the player has been there. tile_content is not
+    /// saved with the game, so it is sized again here for the MapIndexingSystem to fill.
+    pub fn get_map(&self, depth: i32) -> Option<Map> {
+        self.maps.get(&depth).map(|map| {
+            let mut map = map.clone();
+            map.tile_content = vec![Vec::new(); (map.width * map.height) as usize];
+            map
+        })
+    }
+}
@@ -1,32 +1,66 @@
 use rltk::{Algorithm2D, BaseMap, FontCharType, Point, Rltk, RGB};
 use serde::{Deserialize, Serialize};
 use specs::*;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashSet};
 
 use crate::SHOW_MAPGEN_VISUALIZER;
 use super::common::*;
 use super::rect::Rect;
+use crate::systems::ui_system::camera::Camera;
 
 /// TileType enum to declare the structural components of the Map.
-#[derive(Hash, Eq, PartialEq, Serialize, Deserialize, Copy, Clone)]
+#[derive(Hash, Eq, PartialEq, Serialize, Deserialize, Copy, Clone, Debug)]
 pub enum TileType {
     Wall,
     Floor,
     DownStairs,
     UpStairs,
+    ShallowWater,
+    DeepWater,
+    Lava,
+    Bridge,
+    Grass,
+    Gravel,
+    Rubble,
+}
+
+impl TileType {
+    /// Whether anything can walk onto a tile of this type. Deep water can be seen across but
+    /// not waded.
+    pub fn is_walkable(self) -> bool {
+        !matches!(self, TileType::Wall | TileType::DeepWater)
+    }
+
+    pub fn is_opaque(self) -> bool {
+        self == TileType::Wall
+    }
+
+    /// How much walking onto a tile of this type costs, compared to bare floor. Pathing prefers
+    /// cheap tiles, so creatures keep out of the water and well away from lava when they can.
+    pub fn movement_cost(self) -> f32 {
+        match self {
+            TileType::Grass => 1.1,
+            TileType::ShallowWater | TileType::Rubble => 2.0,
+            TileType::Lava => 10.0,
+            _ => 1.0,
+        }
+    }
 }
 
 #[derive(Default, Serialize, Deserialize, Clone)]
 pub struct Map {
     pub tiles: Vec<TileType>,
     pub rooms: Vec<Rect>,
-    pub regions: HashMap<i32, Vec<usize>>,
+    pub regions: BTreeMap<i32, Vec<usize>>,
     pub blocked: Vec<bool>,
     pub width: i32,
     pub height: i32,
     pub revealed_tiles: Vec<bool>,
     pub visible_tiles: Vec<bool>,
     pub depth: i32,
+    /// The run seed this Map was generated from. Together with depth it reproduces the level.
+    #[serde(default)]
+    pub seed: u64,
     pub blood_stains: HashSet<usize>,
     pub magic_map: Vec<bool>,
     pub upstairs: (i32, i32),
@@ -34,23 +68,59 @@ pub struct Map {
     #[serde(skip_serializing)]
     #[serde(skip_deserializing)]
     pub tile_content: Vec<Vec<Entity>>,
+    /// Entities a builder placed by hand, as (tile index, raw name) pairs, for spawn_level to
+    /// create along with the random spawns.
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub spawn_list: Vec<(usize, String)>,
+    /// Tiles that cannot be seen through although they are not walls, such as those with a
+    /// closed Door. Kept up to date by the VisibilitySystem.
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub view_blocked: HashSet<usize>,
+    /// Tiles with a locked Door, which no path leads through. Kept up to date by the
+    /// MapIndexingSystem.
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub locked: HashSet<usize>,
     pub history: Vec<Vec<TileType>>,
     pub history_count: usize,
 }
 
 impl Map {
 
+    /// Translates from Cartesian Coordinates to the Linear Vector that the tiles, and
+    /// everything kept per tile, are stored under.
+    pub fn xy_idx(&self, x: i32, y: i32) -> usize {
+        if x < 0 || y < 0 { return 0; }
+        (y * self.width + x) as usize
+    }
+
     pub fn get_upstairs(&self) -> (i32, i32) {
         self.upstairs
     }
 
+    /// Where the down stairs are, for placing a player who climbs back up to this level.
+    pub fn get_downstairs(&self) -> Option<(i32, i32)> {
+        self.tiles
+            .iter()
+            .position(|tile| *tile == TileType::DownStairs)
+            .map(|idx| (idx as i32 % self.width, idx as i32 / self.width))
+    }
+
+    /// Whether the tile at idx stops sight, and anything shot or thrown: a wall, or a closed
+    /// Door (see view_blocked).
+    pub fn is_opaque(&self, idx: usize) -> bool {
+        self.tiles[idx].is_opaque() || self.view_blocked.contains(&idx)
+    }
+
     pub fn is_blocked(&self, x: i32, y: i32) -> bool {
-        let idx = xy_idx(x, y);
+        let idx = self.xy_idx(x, y);
         self.blocked[idx]
     }
 
     pub fn is_magic_mapped(&self, x: i32, y: i32) -> bool {
-        let idx = xy_idx(x, y);
+        let idx = self.xy_idx(x, y);
         return self.magic_map[idx];
     }
     
@@ -69,7 +139,7 @@ impl Map {
     }
 
     pub fn reveal_me (&mut self, x: i32, y: i32) {
-        let idx = xy_idx(x, y);
+        let idx = self.xy_idx(x, y);
         if self. is_tile_revealable(x, y) {
             self.revealed_tiles[idx] = true;
         }
@@ -87,7 +157,7 @@ impl Map {
         for (index, room) in self.rooms.iter().enumerate() {
             for x in room.x1..=room.x2 {
                 for y in room.y1..=room.y2 {
-                    let idx = xy_idx(x, y);
+                    let idx = self.xy_idx(x, y);
                     region.push(idx);
                 }
             }
@@ -97,14 +167,7 @@ impl Map {
 
     pub fn populate_blocked(&mut self) {
         for (i, tile) in self.tiles.iter_mut().enumerate() {
-            match tile {
-                TileType::Wall => {
-                    self.blocked[i as usize] = true;
-                }
-                _ => {
-                    self.blocked[i as usize] = false;
-                }
-            }
+            self.blocked[i] = !tile.is_walkable();
         }
     }
 
@@ -112,9 +175,8 @@ impl Map {
         if x < 1 || x > self.width - 1 || y < 1 || y > self.height - 1 {
             return false;
         }
-        let idx = xy_idx(x, y);
-        // !self.blocked[idx]
-        self.tiles[idx] != TileType::Wall
+        let idx = self.xy_idx(x, y);
+        self.tiles[idx].is_walkable() && !self.locked.contains(&idx)
     }
 
     pub fn take_snapshot (&mut self) {
@@ -136,7 +198,9 @@ impl Map {
         return None;
     }
 
-    pub fn draw_map(&self, ident: usize, ctx: &mut Rltk) {
+    /// Draws the part of the Map the Camera looks at. An ident above zero draws that snapshot
+    /// from the history instead.
+    pub fn draw_map(&self, ident: usize, camera: &Camera, ctx: &mut Rltk) {
         let tiles;
         if ident == 0 {
             tiles = &self.tiles;
@@ -144,9 +208,12 @@ impl Map {
             if ident - 1 > self.history.len() { return; }
             tiles = &self.history[ident - 1];
         }
-        let mut y = 0;
-        let mut x = 0;
         for (idx, tile) in tiles.iter().enumerate() {
+            let x = idx as i32 % self.width;
+            let y = idx as i32 / self.width;
+            let Some(screen) = camera.to_screen(x, y) else {
+                continue;
+            };
             // Render a tile depending upon the tile type
             if self.revealed_tiles[idx] || ident > 0 {
                 let mut fg: RGB;
@@ -173,6 +240,36 @@ impl Map {
                         fg = RGB::from_f32(0.0, 0.75, 0.0);
                         glyph = rltk::to_cp437('<');
                     }
+                    TileType::ShallowWater => {
+                        fg = RGB::from_f32(0.3, 0.6, 1.0);
+                        glyph = rltk::to_cp437('~');
+                    }
+                    TileType::DeepWater => {
+                        fg = RGB::from_f32(0.1, 0.2, 1.0);
+                        bg = RGB::from_f32(0.0, 0.0, 0.3);
+                        glyph = rltk::to_cp437('≈');
+                    }
+                    TileType::Lava => {
+                        fg = RGB::from_f32(1.0, 0.9, 0.0);
+                        bg = RGB::from_f32(0.6, 0.1, 0.0);
+                        glyph = rltk::to_cp437('≈');
+                    }
+                    TileType::Bridge => {
+                        fg = RGB::from_f32(0.6, 0.4, 0.2);
+                        glyph = rltk::to_cp437('=');
+                    }
+                    TileType::Grass => {
+                        fg = RGB::from_f32(0.0, 0.6, 0.0);
+                        glyph = rltk::to_cp437('"');
+                    }
+                    TileType::Gravel => {
+                        fg = RGB::from_f32(0.6, 0.6, 0.6);
+                        glyph = rltk::to_cp437(';');
+                    }
+                    TileType::Rubble => {
+                        fg = RGB::from_f32(0.5, 0.4, 0.3);
+                        glyph = rltk::to_cp437('%');
+                    }
                 }
                 if ident == 0 {
                     if self.blood_stains.contains(&idx) {
@@ -183,13 +280,7 @@ impl Map {
                         bg = RGB::from_f32(0.0, 0.0, 0.0);
                     }
                 }
-                ctx.set(x, y, fg, bg, glyph);
-            }
-            // Move the coordinates
-            x += 1;
-            if x > 79 {
-                x = 0;
-                y += 1;
+                ctx.set(screen.x, screen.y, fg, bg, glyph);
             }
         }
     }
@@ -209,7 +300,7 @@ impl Map {
             mask += 8;
         }
 
-        if x >= 0 && x <= self.width - 2 && y >= 1 && y <= self.width - 2 {
+        if x >= 0 && x <= self.width - 2 && y >= 1 && y <= self.height - 2 {
             if self.is_revealed_and_wall(x, y - 1) {
                 mask += 1;
             }
@@ -245,28 +336,29 @@ impl Map {
     }
 
     fn is_revealed_and_wall(&self, x: i32, y: i32) -> bool {
-        let idx = xy_idx(x, y);
-        if idx > (self.width * self.height) as usize {
+        if x < 0 || x >= self.width || y < 0 || y >= self.height {
             return false;
         }
+        let idx = self.xy_idx(x, y);
         self.tiles[idx] == TileType::Wall && self.revealed_tiles[idx]
     }
 
-    pub fn new(new_depth: i32) -> Map {
-
-        let width: i32 = 80;
-        let height: i32 = 43;
+    pub fn new(new_depth: i32, width: i32, height: i32) -> Map {
         let mut map = Map {
             tiles: vec![TileType::Wall; (width * height) as usize],
             rooms: Vec::new(),
-            regions: HashMap::new(),
+            regions: BTreeMap::new(),
             blocked: vec![false; (width * height) as usize],
             width,
             height,
             revealed_tiles: vec![false; (width * height) as usize],
             visible_tiles: vec![false; (width * height) as usize],
             tile_content: vec![Vec::new(); (width * height) as usize],
+            spawn_list: Vec::new(),
+            view_blocked: HashSet::new(),
+            locked: HashSet::new(),
             depth: new_depth,
+            seed: 0,
             blood_stains: HashSet::new(),
             magic_map: vec![false; (width * height) as usize],
             upstairs: (0, 0),
@@ -282,40 +374,29 @@ impl Map {
 
 impl BaseMap for Map {
     fn is_opaque(&self, idx: usize) -> bool {
-        self.tiles[idx] == TileType::Wall
+        Map::is_opaque(self, idx)
     }
 
     fn get_available_exits(&self, idx: usize) -> rltk::SmallVec<[(usize, f32); 10]> {
         let mut exits = rltk::SmallVec::new();
         let x = idx as i32 % self.width;
         let y = idx as i32 / self.width;
-        let w = self.width as usize;
-        // Cardinal directions
-        if self.is_exit_valid(x - 1, y) {
-            exits.push((idx - 1, 1.0))
-        };
-        if self.is_exit_valid(x + 1, y) {
-            exits.push((idx + 1, 1.0))
-        };
-        if self.is_exit_valid(x, y - 1) {
-            exits.push((idx - w, 1.0))
-        };
-        if self.is_exit_valid(x, y + 1) {
-            exits.push((idx + w, 1.0))
+        let mut add_exit = |x: i32, y: i32, distance: f32| {
+            if self.is_exit_valid(x, y) {
+                let exit = self.xy_idx(x, y);
+                exits.push((exit, distance * self.tiles[exit].movement_cost()));
+            }
         };
+        // Cardinal directions
+        add_exit(x - 1, y, 1.0);
+        add_exit(x + 1, y, 1.0);
+        add_exit(x, y - 1, 1.0);
+        add_exit(x, y + 1, 1.0);
         // Diagonals
-        if self.is_exit_valid(x - 1, y - 1) {
-            exits.push(((idx - w) - 1, 1.45));
-        }
-        if self.is_exit_valid(x + 1, y - 1) {
-            exits.push(((idx - w) + 1, 1.45));
-        }
-        if self.is_exit_valid(x - 1, y + 1) {
-            exits.push(((idx + w) - 1, 1.45));
-        }
-        if self.is_exit_valid(x + 1, y + 1) {
-            exits.push(((idx + w) + 1, 1.45));
-        }
+        add_exit(x - 1, y - 1, 1.45);
+        add_exit(x + 1, y - 1, 1.45);
+        add_exit(x - 1, y + 1, 1.45);
+        add_exit(x + 1, y + 1, 1.45);
 
         exits
     }
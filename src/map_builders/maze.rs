@@ -1,19 +1,16 @@
 use rltk::RandomNumberGenerator;
 
-use super::common::*;
 use super::map::*;
 use super::{BuilderSettings, MapBuilder};
 
-use crate::spawner::components::Position;
-
 #[derive(Clone)]
 pub struct MazeBuilder {
     pub map: Map,
 }
 
 impl MapBuilder for MazeBuilder {
-    fn new(new_depth: i32) -> Box<dyn MapBuilder> {
-        Box::new(MazeBuilder { map: Map::new(new_depth) })
+    fn start(new_depth: i32, width: i32, height: i32) -> Box<dyn MapBuilder> {
+        Box::new(MazeBuilder { map: Map::new(new_depth, width, height) })
     }
 
     #[allow(unused_variables)]
@@ -21,35 +18,12 @@ impl MapBuilder for MazeBuilder {
         return Box::new(self.clone());
     }
 
-    #[allow(unused_variables)]
-    fn add(&mut self, map: Map) -> Box<dyn MapBuilder> {
-        return Box::new(self.to_owned());
-    }
-
-    fn build(&mut self) -> Map {
-        let mut rng = RandomNumberGenerator::new();
-
+    fn build(&mut self, rng: &mut RandomNumberGenerator) -> Map {
         // Maze gen
-        let mut maze = Grid::new((self.map.width / 2)-2, (self.map.height / 2)-2, &mut rng);
+        let mut maze = Grid::new((self.map.width / 2)-2, (self.map.height / 2)-2, rng);
         maze.generate_maze(self);
-
-        let starting_position = Position::new( 2, 2 );
-        let start_idx = xy_idx(starting_position.get_x(), starting_position.get_y());
         self.map.take_snapshot();
 
-        // Find all tiles we can reach from the starting point
-        let exit_tile = remove_unreachable_areas_returning_most_distant(&mut self.map, start_idx);
-        self.map.take_snapshot();
-
-        // Place the stairs
-        self.map.tiles[exit_tile] = TileType::DownStairs;
-        self.map.upstairs = (starting_position.get_x(), starting_position.get_y() );
-        self.map.tiles[start_idx] = TileType::UpStairs;
-        self.map.take_snapshot();
-
-        // Now we build a noise map for use in spawning entities later
-        self.map.regions = generate_voronoi_spawn_regions(&self.map, &mut rng);
-
         self.map.clone()
     }
 }
@@ -222,7 +196,7 @@ impl<'a> Grid<'a> {
         for cell in self.cells.iter() {
             let x = cell.column + 1;
             let y = cell.row + 1;
-            let idx = xy_idx(x * 2, y * 2);
+            let idx = map.xy_idx(x * 2, y * 2);
 
             map.tiles[idx] = TileType::Floor;
             if !cell.walls[TOP] { map.tiles[idx - map.width as usize] = TileType::Floor }
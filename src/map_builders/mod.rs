@@ -2,34 +2,54 @@ use rltk::RandomNumberGenerator;
 
 /// Provides common functions used in the generation of a Map.
 pub mod common;
+/// dungeon keeps the levels the player has already visited.
+pub mod dungeon;
 /// Contains the core structues to store and create a Map.
 pub mod map;
 /// Rect is a simple helper to create rectangluar spaces within the Map.
 pub mod rect;
 
+mod area_starting_point;
 mod bsp_dungeon_builder;
 mod bsp_interior;
 mod cellular_automata;
+mod cull_unreachable;
+mod distant_exit;
+mod door_placement;
 mod drunkards_walk;
 mod dla;
 mod maze;
-//mod prefab_builder;
+mod prefab_builder;
+mod river;
+mod room_based_spawn_regions;
+mod room_based_stairs;
 mod simple_map;
+mod terrain_dressing;
 mod voronoi;
+mod voronoi_spawn_regions;
 mod wave_function_collapse;
 
 use crate::spawner::random_table::*;
 use common::Symmetry;
 use map::Map;
+use area_starting_point::{AreaStartingPoint, XStart, YStart};
 use bsp_dungeon_builder::BSPDungeonBuilder;
 use bsp_interior::BSPInteriorBuilder;
 use cellular_automata::CellularAutomataBuilder;
+use cull_unreachable::CullUnreachable;
+use distant_exit::DistantExit;
+use door_placement::DoorPlacement;
 use drunkards_walk::DrunkardsWalkBuilder;
 use dla::DLABuilder;
 use maze::MazeBuilder;
-//use prefab_builder::prefab_levels;
+use prefab_builder::{prefab_levels, PrefabBuilder};
+use river::River;
+use room_based_spawn_regions::RoomBasedSpawnRegions;
+use room_based_stairs::RoomBasedStairs;
 use simple_map::SimpleMapBuilder;
+use terrain_dressing::TerrainDressing;
 use voronoi::VoronoiBuilder;
+use voronoi_spawn_regions::VoronoiSpawnRegions;
 use wave_function_collapse::WaveFunctionCollapseBuilder;
 
 #[derive(PartialEq, Copy, Clone)]
@@ -41,14 +61,14 @@ pub enum DrunkSpawnMode {
 #[derive(PartialEq, Copy, Clone)]
 pub enum DLAAlgorithm { WalkInwards, WalkOutwards, CentralAttractor }
 
-/*
+/// Tells the PrefabBuilder which hand made level to load: a REX Paint file registered with
+/// Rltk's resources, or one of the ASCII levels in prefab_levels.
 #[derive(PartialEq, Clone)]
-#[allow(dead_code)]
 pub enum PrefabMode { 
     RexLevel{ template : &'static str },
     Constant{ level : prefab_levels::PrefabLevel },
     None
-}*/
+}
 
 #[derive(Clone)]
 pub struct BuilderSettings {
@@ -58,165 +78,258 @@ pub struct BuilderSettings {
     pub algorithm: Option<DLAAlgorithm>,
     pub symmetry: Option<Symmetry>,
     pub brush_size: Option<i32>,
-//    pub mode : PrefabMode,
+    pub mode : PrefabMode,
 }
 
 /// Define a standard interface for creation maps.
-trait MapBuilder {
-    fn build(&mut self) -> map::Map;
+///
+/// Builders never create their own RandomNumberGenerator; all randomness comes from the rng
+/// handed to build() so that a seeded rng always produces the same Map.
+pub trait MapBuilder {
+    fn build(&mut self, rng: &mut RandomNumberGenerator) -> map::Map;
     fn with_settings(&mut self, settings: BuilderSettings) -> Box<dyn MapBuilder>;
-    fn add(&mut self, map: Map) -> Box<dyn MapBuilder>;
-    fn new(new_depth: i32) -> Box<dyn MapBuilder>
+    fn start(new_depth: i32, width: i32, height: i32) -> Box<dyn MapBuilder>
     where
         Self: Sized;
 }
 
-pub fn build_random_map(new_depth: i32) -> map::Map {
-    let mut rng = RandomNumberGenerator::new();
+/// A meta builder reworks a Map that an earlier builder produced: placing stairs, culling
+/// unreachable areas, dividing spawn regions and so on.
+pub trait MetaMapBuilder {
+    fn build_map(&mut self, map: &mut Map, rng: &mut RandomNumberGenerator);
+}
+
+/// BuilderChain assembles a level from parts: one MapBuilder lays out the initial Map and
+/// every MetaMapBuilder then works on it, in the order they were added.
+pub struct BuilderChain {
+    depth: i32,
+    starter: Option<Box<dyn MapBuilder>>,
+    builders: Vec<Box<dyn MetaMapBuilder>>,
+}
+
+impl BuilderChain {
+    pub fn new(new_depth: i32) -> BuilderChain {
+        BuilderChain {
+            depth: new_depth,
+            starter: None,
+            builders: Vec::new(),
+        }
+    }
+
+    pub fn start_with(mut self, starter: Box<dyn MapBuilder>) -> BuilderChain {
+        if self.starter.is_some() {
+            panic!("A BuilderChain can only have one starting builder");
+        }
+        self.starter = Some(starter);
+        self
+    }
+
+    pub fn with(mut self, meta_builder: Box<dyn MetaMapBuilder>) -> BuilderChain {
+        self.builders.push(meta_builder);
+        self
+    }
+
+    pub fn build(&mut self, rng: &mut RandomNumberGenerator) -> Map {
+        let mut map = match self.starter.as_mut() {
+            Some(starter) => starter.build(rng),
+            None => panic!("A BuilderChain needs a starting builder for depth {}", self.depth),
+        };
+        for meta_builder in self.builders.iter_mut() {
+            meta_builder.build_map(&mut map, rng);
+        }
+
+        map
+    }
+}
+
+/// Mixes the run seed with the depth so that every level of a run gets its own,
+/// reproducible, random stream.
+pub fn level_seed(seed: u64, depth: i32) -> u64 {
+    seed ^ (depth as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+}
+
+/// Builds the Map for new_depth of the run started with seed. The same (seed, depth) pair
+/// always yields the identical Map.
+pub fn build_random_map(new_depth: i32, seed: u64) -> map::Map {
+    let mut rng = RandomNumberGenerator::seeded(level_seed(seed, new_depth));
     let table = map_table();
-    let map;
     let mut wave_possible = true;
+    let mut room_based = false;
+    let mut prefab = false;
+    let mut start = (XStart::Center, YStart::Center);
 
-    match table.roll(&mut rng).as_ref() {
+    let builder = table.roll(&mut rng);
+    let (width, height) = map_size(&builder, &mut rng);
+
+    let starter = match builder.as_ref() {
         "Simple Map" => {
             wave_possible = false;
-            let mut builder = SimpleMapBuilder::new(new_depth);
-            map = builder.build();
+            room_based = true;
+            SimpleMapBuilder::start(new_depth, width, height)
         }
         "BSP Dungeon Builder" => {
-            let mut builder = BSPDungeonBuilder::new(new_depth);
-            map = builder.build();
+            room_based = true;
+            BSPDungeonBuilder::start(new_depth, width, height)
         }
         "BSP Interior" => {
-            let mut builder = BSPInteriorBuilder::new(new_depth);
-            map = builder.build();
-        }
-        "Cellular Automata" => {
-            let mut builder = CellularAutomataBuilder::new(new_depth);
-            map = builder.build();
-        }
-        "Drunkards Walk - Open Area" => {
-            let mut builder = DrunkardsWalkBuilder::new(new_depth);
-            map = builder.build();
-        }
-        "Drunkards Walk - Open Halls" => {
-            let mut builder = DrunkardsWalkBuilder::new(new_depth).with_settings(BuilderSettings {
-                spawn_mode: Some(DrunkSpawnMode::Random),
-                lifetime: Some(400),
-                floor_percent: Some(0.5),
-                algorithm: None,
-                symmetry: Some(Symmetry::None),
-                brush_size: Some(1),
-                //mode: PrefabMode::None,
-            });
-            map = builder.build();
-        }
-        "Drunkards Walk - Winding Passages" => {
-            let mut builder = DrunkardsWalkBuilder::new(new_depth).with_settings(BuilderSettings {
-                spawn_mode: Some(DrunkSpawnMode::Random),
-                lifetime: Some(100),
-                floor_percent: Some(0.4),
-                algorithm: None,
-                symmetry: Some(Symmetry::None),
-                brush_size: Some(1),
-                //mode: PrefabMode::None,
-            });
-            map = builder.build();
-        }
-        "Drunkards Walk - Fat Passages" => {
-            let mut builder = DrunkardsWalkBuilder::new(new_depth).with_settings(BuilderSettings {
-                spawn_mode: Some(DrunkSpawnMode::Random),
-                lifetime: Some(150),
-                floor_percent: Some(0.45),
-                algorithm: None,
-                symmetry: Some(Symmetry::None),
-                brush_size: Some(2),
-                //mode: PrefabMode::None,
-            });
-            map = builder.build();
-        }
-        "Drunkards Walk - Fearful Symmetry" => {
-            let mut builder = DrunkardsWalkBuilder::new(new_depth).with_settings(BuilderSettings {
-                spawn_mode: Some(DrunkSpawnMode::Random),
-                lifetime: Some(100),
-                floor_percent: Some(0.4),
-                algorithm: None,
-                symmetry: Some(Symmetry::Both),
-                brush_size: Some(2),
-                //mode: PrefabMode::None,
-            });
-            map = builder.build();
+            room_based = true;
+            BSPInteriorBuilder::start(new_depth, width, height)
         }
+        "Cellular Automata" => CellularAutomataBuilder::start(new_depth, width, height),
+        "Drunkards Walk - Open Area" => DrunkardsWalkBuilder::start(new_depth, width, height),
+        "Drunkards Walk - Open Halls" => DrunkardsWalkBuilder::start(new_depth, width, height).with_settings(BuilderSettings {
+            spawn_mode: Some(DrunkSpawnMode::Random),
+            lifetime: Some(400),
+            floor_percent: Some(0.5),
+            algorithm: None,
+            symmetry: Some(Symmetry::None),
+            brush_size: Some(1),
+            mode: PrefabMode::None,
+        }),
+        "Drunkards Walk - Winding Passages" => DrunkardsWalkBuilder::start(new_depth, width, height).with_settings(BuilderSettings {
+            spawn_mode: Some(DrunkSpawnMode::Random),
+            lifetime: Some(100),
+            floor_percent: Some(0.4),
+            algorithm: None,
+            symmetry: Some(Symmetry::None),
+            brush_size: Some(1),
+            mode: PrefabMode::None,
+        }),
+        "Drunkards Walk - Fat Passages" => DrunkardsWalkBuilder::start(new_depth, width, height).with_settings(BuilderSettings {
+            spawn_mode: Some(DrunkSpawnMode::Random),
+            lifetime: Some(150),
+            floor_percent: Some(0.45),
+            algorithm: None,
+            symmetry: Some(Symmetry::None),
+            brush_size: Some(2),
+            mode: PrefabMode::None,
+        }),
+        "Drunkards Walk - Fearful Symmetry" => DrunkardsWalkBuilder::start(new_depth, width, height).with_settings(BuilderSettings {
+            spawn_mode: Some(DrunkSpawnMode::Random),
+            lifetime: Some(100),
+            floor_percent: Some(0.4),
+            algorithm: None,
+            symmetry: Some(Symmetry::Both),
+            brush_size: Some(2),
+            mode: PrefabMode::None,
+        }),
         "Maze" => {
             wave_possible = false;
-            let mut builder = MazeBuilder::new(new_depth);
-            map = builder.build();
-        }
-        "DLA - Walk Inwards" => {
-            let mut builder = DLABuilder::new(new_depth).with_settings(BuilderSettings {
-                spawn_mode: None,
-                lifetime: None,
-                floor_percent: Some(0.25),
-                algorithm: Some(DLAAlgorithm::WalkInwards),
-                symmetry: Some(Symmetry::None),
-                brush_size: Some(1),
-                //mode: PrefabMode::None,
-            });
-            map = builder.build();
+            start = (XStart::Left, YStart::Top);
+            MazeBuilder::start(new_depth, width, height)
         }
-        "DLA - Walk Outwards" => {
-            let mut builder = DLABuilder::new(new_depth).with_settings(BuilderSettings {
-                spawn_mode: None,
-                lifetime: None,
-                floor_percent: Some(0.25),
-                algorithm: Some(DLAAlgorithm::WalkOutwards),
-                symmetry: Some(Symmetry::None),
-                brush_size: Some(2),
-                //mode: PrefabMode::None,
-            });
-            map = builder.build();
-        }
-        "DLA - Central Attractor" => {
-            let mut builder = DLABuilder::new(new_depth).with_settings(BuilderSettings {
-                spawn_mode: None,
-                lifetime: None,
-                floor_percent: Some(0.25),
-                algorithm: Some(DLAAlgorithm::CentralAttractor),
-                symmetry: Some(Symmetry::None),
-                brush_size: Some(2),
-                //mode: PrefabMode::None,
-            });
-            map = builder.build();
+        "DLA - Walk Inwards" => DLABuilder::start(new_depth, width, height).with_settings(BuilderSettings {
+            spawn_mode: None,
+            lifetime: None,
+            floor_percent: Some(0.25),
+            algorithm: Some(DLAAlgorithm::WalkInwards),
+            symmetry: Some(Symmetry::None),
+            brush_size: Some(1),
+            mode: PrefabMode::None,
+        }),
+        "DLA - Walk Outwards" => DLABuilder::start(new_depth, width, height).with_settings(BuilderSettings {
+            spawn_mode: None,
+            lifetime: None,
+            floor_percent: Some(0.25),
+            algorithm: Some(DLAAlgorithm::WalkOutwards),
+            symmetry: Some(Symmetry::None),
+            brush_size: Some(2),
+            mode: PrefabMode::None,
+        }),
+        "DLA - Central Attractor" => DLABuilder::start(new_depth, width, height).with_settings(BuilderSettings {
+            spawn_mode: None,
+            lifetime: None,
+            floor_percent: Some(0.25),
+            algorithm: Some(DLAAlgorithm::CentralAttractor),
+            symmetry: Some(Symmetry::None),
+            brush_size: Some(2),
+            mode: PrefabMode::None,
+        }),
+        "DLA - Insectoid" => DLABuilder::start(new_depth, width, height).with_settings(BuilderSettings {
+            spawn_mode: None,
+            lifetime: None,
+            floor_percent: Some(0.25),
+            algorithm: Some(DLAAlgorithm::CentralAttractor),
+            symmetry: Some(Symmetry::Horizontal),
+            brush_size: Some(2),
+            mode: PrefabMode::None,
+        }),
+        "Voronoi Map Builder" => VoronoiBuilder::start(new_depth, width, height),
+        "Prefab Level" => {
+            wave_possible = false;
+            prefab = true;
+            PrefabBuilder::start(new_depth, width, height)
         }
-        "DLA - Insectoid" => {
-            let mut builder = DLABuilder::new(new_depth).with_settings(BuilderSettings {
+        "REX Crypt" => {
+            wave_possible = false;
+            prefab = true;
+            PrefabBuilder::start(new_depth, width, height).with_settings(BuilderSettings {
                 spawn_mode: None,
                 lifetime: None,
-                floor_percent: Some(0.25),
-                algorithm: Some(DLAAlgorithm::CentralAttractor),
-                symmetry: Some(Symmetry::Horizontal),
-                brush_size: Some(2),
-                //mode: PrefabMode::None,
-            });
-            map = builder.build();
-        }
-        "Voronoi Map Builder" => {
-            let mut builder = VoronoiBuilder::new(new_depth);
-            map = builder.build();
+                floor_percent: None,
+                algorithm: None,
+                symmetry: None,
+                brush_size: None,
+                mode: PrefabMode::RexLevel{ template: prefab_builder::CRYPT },
+            })
         }
         _ => {
-            let mut builder = SimpleMapBuilder::new(new_depth);
-            map = builder.build();
+            room_based = true;
+            SimpleMapBuilder::start(new_depth, width, height)
         }
-    }
+    };
 
-    if rng.roll_dice(1, 3)==1 && wave_possible {
-        return WaveFunctionCollapseBuilder::new(new_depth).add(map.clone()).build();
+    let mut chain = BuilderChain::new(new_depth).start_with(starter);
+    let remix = wave_possible && rng.roll_dice(1, 3) == 1;
+    if remix {
+        chain = chain.with(WaveFunctionCollapseBuilder::new());
+    }
+    // A remix throws the rooms away, so it needs the same finishing passes as a cave.
+    if prefab {
+        // Hand made levels already have their stairs and inhabitants.
+    } else if room_based && !remix {
+        chain = chain
+            .with(RoomBasedSpawnRegions::new())
+            .with(RoomBasedStairs::new());
+    } else {
+        chain = chain
+            .with(AreaStartingPoint::new(start.0, start.1))
+            .with(CullUnreachable::new())
+            .with(DistantExit::new())
+            .with(VoronoiSpawnRegions::new());
+    }
+    if !prefab && rng.roll_dice(1, 4) == 1 {
+        chain = chain.with(River::new());
+    }
+    if !prefab && rng.roll_dice(1, 3) == 1 {
+        chain = chain.with(PrefabBuilder::vaults());
+    }
+    if room_based && !remix {
+        chain = chain.with(DoorPlacement::new());
+    }
+    if !prefab {
+        chain = chain.with(TerrainDressing::new());
     }
 
+    let mut map = chain.build(&mut rng);
+    map.seed = seed;
+
     map
 }
 
+/// How big a Map the named builder lays out. Most levels fill the screen; caverns are
+/// sometimes far bigger than it, and a maze is sometimes only a small warren.
+fn map_size(builder: &str, rng: &mut RandomNumberGenerator) -> (i32, i32) {
+    let cavern = matches!(builder, "Cellular Automata" | "Drunkards Walk - Open Area") || builder.starts_with("DLA");
+    if cavern && rng.roll_dice(1, 3) == 1 {
+        (120, 80)
+    } else if builder == "Maze" && rng.roll_dice(1, 3) == 1 {
+        (40, 24)
+    } else {
+        (80, 43)
+    }
+}
+
 fn map_table() -> RandomTable {
     RandomTable::new()
         .add("BSP Dungeon Builder", 4)
@@ -234,4 +347,60 @@ fn map_table() -> RandomTable {
         .add("DLA - Central Attractor", 1)
         .add("DLA - Insectoid", 1)
         .add("Voronoi Map Builder", 1)
+        .add("Prefab Level", 1)
+        .add("REX Crypt", 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_and_depth_build_identical_maps() {
+        for seed in 1..=8 {
+            let first = build_random_map(3, seed);
+            let second = build_random_map(3, seed);
+
+            assert!(first.tiles == second.tiles, "seed {} produced different tiles", seed);
+            assert_eq!(first.upstairs, second.upstairs);
+            assert_eq!(first.regions, second.regions);
+            assert_eq!(first.seed, seed);
+        }
+    }
+
+    #[test]
+    fn every_level_has_reachable_stairs() {
+        for seed in 1..=30 {
+            let map = build_random_map(1, seed);
+            let up_idx = map.xy_idx(map.upstairs.0, map.upstairs.1);
+            assert!(map.tiles[up_idx] == map::TileType::UpStairs, "seed {} has no up stairs", seed);
+            assert!(!map.regions.is_empty() || !map.spawn_list.is_empty(), "seed {} has nothing to spawn", seed);
+
+            let down_idx = map.tiles.iter().position(|t| *t == map::TileType::DownStairs);
+            let down_idx = down_idx.unwrap_or_else(|| panic!("seed {} has no down stairs", seed));
+            let dijkstra_map = rltk::DijkstraMap::new(map.width as usize, map.height as usize, &[up_idx], &map, 500.0);
+            assert!(dijkstra_map.map[down_idx] < f32::MAX, "seed {} has unreachable down stairs", seed);
+        }
+    }
+
+    #[test]
+    fn maps_come_in_their_own_sizes() {
+        let mut sizes = Vec::new();
+        for seed in 1..=40 {
+            let map = build_random_map(2, seed);
+            assert_eq!(map.tiles.len(), (map.width * map.height) as usize, "seed {}", seed);
+            assert_eq!(map.revealed_tiles.len(), map.tiles.len(), "seed {}", seed);
+            let (x, y) = map.upstairs;
+            assert!(x > 0 && x < map.width - 1 && y > 0 && y < map.height - 1, "seed {}", seed);
+            sizes.push((map.width, map.height));
+        }
+        assert!(sizes.contains(&(80, 43)));
+        assert!(sizes.iter().any(|size| *size != (80, 43)));
+    }
+
+    #[test]
+    fn level_seed_differs_by_depth() {
+        assert_ne!(level_seed(42, 1), level_seed(42, 2));
+        assert_eq!(level_seed(42, 7), level_seed(42, 7));
+    }
 }
@@ -1,54 +1,56 @@
-//use rltk::RandomNumberGenerator;
-//use specs::prelude::*;
+use rltk::RandomNumberGenerator;
+use std::sync::Once;
 
 pub mod prefab_levels;
+pub mod prefab_rooms;
 
-use crate::map_builders::common::xy_idx;
-use crate::spawner::components::Position;
 use super::map::{Map, TileType};
-use super::{BuilderSettings, MapBuilder, PrefabMode};
+use super::{BuilderSettings, MapBuilder, MetaMapBuilder, PrefabMode};
 
+rltk::embedded_resource!(CRYPT_LEVEL, "../../../resources/crypt.xp");
+
+/// The REX Paint levels that can be loaded with PrefabMode::RexLevel.
+pub const CRYPT: &str = "resources/crypt.xp";
+
+static LINK_REX_LEVELS: Once = Once::new();
+
+/// XpFile::from_resource only finds files that have been linked into Rltk's resource store,
+/// so this is done once, before the first REX level is loaded.
+fn link_rex_levels() {
+    LINK_REX_LEVELS.call_once(|| {
+        rltk::link_resource!(CRYPT_LEVEL, CRYPT);
+    });
+}
+
+/// PrefabBuilder turns hand made templates into Maps. As a MapBuilder it loads a whole level
+/// chosen by PrefabMode; as a MetaMapBuilder (see vaults()) it stamps small prefab rooms into
+/// the open floor of a Map that another builder made.
 #[derive(Clone)]
 pub struct PrefabBuilder {
     map : Map,
     settings: BuilderSettings,
 }
 
-#[allow(unused_variables)]
 impl MapBuilder for PrefabBuilder {
-    fn build(&mut self) -> Map {
+    fn build(&mut self, _rng: &mut RandomNumberGenerator) -> Map {
         match self.settings.mode {
-            PrefabMode::RexLevel{template} => self.load_rex_map(&template),
+            PrefabMode::RexLevel{template} => self.load_rex_map(template),
             PrefabMode::Constant{level} => self.load_ascii_map(&level),
             PrefabMode::None => (),
         }
-    
-        // Find a starting point; start at the middle and walk left until we find an open tile
-        let mut starting_position = Position::new(self.map.width / 2, self.map.height / 2 );
-        let mut start_idx = xy_idx(starting_position.get_x(), starting_position.get_y());
-        while self.map.tiles[start_idx] != TileType::Floor {
-            starting_position.set_x(starting_position.get_x() - 1);
-            start_idx = xy_idx(starting_position.get_x(), starting_position.get_y());
-        }
         self.map.take_snapshot();
 
-        // TODO: Build Code goes here.
-
         self.map.clone()
     }
 
-    #[allow(unused_variables)]
     fn with_settings (&mut self, settings: BuilderSettings) -> Box<dyn MapBuilder> {
-        return Box::new(self.clone());
-    }
+        self.settings = settings;
 
-    #[allow(unused_variables)]
-    fn add(&mut self, map: Map) -> Box<dyn MapBuilder> {
-        return Box::new(self.to_owned());
+        Box::new(self.clone())
     }
 
-    fn new(new_depth: i32) -> Box<dyn MapBuilder> {
-        Box::new(PrefabBuilder { map: Map::new (new_depth),settings: BuilderSettings {
+    fn start(new_depth: i32, width: i32, height: i32) -> Box<dyn MapBuilder> {
+        Box::new(PrefabBuilder { map: Map::new(new_depth, width, height),settings: BuilderSettings {
             spawn_mode: None,
             lifetime: None,
             floor_percent: None,
@@ -60,81 +62,174 @@ impl MapBuilder for PrefabBuilder {
     }
 }
 
-impl PrefabBuilder {
+impl MetaMapBuilder for PrefabBuilder {
+    /// Places up to a few vaults that suit the depth. A vault only goes where it and a one tile
+    /// border around it are all floor, so it never cuts a level in two or covers the stairs.
+    fn build_map(&mut self, map: &mut Map, rng: &mut RandomNumberGenerator) {
+        let vaults: Vec<&prefab_rooms::PrefabRoom> = [&prefab_rooms::TRAPPED_CACHE, &prefab_rooms::GUARD_POST, &prefab_rooms::PANTRY]
+            .iter()
+            .copied()
+            .filter(|v| map.depth >= v.first_depth && map.depth <= v.last_depth)
+            .collect();
+        if vaults.is_empty() { return; }
 
-    fn char_to_map(&mut self, ch : char, idx: usize) -> Position {
-        let starting_position: Position;
-        match ch {
-            ' ' => self.map.tiles[idx] = TileType::Floor,
-            '#' => self.map.tiles[idx] = TileType::Wall,
-            '@' => {
-                let x = idx as i32 % self.map.width;
-                let y = idx as i32 / self.map.width;
-                self.map.tiles[idx] = TileType::Floor;
-                starting_position = Position::new(x as i32, y as i32);
-            }
-            '>' => self.map.tiles[idx] = TileType::DownStairs,
-            'g' => {
-                self.map.tiles[idx] = TileType::Floor;
-                self.spawns.push((idx, "Goblin".to_string()));
-            }
-            'o' => {
-                self.map.tiles[idx] = TileType::Floor;
-                self.spawns.push((idx, "Orc".to_string()));
-            }
-            '^' => {
-                self.map.tiles[idx] = TileType::Floor;
-                self.spawns.push((idx, "Bear Trap".to_string()));
-            }
-            '%' => {
-                self.map.tiles[idx] = TileType::Floor;
-                self.spawns.push((idx, "Rations".to_string()));
-            }
-            '!' => {
-                self.map.tiles[idx] = TileType::Floor;
-                self.spawns.push((idx, "Health Potion".to_string()));
+        let n_vaults = i32::min(rng.roll_dice(1, 3), vaults.len() as i32);
+        let mut used_tiles: Vec<usize> = Vec::new();
+        for _i in 0..n_vaults {
+            let vault = vaults[(rng.roll_dice(1, vaults.len() as i32) - 1) as usize];
+            let (width, height) = (vault.width as i32, vault.height as i32);
+
+            // Collect every spot the vault would fit, then pick one of them.
+            let mut candidates: Vec<(i32, i32)> = Vec::new();
+            for y in 2 .. map.height - height - 2 {
+                for x in 2 .. map.width - width - 2 {
+                    let mut possible = true;
+                    for ty in y-1 ..= y+height {
+                        for tx in x-1 ..= x+width {
+                            let idx = map.xy_idx(tx, ty);
+                            if map.tiles[idx] != TileType::Floor || used_tiles.contains(&idx) {
+                                possible = false;
+                            }
+                        }
+                    }
+                    if possible { candidates.push((x, y)); }
+                }
             }
-            _ => {
-                rltk::console::log(format!("Unknown glyph loading map: {}", (ch as u8) as char));
+            if candidates.is_empty() { continue; }
+
+            let (x, y) = candidates[(rng.roll_dice(1, candidates.len() as i32) - 1) as usize];
+            let rows = template_rows(vault.template);
+            for ty in 0..height {
+                for tx in 0..width {
+                    let idx = map.xy_idx(x + tx, y + ty);
+                    let ch = rows.get(ty as usize).and_then(|row| row.get(tx as usize)).copied().unwrap_or(' ');
+                    char_to_map(map, ch, idx);
+                    used_tiles.push(idx);
+                }
             }
+            map.take_snapshot();
+        }
+
+        // Vaults bring their own inhabitants, so keep the random spawns out of them.
+        for region in map.regions.values_mut() {
+            region.retain(|idx| !used_tiles.contains(idx));
         }
-        starting_position
+        map.regions.retain(|_, region| !region.is_empty());
+    }
+}
+
+impl PrefabBuilder {
+    /// A meta builder that stamps prefab rooms into an existing Map.
+    pub fn vaults() -> Box<PrefabBuilder> {
+        Box::new(PrefabBuilder { map: Map::new(0, 0, 0), settings: BuilderSettings {
+            spawn_mode: None,
+            lifetime: None,
+            floor_percent: None,
+            algorithm: None,
+            symmetry: None,
+            brush_size: None,
+            mode: PrefabMode::None,
+        },})
     }
 
     fn load_rex_map(&mut self, path: &str) {
-        let xp_file = rltk::rex::XpFile::from_resource(path).unwrap();
-        let starting_position: Position;
+        link_rex_levels();
+        let xp_file = rltk::rex::XpFile::from_resource(path).expect("Unable to load REX level");
 
         for layer in &xp_file.layers {
             for y in 0..layer.height {
                 for x in 0..layer.width {
                     let cell = layer.get(x, y).unwrap();
                     if x < self.map.width as usize && y < self.map.height as usize {
-                        let idx = xy_idx(x as i32, y as i32);
+                        let idx = self.map.xy_idx(x as i32, y as i32);
                         // We're doing some nasty casting to make it easier to type things like '#' in the match
-                        starting_position = self.char_to_map(cell.ch as u8 as char, idx);
+                        char_to_map(&mut self.map, cell.ch as u8 as char, idx);
                     }
                 }
             }
         }
     }
-    
+
+    /// A hand drawn level is as big as it was drawn, whatever size of Map the builder was
+    /// created with.
     fn load_ascii_map(&mut self, level: &prefab_levels::PrefabLevel) {
-        // Start by converting to a vector, with newlines removed
-        let starting_position: Position;
-        let mut string_vec : Vec<char> = level.template.chars().filter(|a| *a != '\r' && *a !='\n').collect();
-        for c in string_vec.iter_mut() { if *c as u8 == 160u8 { *c = ' '; } }
-    
-        let mut i = 0;
-        for ty in 0..level.height {
-            for tx in 0..level.width {
+        self.map = Map::new(self.map.depth, level.width as i32, level.height as i32);
+        let rows = template_rows(level.template);
+        for (ty, row) in rows.iter().enumerate().take(level.height) {
+            for (tx, ch) in row.iter().enumerate().take(level.width) {
                 if tx < self.map.width as usize && ty < self.map.height as usize {
-                    let idx = xy_idx(tx as i32, ty as i32);
-                    starting_position = self.char_to_map(string_vec[i], idx);
+                    let idx = self.map.xy_idx(tx as i32, ty as i32);
+                    char_to_map(&mut self.map, *ch, idx);
                 }
-                i += 1;
             }
         }
     }
-    
+}
+
+/// Splits a template into rows of characters. The blank line that the raw string templates
+/// start with is dropped, and non-breaking spaces count as spaces.
+fn template_rows(template: &str) -> Vec<Vec<char>> {
+    template
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.chars().map(|c| if c as u32 == 160 { ' ' } else { c }).collect())
+        .collect()
+}
+
+/// Sets the tile at idx for one template character. Monster and item glyphs become floor with
+/// the matching raw queued in map.spawn_list.
+fn char_to_map(map: &mut Map, ch : char, idx: usize) {
+    let spawn = |map: &mut Map, name: &str| {
+        map.tiles[idx] = TileType::Floor;
+        map.spawn_list.push((idx, name.to_string()));
+    };
+    match ch {
+        ' ' => map.tiles[idx] = TileType::Floor,
+        '#' => map.tiles[idx] = TileType::Wall,
+        '@' => {
+            map.tiles[idx] = TileType::UpStairs;
+            map.upstairs = (idx as i32 % map.width, idx as i32 / map.width);
+        }
+        '>' => map.tiles[idx] = TileType::DownStairs,
+        'g' => spawn(map, "Goblin"),
+        'o' => spawn(map, "Orc"),
+        '^' => spawn(map, "Bear Trap"),
+        '%' => spawn(map, "Rations"),
+        '!' => spawn(map, "Healing Potion"),
+        _ => {
+            rltk::console::log(format!("Unknown glyph loading map: {}", ch));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefab_levels_place_stairs_and_spawns() {
+        let mut rng = RandomNumberGenerator::seeded(1);
+        let modes = [
+            PrefabMode::Constant{ level: prefab_levels::WFC_POPULATED },
+            PrefabMode::RexLevel{ template: CRYPT },
+        ];
+        for mode in modes.iter() {
+            let mut builder = PrefabBuilder::start(1, 80, 43).with_settings(BuilderSettings {
+                spawn_mode: None,
+                lifetime: None,
+                floor_percent: None,
+                algorithm: None,
+                symmetry: None,
+                brush_size: None,
+                mode: mode.clone(),
+            });
+            let map = builder.build(&mut rng);
+
+            assert!(map.upstairs != (0, 0));
+            assert!(map.tiles[map.xy_idx(map.upstairs.0, map.upstairs.1)] == TileType::UpStairs);
+            assert!(map.tiles.contains(&TileType::DownStairs));
+            assert!(map.spawn_list.iter().any(|(_, name)| name == "Goblin"));
+            assert!(map.spawn_list.iter().all(|(idx, _)| map.tiles[*idx] == TileType::Floor));
+        }
+    }
 }
@@ -1,3 +1,5 @@
+/// A complete hand drawn level. Every line of template is one row of the Map; see
+/// PrefabBuilder::char_to_map for what each character stands for.
 #[derive(PartialEq, Copy, Clone)]
 pub struct PrefabLevel {
     pub template : &'static str,
@@ -6,43 +8,7 @@ pub struct PrefabLevel {
 }
 
 pub const WFC_POPULATED : PrefabLevel = PrefabLevel{
-    template : LEVEL_MAP,
+    template : include_str!("../../../resources/wfc-populated.txt"),
     width: 80,
     height: 43
 };
-
-#[allow(dead_code)]
-const LEVEL_MAP : &str = 
-"
-###############################################################################
-#### ######### #    # #######       #########  ####    #####                ###
-#### ######### ###### #######   o   #########  #### ## #####                ###
-#                        ####       #########   ### ##         o            ###
-#### ######### ###       ####       #######         ## #####                ###
-#### ######### ###       ####       ####### #   ### ## #####                ###
-#### ######### ###       ####       ####### #######    #####     o          ###
-##          ## ###       ####       ####### ################                ###
-##          ## ###   o   ###### ########### #   ############                ###
-##          ## ###       ###### ###########     ###                         ###
-##    %                  ###### ########### #   ###   !   ##                ###
-##          ## ###              ######   ## #######       ##                ###
-##          ## ###       ## ### #####     # ########################      #####
-##          ## ###       ## ### #####     # #   ######################    #####
-### ## ####### ###### ##### ### ####          o ###########     ######    #####
-### ## ####### ###### ####   ## ####        #   #########         ###### ######
-######                  ####### ####            ######     !    !    ### #    #
-#####                     ##### ####        #   ######               ### ######
-####                            #####     # ##########               ### ######
-####           !           ### ######     # ##########      o##o     ### #   ##
-####                       ### #######   ## #   ######               ###   g ##
-####   ######### ########## %  ######## ###################     ######## ##   #
-### ### ######## ##########    ######## #################### ##########   #   #
-## ##### ######   #########    ########          ########### #######   # g#   #
-## #####           ###############      ###      ########### #######   ####   #
-## ##### ####       ############## ######## g  g ########### ####         # ^ #
-### ###^####         ############# ########      #####       ####      # g#   #
-####   ######       ###            ########      ##### g     ####   !  ####^^ #
-#!%^## ###  ##           ########## ########  gg                 g         # > #
-#!%^   ###  ###     ############### ########      ##### g     ####      # g#   #
-###############################################################################
-";
\ No newline at end of file
@@ -0,0 +1,56 @@
+/// A small vault that can be stamped into the open floor of a generated Map. Vaults only turn
+/// up between first_depth and last_depth.
+#[derive(PartialEq, Copy, Clone)]
+pub struct PrefabRoom {
+    pub template : &'static str,
+    pub width : usize,
+    pub height: usize,
+    pub first_depth: i32,
+    pub last_depth: i32
+}
+
+pub const TRAPPED_CACHE : PrefabRoom = PrefabRoom{
+    template : TRAPPED_CACHE_MAP,
+    width: 5,
+    height: 3,
+    first_depth: 0,
+    last_depth: 100
+};
+
+const TRAPPED_CACHE_MAP : &str = "
+     
+ ^!^ 
+     
+";
+
+pub const GUARD_POST : PrefabRoom = PrefabRoom{
+    template : GUARD_POST_MAP,
+    width: 6,
+    height: 5,
+    first_depth: 2,
+    last_depth: 100
+};
+
+const GUARD_POST_MAP : &str = "
+######
+#g %g#
+  o   
+#g !g#
+######
+";
+
+pub const PANTRY : PrefabRoom = PrefabRoom{
+    template : PANTRY_MAP,
+    width: 5,
+    height: 5,
+    first_depth: 0,
+    last_depth: 4
+};
+
+const PANTRY_MAP : &str = "
+## ##
+#%%%#
+ %!% 
+#%%%#
+## ##
+";
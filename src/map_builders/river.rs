@@ -0,0 +1,196 @@
+use rltk::RandomNumberGenerator;
+
+use super::map::{Map, TileType};
+use super::MetaMapBuilder;
+
+/// Runs a river across the Map, from one edge to the other. The course meanders at random and
+/// its banks are grown with cellular automata, so the river widens and narrows as it goes.
+/// Deep water in the middle cannot be waded, so bridges are thrown across it every so often;
+/// deeper down the river may be lava with gravel banks instead. The river only floods open
+/// ground: it runs underground wherever it meets a wall.
+pub struct River {}
+
+impl River {
+    pub fn new() -> Box<River> {
+        Box::new(River {})
+    }
+}
+
+/// The fewest steps between two bridges along the river.
+const BRIDGE_SPACING: usize = 10;
+
+impl MetaMapBuilder for River {
+    fn build_map(&mut self, map: &mut Map, rng: &mut RandomNumberGenerator) {
+        let lava = map.depth >= 3 && rng.roll_dice(1, 3) == 1;
+        let (deep, shallow) = if lava { (TileType::Lava, TileType::Gravel) } else { (TileType::DeepWater, TileType::ShallowWater) };
+
+        // Work along and across the river, whichever way it runs.
+        let vertical = rng.roll_dice(1, 2) == 1;
+        let (length, breadth) = if vertical { (map.height, map.width) } else { (map.width, map.height) };
+        let width = map.width;
+        let at = |along: i32, across: i32| (if vertical { along * width + across } else { across * width + along }) as usize;
+
+        let mut course = Vec::with_capacity(length as usize);
+        let mut across = rng.range(breadth / 4, breadth * 3 / 4);
+        for _along in 0..length {
+            course.push(across);
+            across = i32::clamp(across + rng.range(-1, 2), 3, breadth - 4);
+        }
+        let distance = |along: i32, across: i32| i32::abs(across - course[along as usize]);
+
+        // The middle of the river is always wet; the banks start as noise and are smoothed.
+        let mut wet = vec![false; map.tiles.len()];
+        for along in 1..length - 1 {
+            for across in 1..breadth - 1 {
+                let from_middle = distance(along, across);
+                wet[at(along, across)] = from_middle <= 1 || (from_middle <= 4 && rng.roll_dice(1, 100) <= 55);
+            }
+        }
+        for _i in 0..4 {
+            let mut next = wet.clone();
+            for along in 1..length - 1 {
+                for across in 1..breadth - 1 {
+                    let mut neighbours = 0;
+                    for delta_along in -1..=1 {
+                        for delta_across in -1..=1 {
+                            if (delta_along, delta_across) != (0, 0) && wet[at(along + delta_along, across + delta_across)] {
+                                neighbours += 1;
+                            }
+                        }
+                    }
+                    let idx = at(along, across);
+                    let from_middle = distance(along, across);
+                    next[idx] = from_middle <= 1 || (from_middle <= 4 && (neighbours >= 5 || (wet[idx] && neighbours >= 4)));
+                }
+            }
+            wet = next;
+        }
+
+        for along in 1..length - 1 {
+            for across in 1..breadth - 1 {
+                let idx = at(along, across);
+                if wet[idx] && map.tiles[idx] == TileType::Floor {
+                    map.tiles[idx] = if distance(along, across) <= 1 { deep } else { shallow };
+                }
+            }
+        }
+        map.take_snapshot();
+
+        // Bridge the deep middle wherever there is dry footing on both sides.
+        let mut last_bridge: Option<usize> = None;
+        for along in 1..length - 1 {
+            if last_bridge.is_some_and(|last| (along as usize) < last + BRIDGE_SPACING) {
+                continue;
+            }
+            let middle = course[along as usize];
+            let crossing: Vec<usize> = (middle - 1..=middle + 1).map(|across| at(along, across)).collect();
+            let footing = [at(along, middle - 2), at(along, middle + 2)];
+            if crossing.iter().all(|idx| map.tiles[*idx] == deep)
+                && footing.iter().all(|idx| map.tiles[*idx].is_walkable() && map.tiles[*idx] != TileType::Lava)
+            {
+                for idx in crossing {
+                    map.tiles[idx] = TileType::Bridge;
+                }
+                last_bridge = Some(along as usize);
+            }
+        }
+
+        // Lava can be walked through, but only at a cost no fresh character survives, so a lava
+        // river that still cuts off the way down gets a causeway across it, as near the middle
+        // of its course as will do.
+        if lava {
+            let mut alongs: Vec<i32> = (1..length - 1).collect();
+            alongs.sort_by_key(|along| i32::abs(along - length / 2));
+            for along in alongs {
+                if !stairs_cut_off(map) {
+                    break;
+                }
+                for across in 1..breadth - 1 {
+                    let idx = at(along, across);
+                    if map.tiles[idx] == TileType::Lava {
+                        map.tiles[idx] = TileType::Bridge;
+                    }
+                }
+            }
+        }
+
+        // If the bridges still leave the way down cut off, the river is no more than a ford.
+        if stairs_cut_off(map) {
+            for tile in map.tiles.iter_mut() {
+                match *tile {
+                    TileType::DeepWater => *tile = TileType::ShallowWater,
+                    TileType::Lava => *tile = TileType::Gravel,
+                    _ => {}
+                }
+            }
+        }
+
+        // Nothing is spawned into the river.
+        let tiles = &map.tiles;
+        for region in map.regions.values_mut() {
+            region.retain(|idx| tiles[*idx].is_walkable() && tiles[*idx] != TileType::Lava);
+        }
+        map.take_snapshot();
+    }
+}
+
+/// Whether the down stairs cannot be reached from the up stairs without wading through deep
+/// water or lava.
+fn stairs_cut_off(map: &Map) -> bool {
+    let mut walled = map.clone();
+    for tile in walled.tiles.iter_mut().filter(|tile| **tile == TileType::Lava) {
+        *tile = TileType::Wall;
+    }
+    let start = map.xy_idx(map.upstairs.0, map.upstairs.1);
+    let distances = rltk::DijkstraMap::new(map.width as usize, map.height as usize, &[start], &walled, 500.0).map;
+    map.get_downstairs().is_some_and(|(x, y)| distances[map.xy_idx(x, y)] == f32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map_builders::common::apply_room_to_map;
+    use crate::map_builders::rect::Rect;
+
+    #[test]
+    fn rivers_never_cut_off_the_stairs() {
+        let mut open = Map::new(5, 80, 43);
+        apply_room_to_map(&Rect::new(1, 1, 77, 40), &mut open);
+        open.upstairs = (3, 3);
+        let (up, down) = (open.xy_idx(3, 3), open.xy_idx(76, 39));
+        open.tiles[up] = TileType::UpStairs;
+        open.tiles[down] = TileType::DownStairs;
+
+        let mut bridged = false;
+        let mut lava = false;
+        for seed in 1..=20 {
+            let mut map = open.clone();
+            River::new().build_map(&mut map, &mut RandomNumberGenerator::seeded(seed));
+            assert!(map.tiles.iter().any(|tile| matches!(tile, TileType::DeepWater | TileType::ShallowWater | TileType::Lava)));
+            assert!(map.tiles[map.xy_idx(3, 3)] == TileType::UpStairs);
+
+            // Lava counts as in the way, as nobody would cross it on purpose.
+            assert!(!stairs_cut_off(&map), "seed {} cut off the stairs", seed);
+            bridged |= map.tiles.contains(&TileType::Bridge);
+            lava |= map.tiles.contains(&TileType::Lava);
+        }
+        assert!(bridged && lava);
+
+        // A lava river through ground too narrow for the usual bridges still gets a way across.
+        let mut corridor = Map::new(5, 80, 43);
+        apply_room_to_map(&Rect::new(1, 18, 77, 4), &mut corridor);
+        apply_room_to_map(&Rect::new(36, 1, 4, 40), &mut corridor);
+        corridor.upstairs = (3, 20);
+        let (up, down) = (corridor.xy_idx(3, 20), corridor.xy_idx(76, 20));
+        corridor.tiles[up] = TileType::UpStairs;
+        corridor.tiles[down] = TileType::DownStairs;
+        let mut lava_seen = false;
+        for seed in 1..=30 {
+            let mut map = corridor.clone();
+            River::new().build_map(&mut map, &mut RandomNumberGenerator::seeded(seed));
+            assert!(!stairs_cut_off(&map), "seed {} left lava in the way", seed);
+            lava_seen |= map.tiles.contains(&TileType::Lava);
+        }
+        assert!(lava_seen);
+    }
+}
@@ -0,0 +1,24 @@
+use rltk::RandomNumberGenerator;
+
+use super::map::Map;
+use super::MetaMapBuilder;
+
+/// Turns every room of the Map into its own spawn region.
+pub struct RoomBasedSpawnRegions {}
+
+impl RoomBasedSpawnRegions {
+    pub fn new() -> Box<RoomBasedSpawnRegions> {
+        Box::new(RoomBasedSpawnRegions {})
+    }
+}
+
+impl MetaMapBuilder for RoomBasedSpawnRegions {
+    fn build_map(&mut self, map: &mut Map, _rng: &mut RandomNumberGenerator) {
+        if map.rooms.is_empty() {
+            panic!("RoomBasedSpawnRegions only works after a builder that creates rooms");
+        }
+
+        map.regions.clear();
+        map.convert_rooms_to_regions();
+    }
+}
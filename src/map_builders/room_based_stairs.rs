@@ -0,0 +1,31 @@
+use rltk::RandomNumberGenerator;
+
+use super::map::{Map, TileType};
+use super::MetaMapBuilder;
+
+/// Places the up stairs in the centre of the first room and the down stairs in the centre of
+/// the last one. Only useful after a builder that fills in Map.rooms.
+pub struct RoomBasedStairs {}
+
+impl RoomBasedStairs {
+    pub fn new() -> Box<RoomBasedStairs> {
+        Box::new(RoomBasedStairs {})
+    }
+}
+
+impl MetaMapBuilder for RoomBasedStairs {
+    fn build_map(&mut self, map: &mut Map, _rng: &mut RandomNumberGenerator) {
+        if map.rooms.is_empty() {
+            panic!("RoomBasedStairs only works after a builder that creates rooms");
+        }
+
+        map.upstairs = map.rooms[0].center();
+        let up_idx = map.xy_idx(map.upstairs.0, map.upstairs.1);
+        map.tiles[up_idx] = TileType::UpStairs;
+
+        let down_stairs = map.rooms[map.rooms.len()-1].center();
+        let down_idx = map.xy_idx(down_stairs.0, down_stairs.1);
+        map.tiles[down_idx] = TileType::DownStairs;
+        map.take_snapshot();
+    }
+}
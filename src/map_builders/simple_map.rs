@@ -15,10 +15,8 @@ impl SimpleMapBuilder {
 }
 
 impl MapBuilder for SimpleMapBuilder {
-    fn build(&mut self) -> Map {
-        rooms_and_corridors(&mut self.map);
-        
-        self.map.convert_rooms_to_regions();
+    fn build(&mut self, rng: &mut RandomNumberGenerator) -> Map {
+        rooms_and_corridors(&mut self.map, rng);
 
         self.map.clone()
     }
@@ -28,23 +26,16 @@ impl MapBuilder for SimpleMapBuilder {
         return Box::new(self.clone());
     }
 
-    #[allow(unused_variables)]
-    fn add(&mut self, map: Map) -> Box<dyn MapBuilder> {
-        return Box::new(self.to_owned());
-    }
-
-    fn new(new_depth: i32) -> Box<dyn MapBuilder> {
-        Box::new(SimpleMapBuilder { map: Map::new (new_depth)})
+    fn start(new_depth: i32, width: i32, height: i32) -> Box<dyn MapBuilder> {
+        Box::new(SimpleMapBuilder { map: Map::new(new_depth, width, height)})
     }
 }
 
-fn rooms_and_corridors(map : &mut Map) {
+fn rooms_and_corridors(map : &mut Map, rng : &mut RandomNumberGenerator) {
     const MAX_ROOMS : i32 = 30;
     const MIN_SIZE : i32 = 6;
     const MAX_SIZE : i32 = 10;
 
-    let mut rng = RandomNumberGenerator::new();
-
     for _i in 0..MAX_ROOMS {
         let w = rng.range(MIN_SIZE, MAX_SIZE);
         let h = rng.range(MIN_SIZE, MAX_SIZE);
@@ -78,11 +69,4 @@ fn rooms_and_corridors(map : &mut Map) {
     }
 
     map.populate_blocked();
-    
-    map.upstairs = map.rooms[0].center();
-    let upstairs_idx = xy_idx(map.upstairs.0, map.upstairs.1);
-    map.tiles[upstairs_idx] = TileType::UpStairs;
-    let stairs_position = map.rooms[map.rooms.len()-1].center();
-    let stairs_idx = xy_idx(stairs_position.0, stairs_position.1);
-    map.tiles[stairs_idx] = TileType::DownStairs;
 }
\ No newline at end of file
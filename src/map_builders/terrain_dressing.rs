@@ -0,0 +1,72 @@
+use rltk::RandomNumberGenerator;
+
+use super::map::{Map, TileType};
+use super::MetaMapBuilder;
+
+/// Dresses up bare floor. Grass grows in patches, seeded at random and smoothed with cellular
+/// automata; rubble has fallen here and there along the walls, with gravel scattered round it.
+pub struct TerrainDressing {}
+
+impl TerrainDressing {
+    pub fn new() -> Box<TerrainDressing> {
+        Box::new(TerrainDressing {})
+    }
+}
+
+impl MetaMapBuilder for TerrainDressing {
+    fn build_map(&mut self, map: &mut Map, rng: &mut RandomNumberGenerator) {
+        let mut grassy: Vec<bool> = map.tiles
+            .iter()
+            .map(|tile| *tile == TileType::Floor && rng.roll_dice(1, 100) <= 45)
+            .collect();
+        for _i in 0..4 {
+            let mut next = grassy.clone();
+            for y in 1..map.height - 1 {
+                for x in 1..map.width - 1 {
+                    let idx = map.xy_idx(x, y);
+                    let neighbours = count_neighbours(map, x, y, |idx| grassy[idx]);
+                    next[idx] = map.tiles[idx] == TileType::Floor && (neighbours >= 5 || (grassy[idx] && neighbours >= 4));
+                }
+            }
+            grassy = next;
+        }
+        for (tile, grassy) in map.tiles.iter_mut().zip(grassy.iter()) {
+            if *grassy {
+                *tile = TileType::Grass;
+            }
+        }
+
+        for y in 1..map.height - 1 {
+            for x in 1..map.width - 1 {
+                let idx = map.xy_idx(x, y);
+                let walls = count_neighbours(map, x, y, |idx| map.tiles[idx] == TileType::Wall);
+                if map.tiles[idx] == TileType::Floor && walls >= 3 && rng.roll_dice(1, 12) == 1 {
+                    map.tiles[idx] = TileType::Rubble;
+                }
+            }
+        }
+        for y in 1..map.height - 1 {
+            for x in 1..map.width - 1 {
+                let idx = map.xy_idx(x, y);
+                let rubble = count_neighbours(map, x, y, |idx| map.tiles[idx] == TileType::Rubble);
+                if map.tiles[idx] == TileType::Floor && rubble > 0 && rng.roll_dice(1, 3) == 1 {
+                    map.tiles[idx] = TileType::Gravel;
+                }
+            }
+        }
+        map.take_snapshot();
+    }
+}
+
+/// Counts the tiles around (x, y) for which test is true.
+fn count_neighbours(map: &Map, x: i32, y: i32, test: impl Fn(usize) -> bool) -> i32 {
+    let mut count = 0;
+    for delta_y in -1..=1 {
+        for delta_x in -1..=1 {
+            if (delta_x, delta_y) != (0, 0) && test(map.xy_idx(x + delta_x, y + delta_y)) {
+                count += 1;
+            }
+        }
+    }
+    count
+}
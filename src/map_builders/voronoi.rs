@@ -1,6 +1,5 @@
 use rltk::RandomNumberGenerator;
 
-use super::common::*;
 use super::map::*;
 use super::{BuilderSettings, MapBuilder};
 
@@ -14,15 +13,14 @@ impl VoronoiBuilder {
 }
 
 impl MapBuilder for VoronoiBuilder {
-    fn build(&mut self) -> Map {
-        let mut rng = RandomNumberGenerator::new();
+    fn build(&mut self, rng: &mut RandomNumberGenerator) -> Map {
         let n_seeds = 64;
         let mut voronoi_seeds : Vec<(usize, rltk::Point)> = Vec::new();
 
         while voronoi_seeds.len() < n_seeds {
             let vx = rng.roll_dice(1, self.map.width-1);
             let vy = rng.roll_dice(1, self.map.height-1);
-            let vidx = xy_idx(vx, vy);
+            let vidx = self.map.xy_idx(vx, vy);
             let candidate = (vidx, rltk::Point::new(vx, vy));
             if !voronoi_seeds.contains(&candidate) {
                 voronoi_seeds.push(candidate);
@@ -51,12 +49,12 @@ impl MapBuilder for VoronoiBuilder {
         for y in 1..self.map.height-1 {
             for x in 1..self.map.width-1 {
                 let mut neighbors = 0;
-                let my_idx = xy_idx(x, y);
+                let my_idx = self.map.xy_idx(x, y);
                 let my_seed = voronoi_membership[my_idx];
-                if voronoi_membership[xy_idx(x-1, y)] != my_seed { neighbors += 1; }
-                if voronoi_membership[xy_idx(x+1, y)] != my_seed { neighbors += 1; }
-                if voronoi_membership[xy_idx(x, y-1)] != my_seed { neighbors += 1; }
-                if voronoi_membership[xy_idx(x, y+1)] != my_seed { neighbors += 1; }
+                if voronoi_membership[self.map.xy_idx(x-1, y)] != my_seed { neighbors += 1; }
+                if voronoi_membership[self.map.xy_idx(x+1, y)] != my_seed { neighbors += 1; }
+                if voronoi_membership[self.map.xy_idx(x, y-1)] != my_seed { neighbors += 1; }
+                if voronoi_membership[self.map.xy_idx(x, y+1)] != my_seed { neighbors += 1; }
         
                 if neighbors < 2 {
                     self.map.tiles[my_idx] = TileType::Floor;
@@ -73,13 +71,8 @@ impl MapBuilder for VoronoiBuilder {
         return Box::new(self.clone());
     }
 
-    #[allow(unused_variables)]
-    fn add(&mut self, map: Map) -> Box<dyn MapBuilder> {
-        return Box::new(self.to_owned());
-    }
-
-    fn new(new_depth: i32) -> Box<dyn MapBuilder> {
-        Box::new(VoronoiBuilder { map: Map::new (new_depth)})
+    fn start(new_depth: i32, width: i32, height: i32) -> Box<dyn MapBuilder> {
+        Box::new(VoronoiBuilder { map: Map::new(new_depth, width, height)})
     }
 }
 
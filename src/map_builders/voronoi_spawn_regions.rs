@@ -0,0 +1,21 @@
+use rltk::RandomNumberGenerator;
+
+use super::common::generate_voronoi_spawn_regions;
+use super::map::Map;
+use super::MetaMapBuilder;
+
+/// Divides the open tiles of the Map into spawn regions with cellular noise, for maps without
+/// rooms.
+pub struct VoronoiSpawnRegions {}
+
+impl VoronoiSpawnRegions {
+    pub fn new() -> Box<VoronoiSpawnRegions> {
+        Box::new(VoronoiSpawnRegions {})
+    }
+}
+
+impl MetaMapBuilder for VoronoiSpawnRegions {
+    fn build_map(&mut self, map: &mut Map, rng: &mut RandomNumberGenerator) {
+        map.regions = generate_voronoi_spawn_regions(map, rng);
+    }
+}
@@ -1,4 +1,3 @@
-use crate::map_builders::common::xy_idx;
 use std::collections::HashSet;
 
 use crate::map_builders::map::Map;
@@ -173,7 +172,7 @@ impl Solver {
             let mut i : usize = 0;
             for y in top_y .. bottom_y {
                 for x in left_x .. right_x {
-                    let mapidx = xy_idx(x, y);
+                    let mapidx = map.xy_idx(x, y);
                     let tile = self.constraints[new_chunk_idx].pattern[i];
                     map.tiles[mapidx] = tile;
                     i += 1;
@@ -199,6 +198,8 @@ impl Solver {
                     possible_options.push(*new_chunk_idx);
                 }
             }
+            // HashSet iteration order is random, sort so seeded maps are reproducible.
+            possible_options.sort_unstable();
 
             if possible_options.is_empty() {
                 rltk::console::log("Oh no! It's not possible!");
@@ -218,7 +219,7 @@ impl Solver {
                 let mut i : usize = 0;
                 for y in top_y .. bottom_y {
                     for x in left_x .. right_x {
-                        let mapidx = xy_idx(x, y);
+                        let mapidx = map.xy_idx(x, y);
                         let tile = self.constraints[possible_options[new_chunk_idx as usize]].pattern[i];
                         map.tiles[mapidx] = tile;
                         i += 1;
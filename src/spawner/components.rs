@@ -1,4 +1,4 @@
-use rltk::{FontCharType, Point, RGB};
+use rltk::{FontCharType, Point, RandomNumberGenerator, RGB};
 use specs::*;
 use specs::saveload::{ConvertSaveload, Marker};
 use specs::error::NoError;
@@ -6,9 +6,12 @@ use specs_derive::*;
 use serde::{Serialize, Deserialize};
 
 use crate::map_builders::map::Map;
-use super::{item::Item, item::Potion, monster::Monster, player::Player};
+use super::{item::Charges, item::Cursed, item::Durability, item::Enchantment, item::Identifies, item::Item, item::MagicItem, item::Potion, item::Recharges, item::RemovesCurse, item::Repairs, item::Stackable, monster::Behaviour, monster::Faction, monster::Monster, player::Player};
 
-trait Build<T> {
+/// Build creates a component from a string of whitespace separated "key:value" pairs, which is
+/// how the raws describe the components of everything they spawn. Keys that are left out get
+/// a default value.
+pub trait Build<T> {
     fn create (creator: String) -> Option<T>;
 }
 
@@ -17,43 +20,83 @@ trait Build<T> {
 /// these components in their initial creation.
 pub fn register_spawns(ecs: &mut World) {
     ecs.register::<Actor>();
+    ecs.register::<Ammunition>();
     ecs.register::<AreaOfEffect>();
+    ecs.register::<Behaviour>();
     ecs.register::<BlocksTile>();
+    ecs.register::<Charges>();
     ecs.register::<CombatStats>();
-    ecs.register::<Confusion>();
     ecs.register::<Consumable>();
+    ecs.register::<Cursed>();
     ecs.register::<DefenseBonus>();
+    ecs.register::<Door>();
+    ecs.register::<Durability>();
+    ecs.register::<Enchantment>();
     ecs.register::<Equippable>();
     ecs.register::<Equipped>();
     ecs.register::<EntryTrigger>();
+    ecs.register::<Experience>();
+    ecs.register::<ExperienceValue>();
+    ecs.register::<Faction>();
+    ecs.register::<GeneralStats>();
     ecs.register::<Hidden>();
     ecs.register::<HungerClock>();
+    ecs.register::<Identifies>();
     ecs.register::<InflictsDamage>();
+    ecs.register::<InflictsStatus>();
     ecs.register::<Item>();
+    ecs.register::<Key>();
+    ecs.register::<MagicItem>();
     ecs.register::<MagicMapper>();
     ecs.register::<MeleePowerBonus>();
     ecs.register::<Monster>();
     ecs.register::<Name>();
+    ecs.register::<NaturalAttack>();
     ecs.register::<Player>();
     ecs.register::<Position>();
     ecs.register::<Potion>();
     ecs.register::<ProvidesFood>();
     ecs.register::<ProvidesHealing>();
     ecs.register::<Ranged>();
+    ecs.register::<RangedWeapon>();
+    ecs.register::<RawName>();
+    ecs.register::<Recharges>();
+    ecs.register::<RemovesCurse>();
     ecs.register::<Renderable>();
+    ecs.register::<Repairs>();
     ecs.register::<SingleActivation>();
+    ecs.register::<Stackable>();
+    ecs.register::<StatusEffects>();
     ecs.register::<Viewshed>();
 }
 
-/// Come to think of it, I don't have a clue why this exists.  It is not currently being 
-/// used anywhere.
+/// Actor gives an Entity a place in the turn order. Every round of the InitiativeSystem adds
+/// speed to energy, and the Entity takes a turn each time energy reaches Actor::TURN_COST, so
+/// an Actor with twice the speed acts twice as often. An empty key gives NORMAL_SPEED.
 #[derive(Component, Serialize, Deserialize, Clone, Debug)]
-pub struct Actor {}
-#[allow(unused_variables)]
+pub struct Actor {
+    pub speed: i32,
+    pub energy: i32,
+}
 impl Build<Actor> for Actor {
     fn create(key: String) -> Option<Actor> {
-        Some(Actor {})
-    } 
+        let mut speed = Actor::NORMAL_SPEED;
+        for item in key.split_ascii_whitespace() {
+            let entry:Vec<&str> = item.split(":").collect();
+            if entry[0] == "speed" { speed = entry[1].parse().unwrap_or(Actor::NORMAL_SPEED); }
+        }
+        Some(Actor::new(speed))
+    }
+}
+impl Actor {
+    /// The speed of the player and of most monsters.
+    pub const NORMAL_SPEED: i32 = 10;
+    /// The energy an Actor spends to take one turn.
+    pub const TURN_COST: i32 = 100;
+
+    pub fn new(speed: i32) -> Actor {
+        Actor { speed, energy: 0 }
+    }
 }
 
 /// The Fireball Scroll effects an area with a radius of 3. This Component tells the 
@@ -88,7 +131,35 @@ impl Build<BlocksTile> for BlocksTile {
     }
 }
 
-/// CombatStats contains the base combat abilities of the Entity it is attached to.
+/// DiceRoll is a number of dice of one size plus a bonus, written "2d6+1" in the raws.
+#[derive(PartialEq, Copy, Clone, Serialize, Deserialize, Debug)]
+pub struct DiceRoll {
+    pub n_dice: i32,
+    pub die_type: i32,
+    pub bonus: i32,
+}
+impl DiceRoll {
+    pub fn new(n_dice: i32, die_type: i32, bonus: i32) -> DiceRoll {
+        DiceRoll { n_dice, die_type, bonus }
+    }
+
+    /// Reads "NdS", "NdS+B" or "NdS-B". Returns None for anything else.
+    pub fn parse(text: &str) -> Option<DiceRoll> {
+        let (n_dice, rest) = text.split_once('d')?;
+        let (die_type, bonus) = match rest.find(['+', '-']) {
+            Some(i) => (&rest[..i], rest[i..].trim_start_matches('+').parse().ok()?),
+            None => (rest, 0),
+        };
+        Some(DiceRoll::new(n_dice.parse().ok()?, die_type.parse().ok()?, bonus))
+    }
+
+    pub fn roll(&self, rng: &mut RandomNumberGenerator) -> i32 {
+        rng.roll_dice(self.n_dice, self.die_type) + self.bonus
+    }
+}
+
+/// CombatStats contains the base combat abilities of the Entity it is attached to. attack is 
+/// added to every d20 attack roll and defense to the Entity's armour class.
 #[derive(Component, ConvertSaveload, Clone, Debug)]
 pub struct CombatStats {
     pub hp: i32,
@@ -134,26 +205,159 @@ impl CombatStats {
     }
 }
 
-/// Is the Entity under the effect of Confuision? How many turns are left on the Confusion
-/// effect? This comonent is the control for answering those questions.
-/// 
-/// TODO: This should probably be moved to the top-level Components module.
-#[derive(Component, ConvertSaveload, Clone, Debug)]
-pub struct Confusion {
-    pub turns: i32
+/// The timed effects an Entity can be under. potency is how strong an effect is where that
+/// matters: hit points lost to Poison or gained from Regeneration every turn, or extra damage
+/// from Strength.
+#[derive(PartialEq, Copy, Clone, Serialize, Deserialize, Debug)]
+pub enum StatusKind { Confusion, Poison, Regeneration, Slow, Haste, Paralysis, Blindness, Strength }
+
+impl StatusKind {
+    pub fn parse(name: &str) -> Option<StatusKind> {
+        match name {
+            "confusion" => Some(StatusKind::Confusion),
+            "poison" => Some(StatusKind::Poison),
+            "regeneration" => Some(StatusKind::Regeneration),
+            "slow" => Some(StatusKind::Slow),
+            "haste" => Some(StatusKind::Haste),
+            "paralysis" => Some(StatusKind::Paralysis),
+            "blindness" => Some(StatusKind::Blindness),
+            "strength" => Some(StatusKind::Strength),
+            _ => None,
+        }
+    }
+
+    /// How an Entity under the effect is described in the GameLog, e.g. "confused".
+    pub fn adjective(self) -> &'static str {
+        match self {
+            StatusKind::Confusion => "confused",
+            StatusKind::Poison => "poisoned",
+            StatusKind::Regeneration => "regenerating",
+            StatusKind::Slow => "slowed",
+            StatusKind::Haste => "hasted",
+            StatusKind::Paralysis => "paralysed",
+            StatusKind::Blindness => "blind",
+            StatusKind::Strength => "unnaturally strong",
+        }
+    }
+
+    /// The glyph and colour the effect is shown with in the status bar.
+    pub fn icon(self) -> (char, RGB) {
+        match self {
+            StatusKind::Confusion => ('?', RGB::named(rltk::MAGENTA)),
+            StatusKind::Poison => ('!', RGB::named(rltk::GREEN)),
+            StatusKind::Regeneration => ('+', RGB::named(rltk::RED)),
+            StatusKind::Slow => ('<', RGB::named(rltk::CYAN3)),
+            StatusKind::Haste => ('>', RGB::named(rltk::YELLOW)),
+            StatusKind::Paralysis => ('#', RGB::named(rltk::PINK)),
+            StatusKind::Blindness => ('*', RGB::named(rltk::WHITE)),
+            StatusKind::Strength => ('^', RGB::named(rltk::ORANGE)),
+        }
+    }
+}
+
+#[derive(PartialEq, Copy, Clone, Serialize, Deserialize, Debug)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub turns: i32,
+    pub potency: i32,
+}
+
+/// StatusEffects holds every timed effect an Entity is under. The status_effect_system counts
+/// them down on the Entity's own turns.
+#[derive(Component, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct StatusEffects {
+    pub effects: Vec<StatusEffect>,
+}
+
+impl StatusEffects {
+    /// Puts effect on target, giving it a StatusEffects first if it has none.
+    pub fn inflict(store: &mut WriteStorage<StatusEffects>, target: Entity, effect: StatusEffect) {
+        if let Some(effects) = store.get_mut(target) {
+            effects.add(effect);
+        } else {
+            let mut effects = StatusEffects::default();
+            effects.add(effect);
+            store.insert(target, effects).expect("Unable to insert status");
+        }
+    }
+
+    /// Adds an effect. Poison stacks, so a second dose hurts more; anything else already in
+    /// effect is refreshed to the longer duration and the stronger potency. Haste and Slow
+    /// cancel each other out.
+    pub fn add(&mut self, effect: StatusEffect) {
+        let opposite = match effect.kind {
+            StatusKind::Haste => Some(StatusKind::Slow),
+            StatusKind::Slow => Some(StatusKind::Haste),
+            _ => None,
+        };
+        if let Some(opposite) = opposite {
+            if self.has(opposite) {
+                self.effects.retain(|e| e.kind != opposite);
+                return;
+            }
+        }
+
+        match self.effects.iter_mut().find(|e| e.kind == effect.kind) {
+            None => self.effects.push(effect),
+            Some(current) => {
+                current.turns = i32::max(current.turns, effect.turns);
+                if effect.kind == StatusKind::Poison {
+                    current.potency += effect.potency;
+                } else {
+                    current.potency = i32::max(current.potency, effect.potency);
+                }
+            }
+        }
+    }
+
+    pub fn has(&self, kind: StatusKind) -> bool {
+        self.effects.iter().any(|e| e.kind == kind)
+    }
+
+    /// The potency of kind, or 0 when the Entity is not under it.
+    pub fn potency(&self, kind: StatusKind) -> i32 {
+        self.effects.iter().find(|e| e.kind == kind).map_or(0, |e| e.potency)
+    }
+
+    /// Confused and paralysed creatures lose their turns.
+    pub fn prevents_action(&self) -> bool {
+        self.has(StatusKind::Confusion) || self.has(StatusKind::Paralysis)
+    }
+
+    /// The speed an Actor with base speed has while hasted or slowed.
+    pub fn adjust_speed(&self, speed: i32) -> i32 {
+        if self.has(StatusKind::Haste) {
+            speed * 2
+        } else if self.has(StatusKind::Slow) {
+            speed / 2
+        } else {
+            speed
+        }
+    }
+}
+
+/// InflictsStatus puts an effect on whoever an Item is used on or a trap is triggered by, e.g.
+/// "kind:poison turns:5 potency:1".
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct InflictsStatus {
+    pub effect: StatusEffect,
 }
-impl Build<Confusion> for Confusion {
-    fn create(key:String) -> Option<Confusion> {
+impl Build<InflictsStatus> for InflictsStatus {
+    fn create(key:String) -> Option<InflictsStatus> {
         if key.is_empty() {return None;}
+        let mut kind = None;
         let mut turns = 4;
+        let mut potency = 1;
         for item in key.split_ascii_whitespace() {
             let entry:Vec<&str> = item.split(":").collect();
             match entry[0] {
+                "kind" => {kind = StatusKind::parse(entry[1]);},
                 "turns" => {turns = entry[1].parse().unwrap_or(4);},
+                "potency" => {potency = entry[1].parse().unwrap_or(1);},
                 _ => {}
             }
         }
-        Some(Confusion {turns})
+        Some(InflictsStatus { effect: StatusEffect { kind: kind?, turns, potency } })
     }
 }
 
@@ -202,28 +406,142 @@ impl Build<DefenseBonus> for DefenseBonus {
     }
 }
 
+/// A Door blocks movement and sight while it is closed. Bumping into a closed Door opens it,
+/// unless it is locked: a locked Door only opens for someone carrying a Key of the kind named
+/// by lock. "open" starts the Door open, "locked" starts it locked.
+#[derive(Component, ConvertSaveload, Clone, Debug)]
+pub struct Door {
+    pub open: bool,
+    pub locked: bool,
+    pub lock: Option<String>,
+}
+impl Build<Door> for Door {
+    fn create(key: String) -> Option<Door> {
+        let mut door = Door { open: false, locked: false, lock: None };
+        for item in key.split_ascii_whitespace() {
+            let entry:Vec<&str> = item.split(":").collect();
+            match entry[0] {
+                "open" => { door.open = true; },
+                "locked" => { door.locked = true; },
+                "lock" => { door.lock = Some(entry[1].to_string()); },
+                _ => {}
+            }
+        }
+        Some ( door )
+    }
+}
+impl Door {
+    /// Opens or closes the Door and shows it on the map as '/' or '+'.
+    pub fn set_open(&mut self, open: bool, renderable: Option<&mut Renderable>) {
+        self.open = open;
+        if let Some(renderable) = renderable {
+            renderable.glyph = rltk::to_cp437(if open { '/' } else { '+' });
+        }
+    }
+
+    /// True when a Key of the given kind fits this Door's lock.
+    pub fn fits(&self, key: &Key) -> bool {
+        self.lock.as_ref().is_some_and(|lock| *lock == key.kind)
+    }
+}
+
 /// Enumeration tag used by Equippable to indicate how this Item is used, when it is Equipped.
-#[derive(PartialEq, Copy, Clone, Serialize, Deserialize)]
-pub enum EquipmentSlot { Melee, Shield, None }
+#[derive(PartialEq, Copy, Clone, Serialize, Deserialize, Debug)]
+pub enum EquipmentSlot { Melee, Shield, Ranged, Head, Torso, Legs, Hands, Feet, Ring, Amulet, None }
+
+impl EquipmentSlot {
+    /// Every slot that can hold something, in the order the equipment screen lists them.
+    pub const WORN: [EquipmentSlot; 10] = [
+        EquipmentSlot::Melee,
+        EquipmentSlot::Shield,
+        EquipmentSlot::Ranged,
+        EquipmentSlot::Head,
+        EquipmentSlot::Torso,
+        EquipmentSlot::Legs,
+        EquipmentSlot::Hands,
+        EquipmentSlot::Feet,
+        EquipmentSlot::Ring,
+        EquipmentSlot::Amulet,
+    ];
+
+    /// How many items can be worn in the slot at once: a ring on each hand, one of anything else.
+    pub fn capacity(self) -> usize {
+        match self {
+            EquipmentSlot::Ring => 2,
+            EquipmentSlot::None => 0,
+            _ => 1,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EquipmentSlot::Melee => "Weapon",
+            EquipmentSlot::Shield => "Shield",
+            EquipmentSlot::Ranged => "Ranged",
+            EquipmentSlot::Head => "Head",
+            EquipmentSlot::Torso => "Torso",
+            EquipmentSlot::Legs => "Legs",
+            EquipmentSlot::Hands => "Hands",
+            EquipmentSlot::Feet => "Feet",
+            EquipmentSlot::Ring => "Ring",
+            EquipmentSlot::Amulet => "Amulet",
+            EquipmentSlot::None => "None",
+        }
+    }
+}
+
+/// Ammunition is shot of one kind, fired by the RangedWeapons that use that kind. How many
+/// shots there are is kept by the Stackable it comes with, so a quiver stacks like any other
+/// Item and every shot fired takes one off the stack.
+#[derive(Component, ConvertSaveload, Clone, Debug)]
+pub struct Ammunition {
+    pub kind: String,
+}
+impl Build<Ammunition> for Ammunition {
+    fn create(key: String) -> Option<Ammunition> {
+        if key.is_empty() { return None; }
+        let mut kind = String::new();
+        for item in key.split_ascii_whitespace() {
+            let entry:Vec<&str> = item.split(":").collect();
+            if entry[0] == "kind" {
+                kind = entry[1].to_string();
+            }
+        }
+        if kind.is_empty() { return None; }
+        Some ( Ammunition { kind } )
+    }
+}
 
 /// This Component tells the code that this Item can be equipped and what type of equipment it 
-/// is.
+/// is. A two_handed weapon ("melee two_handed") leaves no hand free for a shield.
 #[derive(Component, Serialize, Deserialize, Clone)]
 pub struct Equippable {
-    pub slot : EquipmentSlot
+    pub slot : EquipmentSlot,
+    #[serde(default)]
+    pub two_handed : bool,
 }
 impl Build<Equippable> for Equippable {
     fn create(key:String) -> Option<Equippable> {
         if key.is_empty() { return None; }
         let mut slot = EquipmentSlot::None;
+        let mut two_handed = false;
         for item in key.split_ascii_whitespace() {
             match item {
                 "melee" => {slot = EquipmentSlot::Melee;},
                 "shield" => {slot = EquipmentSlot::Shield;},
+                "ranged" => {slot = EquipmentSlot::Ranged;},
+                "head" => {slot = EquipmentSlot::Head;},
+                "torso" => {slot = EquipmentSlot::Torso;},
+                "legs" => {slot = EquipmentSlot::Legs;},
+                "hands" => {slot = EquipmentSlot::Hands;},
+                "feet" => {slot = EquipmentSlot::Feet;},
+                "ring" => {slot = EquipmentSlot::Ring;},
+                "amulet" => {slot = EquipmentSlot::Amulet;},
+                "two_handed" => {two_handed = true;},
                 _ => { return None; }
             }
         }
-        Some( Equippable { slot })
+        Some( Equippable { slot, two_handed })
     }
 }
 
@@ -251,14 +569,118 @@ impl Build<EntryTrigger> for EntryTrigger {
     }
 }
 
-/// GeneralStats is not yet implemented. I placed it in here intending to implement modifiers 
-/// based on randomized Stats included here, but haven't gotten to that yet.
+/// Experience is the player's progress: their character level and the experience points 
+/// gathered so far. Points are never spent; a new level is reached each time xp passes 
+/// Experience::next_level_at(level).
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Experience {
+    pub level: i32,
+    pub xp: i32,
+}
+impl Experience {
+    /// The total experience needed to go up from level: 100, then 300, 600, 1000 and so on.
+    pub fn next_level_at(level: i32) -> i32 {
+        50 * level * (level + 1)
+    }
+
+    pub fn can_level_up(&self) -> bool {
+        self.xp >= Experience::next_level_at(self.level)
+    }
+}
+
+/// ExperienceValue is how much experience a Monster is worth when the player kills it. It is 
+/// multiplied by the depth of the level, as the same Monster is found with better friends 
+/// further down.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct ExperienceValue {
+    pub xp: i32,
+}
+impl Build<ExperienceValue> for ExperienceValue {
+    fn create(key: String) -> Option<ExperienceValue> {
+        if key.is_empty() {return None;}
+        let mut xp = 0;
+        for item in key.split_ascii_whitespace() {
+            let entry:Vec<&str> = item.split(":").collect();
+            if entry[0] == "xp" { xp = entry[1].parse().unwrap_or(0); }
+        }
+        Some( ExperienceValue { xp } )
+    }
+}
+
+/// GeneralStats are the attributes of a creature. The player rolls them when the game starts
+/// and Monsters get theirs from the raws. Each attribute gives a modifier: strength to melee 
+/// damage and carry capacity, dexterity to hitting and dodging in melee, constitution to 
+/// hp_max and intelligence to the damage done by magic.
 #[derive(Component, ConvertSaveload, Clone, Debug)]
 pub struct GeneralStats {
-    pub strength: u32,
-    pub dexterity: u32,
-    pub constution: u32,
-    pub intelligence: u32
+    pub strength: i32,
+    pub dexterity: i32,
+    pub constitution: i32,
+    pub intelligence: i32
+}
+impl Build<GeneralStats> for GeneralStats {
+    fn create(key: String) -> Option<GeneralStats> {
+        if key.is_empty() {return None;}
+        let mut stats = GeneralStats { strength: 10, dexterity: 10, constitution: 10, intelligence: 10 };
+        for item in key.split_ascii_whitespace() {
+            let entry:Vec<&str> = item.split(":").collect();
+            match entry[0] {
+                "strength" => {stats.strength = entry[1].parse().unwrap_or(10);},
+                "dexterity" => {stats.dexterity = entry[1].parse().unwrap_or(10);},
+                "constitution" => {stats.constitution = entry[1].parse().unwrap_or(10);},
+                "intelligence" => {stats.intelligence = entry[1].parse().unwrap_or(10);},
+                _ => {}
+            }
+        }
+        Some( stats )
+    }
+}
+impl GeneralStats {
+    /// Extra hp_max for every point of constitution modifier.
+    pub const HP_PER_CONSTITUTION: i32 = 3;
+    /// How many pounds can be carried unburdened with an average strength.
+    pub const BASE_CARRY_CAPACITY: f32 = 60.0;
+    /// Extra pounds carried unburdened for every point of strength modifier.
+    pub const CARRY_PER_STRENGTH: f32 = 10.0;
+
+    /// Rolls each attribute as the best three of four six sided dice.
+    pub fn roll(rng: &mut RandomNumberGenerator) -> GeneralStats {
+        let mut roll_attribute = || {
+            let mut dice: Vec<i32> = (0..4).map(|_| rng.roll_dice(1, 6)).collect();
+            dice.sort_unstable();
+            dice[1..].iter().sum()
+        };
+        GeneralStats {
+            strength: roll_attribute(),
+            dexterity: roll_attribute(),
+            constitution: roll_attribute(),
+            intelligence: roll_attribute(),
+        }
+    }
+
+    /// The modifier for an attribute: 0 for 10 or 11, one more for every two points above that 
+    /// and one less for every two points below.
+    pub fn modifier(attribute: i32) -> i32 {
+        (attribute - 10).div_euclid(2)
+    }
+
+    pub fn hp_bonus(&self) -> i32 {
+        GeneralStats::modifier(self.constitution) * GeneralStats::HP_PER_CONSTITUTION
+    }
+
+    /// How many pounds can be carried without being slowed down by them.
+    pub fn carry_capacity(&self) -> f32 {
+        f32::max(10.0, GeneralStats::BASE_CARRY_CAPACITY + GeneralStats::modifier(self.strength) as f32 * GeneralStats::CARRY_PER_STRENGTH)
+    }
+
+    /// The most that can be carried at all: twice the carry capacity.
+    pub fn max_load(&self) -> f32 {
+        self.carry_capacity() * 2.0
+    }
+
+    pub fn spell_power(&self) -> i32 {
+        GeneralStats::modifier(self.intelligence)
+    }
 }
 
 /// The Hidden component is a simple tag to indicate that the Item it is attached to should not 
@@ -323,6 +745,25 @@ impl Build<InflictsDamage> for InflictsDamage {
     }
 }
 
+/// A Key opens every Door whose lock is of the same kind.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Key {
+    pub kind: String,
+}
+impl Build<Key> for Key {
+    fn create(key: String) -> Option<Key> {
+        if key.is_empty() { return None; }
+        let mut kind = String::new();
+        for item in key.split_ascii_whitespace() {
+            let entry:Vec<&str> = item.split(":").collect();
+            if entry[0] == "kind" {
+                kind = entry[1].to_string();
+            }
+        }
+        Some ( Key { kind } )
+    }
+}
+
 #[derive(Component, Serialize, Deserialize, Clone, Debug)]
 pub struct MagicMapper {  }
 #[allow(unused_variables)]
@@ -332,22 +773,52 @@ impl Build<MagicMapper> for MagicMapper {
     }
 }
 
+/// MeleePowerBonus makes an Item a melee weapon: a hit does damage dice, e.g. "damage:1d8", 
+/// and power is added to both the attack roll and the damage. The dice only count for the 
+/// weapon in the Melee slot; the power of everything equipped is added together.
 #[derive(Component, ConvertSaveload, Clone)]
 pub struct MeleePowerBonus {
-    pub power : i32
+    pub power : i32,
+    pub damage : DiceRoll,
 }
 impl Build<MeleePowerBonus> for MeleePowerBonus {
     fn create(key: String) -> Option<MeleePowerBonus> {
         if key.is_empty() { return None; }
-        let mut power=2;
+        let mut power = 0;
+        let mut damage = DiceRoll::new(1, 4, 0);
+        for item in key.split_ascii_whitespace() {
+            let entry:Vec<&str> = item.split(":").collect();
+            match entry[0] {
+                "power" => { power = entry[1].parse().unwrap_or(0); }
+                "damage" => { damage = DiceRoll::parse(entry[1]).unwrap_or(damage); }
+                _ => {}
+            }
+        }
+        Some( MeleePowerBonus { power, damage } )
+    }
+}
+
+/// NaturalAttack is how a creature fights without a weapon: a Goblin's "name:claws damage:1d4"
+/// or the player's fists.
+#[derive(Component, ConvertSaveload, Clone)]
+pub struct NaturalAttack {
+    pub name : String,
+    pub damage : DiceRoll,
+}
+impl Build<NaturalAttack> for NaturalAttack {
+    fn create(key: String) -> Option<NaturalAttack> {
+        if key.is_empty() { return None; }
+        let mut name = "hit".to_string();
+        let mut damage = DiceRoll::new(1, 4, 0);
         for item in key.split_ascii_whitespace() {
             let entry:Vec<&str> = item.split(":").collect();
             match entry[0] {
-                "power" => { power = entry[1].parse().unwrap_or(2); }
+                "name" => { name = entry[1].to_string(); }
+                "damage" => { damage = DiceRoll::parse(entry[1]).unwrap_or(damage); }
                 _ => {}
             }
         }
-        Some( MeleePowerBonus { power } )
+        Some( NaturalAttack { name, damage } )
     }
 }
 
@@ -370,6 +841,13 @@ impl Build<Name> for Name {
     }
 }
 
+/// RawName is the name of the raw an Entity was spawned from, e.g. "Healing Potion". Whatever
+/// its Name says, for the moment or for good, this is what says which kind of thing it is.
+#[derive(Component, ConvertSaveload, Clone)]
+pub struct RawName {
+    pub name: String,
+}
+
 #[derive(Component, ConvertSaveload, Clone, Copy, Debug)]
 pub struct Position {
     x: i32,
@@ -476,6 +954,34 @@ impl Build<Ranged> for Ranged {
     }
 }
 
+/// RangedWeapon makes an Item something to shoot or throw from the Ranged slot. A bow or 
+/// crossbow names the kind of Ammunition it uses ("ammo:arrow"); without ammo the weapon is 
+/// thrown itself and lands where it hits. power is added to the attack roll and the damage.
+#[derive(Component, ConvertSaveload, Clone, Debug)]
+pub struct RangedWeapon {
+    pub range: i32,
+    pub power: i32,
+    pub damage: DiceRoll,
+    pub ammo: Option<String>,
+}
+impl Build<RangedWeapon> for RangedWeapon {
+    fn create(key: String) -> Option<RangedWeapon> {
+        if key.is_empty() { return None; }
+        let mut weapon = RangedWeapon { range: 6, power: 0, damage: DiceRoll::new(1, 4, 0), ammo: None };
+        for item in key.split_ascii_whitespace() {
+            let entry:Vec<&str> = item.split(":").collect();
+            match entry[0] {
+                "range" => { weapon.range = entry[1].parse().unwrap_or(6); },
+                "power" => { weapon.power = entry[1].parse().unwrap_or(0); },
+                "damage" => { weapon.damage = DiceRoll::parse(entry[1]).unwrap_or(weapon.damage); },
+                "ammo" => { weapon.ammo = Some(entry[1].to_string()); },
+                _ => {}
+            }
+        }
+        Some ( weapon )
+    }
+}
+
 #[derive(Component, ConvertSaveload, Clone, Copy)]
 pub struct Renderable {
     pub glyph: FontCharType,
@@ -486,17 +992,17 @@ pub struct Renderable {
 impl Build<Renderable> for Renderable {
     fn create(key: String) -> Option<Renderable> {
         if key.is_empty() { return None; } 
-        let mut glyph = 0x007E;
+        let mut glyph = rltk::to_cp437('~');
         let mut fg = RGB::named(rltk::GREEN);
         let mut bg = RGB::named(rltk::BLACK);
         let mut render_order = 3;
         for item in key.split_ascii_whitespace() {
             let entry:Vec<&str> = item.split(":").collect();
             match entry[0] {
-                "glyph" => { glyph = entry[1].encode_utf16().nth(0).unwrap_or(0x007E); }, // 0x007E = '~' in utf-16
+                "glyph" => { glyph = rltk::to_cp437(entry[1].chars().next().unwrap_or('~')); },
                 "fg" => { fg = select_RGB(entry[1].parse().unwrap_or("GREEN".into())); },
                 "bg" => { bg = select_RGB(entry[1].parse().unwrap_or("BLACK".into())); },
-                "render_odrer" => { render_order = entry[1].parse().unwrap_or(3); },
+                "render_order" => { render_order = entry[1].parse().unwrap_or(3); },
                 _ => {}
             }
         }
@@ -533,6 +1039,17 @@ impl Build<Viewshed> for Viewshed {
         Some ( Viewshed { visible_tiles: Vec::new(), range, dirty: true } )
     }
 }
+impl Viewshed {
+    /// Marks every Viewshed that can see the tile at as dirty, so that a change there (a Door
+    /// opening or closing) shows up in what they see as soon as the VisibilitySystem next runs.
+    pub fn look_again_at(viewsheds: &mut WriteStorage<Viewshed>, at: Point) {
+        for viewshed in viewsheds.join() {
+            if viewshed.visible_tiles.contains(&at) {
+                viewshed.dirty = true;
+            }
+        }
+    }
+}
 
 #[allow(non_snake_case)]
 fn select_RGB(key:String) -> RGB {
@@ -547,6 +1064,7 @@ fn select_RGB(key:String) -> RGB {
         "YELLOW" => RGB::named(rltk::YELLOW),
         "RED" => RGB::named(rltk::RED),
         "MAGENTA" => RGB::named(rltk::MAGENTA),
+        "CHOCOLATE" => RGB::named(rltk::CHOCOLATE),
         _ => RGB::named(rltk::GRAY)
     }
 }
\ No newline at end of file
@@ -1,303 +1,356 @@
-use rltk::{RandomNumberGenerator, *};
+use rltk::RandomNumberGenerator;
 use serde::{Deserialize, Serialize};
 use specs::prelude::*;
-use specs::saveload::{MarkedBuilder, SimpleMarker};
+use specs::storage::MaskedStorage;
 use specs_derive::*;
+use std::collections::{HashMap, HashSet};
+use std::ops::Deref;
 
-use crate::components::SerializeMe;
-use crate::map_builders::map::Map;
-use crate::spawner::{components::*, random_table::RandomTable};
+use crate::components::InBackpack;
+use crate::spawner::components::{Build, DefenseBonus, MeleePowerBonus, Name, Position, RawName};
+use crate::spawner::raws::{raws, spawn_named_entity};
 
+/// Item tags everything that can be picked up and carried, and gives its weight in pounds, e.g.
+/// "weight:2.5". What an Item does is described by the other components the raws give it.
 #[derive(Component, Serialize, Deserialize, Clone, Debug)]
-pub struct Item {}
+pub struct Item {
+    #[serde(default)]
+    pub weight: f32,
+}
+impl Build<Item> for Item {
+    fn create(key: String) -> Option<Item> {
+        let mut weight = 0.0;
+        for item in key.split_ascii_whitespace() {
+            let entry: Vec<&str> = item.split(":").collect();
+            if entry[0] == "weight" {
+                weight = entry[1].parse().unwrap_or(0.0);
+            }
+        }
+        Some(Item { weight })
+    }
+}
 
 impl Item {
-    pub fn new(ecs: &mut World, x: i32, y: i32) {
-        let item_name: String;
-        {
-            let mut rng = ecs.write_resource::<RandomNumberGenerator>();
-            let map = ecs.fetch::<Map>();
-            item_name = item_table(&*map).roll(&mut rng);
-        }
-        match item_name.as_ref() {
-            "Healing Potion" => {
-                healing_potion(ecs, x, y);
-            }
-            "Fireball Scroll" => {
-                fireball_scroll(ecs, x, y);
-            }
-            "Confusion Scroll" => {
-                confusion_scroll(ecs, x, y);
-            }
-            "Magic Missile Scroll" => {
-                magic_missile_scroll(ecs, x, y);
-            }
-            "Dagger" => {
-                dagger(ecs, x, y);
-            }
-            "Shield" => {
-                shield(ecs, x, y);
-            }
-            "Longsword" => {
-                longsword(ecs, x, y);
-            }
-            "Tower Shield" => {
-                tower_shield(ecs, x, y);
-            }
-            "Rations" => {
-                rations (ecs, x, y);
-            }
-            "Magic Mapping Scroll" => {
-                magic_mapping_scroll(ecs, x, y);
+    /// What the Item weighs altogether: the weight is for a single potion or arrow, so a stack
+    /// weighs that many times as much.
+    pub fn total_weight(&self, stack: Option<&Stackable>) -> f32 {
+        self.weight * stack.map_or(1, |s| s.count) as f32
+    }
+}
+
+/// Potion tags the Items that are drunk rather than read or worn.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Potion {}
+#[allow(unused_variables)]
+impl Build<Potion> for Potion {
+    fn create(key: String) -> Option<Potion> {
+        Some(Potion {})
+    }
+}
+
+/// Stackable Items of the same kind are carried as one stack of count items, which takes a
+/// single place in the backpack. Using one takes it off the top of the stack.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Stackable {
+    pub count: i32,
+}
+impl Build<Stackable> for Stackable {
+    fn create(key: String) -> Option<Stackable> {
+        let mut count = 1;
+        for item in key.split_ascii_whitespace() {
+            let entry: Vec<&str> = item.split(":").collect();
+            if entry[0] == "count" {
+                count = entry[1].parse().unwrap_or(1);
             }
-            "Bear Trap" => {
-                bear_trap(ecs, x, y);
+        }
+        Some(Stackable { count })
+    }
+}
+
+impl Stackable {
+    /// How a stack is listed: "3 x Healing Potion", or just the name for a single item.
+    pub fn label(name: &str, stack: Option<&Stackable>) -> String {
+        match stack {
+            Some(stack) if stack.count > 1 => format!("{} x {}", stack.count, name),
+            _ => name.to_string(),
+        }
+    }
+}
+
+/// Takes quantity items off a carried stack and makes them an Entity of their own, in the
+/// same backpack. Asking for the whole stack, or more, returns the stack itself.
+pub fn split_stack(ecs: &mut World, stack: Entity, quantity: i32) -> Option<Entity> {
+    let count = ecs.read_storage::<Stackable>().get(stack).map_or(1, |s| s.count);
+    if quantity >= count {
+        return Some(stack);
+    }
+    let owner = ecs.read_storage::<InBackpack>().get(stack)?.owner;
+    let raw_name = ecs.read_storage::<RawName>().get(stack)?.name.clone();
+
+    let part = spawn_named_entity(ecs, &raw_name, 0, 0)?;
+    ecs.write_storage::<Position>().remove(part);
+    ecs.write_storage::<InBackpack>().insert(part, InBackpack { owner }).expect("Unable to insert backpack entry");
+    let mut stacks = ecs.write_storage::<Stackable>();
+    stacks.insert(part, Stackable { count: quantity }).expect("Unable to insert stack");
+    if let Some(stack) = stacks.get_mut(stack) {
+        stack.count -= quantity;
+    }
+    Some(part)
+}
+
+/// MagicItem marks the Items that are not recognised on sight. Until their kind is identified
+/// they go by the made-up name Identification gave it for this run, e.g. "kind:potion" for a
+/// "Swirling Purple Potion" or "kind:scroll" for a "Scroll of Xyzzy".
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct MagicItem {
+    pub kind: String,
+}
+impl Build<MagicItem> for MagicItem {
+    fn create(key: String) -> Option<MagicItem> {
+        let mut kind = String::new();
+        for item in key.split_ascii_whitespace() {
+            let entry: Vec<&str> = item.split(":").collect();
+            if entry[0] == "kind" {
+                kind = entry[1].to_string();
             }
-            _ => {}
         }
+        if kind.is_empty() { None } else { Some(MagicItem { kind }) }
     }
 }
 
-fn item_table(map: &Map) -> RandomTable {
-    RandomTable::new()
-        .add("Healing Potion", 7)
-        .add("Fireball Scroll", 2)
-        .add("Confusion Scroll", 1 + map.depth)
-        .add("Magic Missle Scroll", 2)
-        .add("Dagger", 1)
-        .add("Shield", 1)
-        .add("Longsword", map.depth - 1)
-        .add("Tower Shield", map.depth - 1)
-        .add("Rations", 7)
-        .add("Magic Mapping Scroll", map.depth/2 - 1 )
-        .add("Bear Trap", 1)
+/// Identifies makes an Item, such as the Scroll of Identify, reveal what every MagicItem in
+/// its user's backpack really is.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Identifies {}
+#[allow(unused_variables)]
+impl Build<Identifies> for Identifies {
+    fn create(key: String) -> Option<Identifies> {
+        Some(Identifies {})
+    }
 }
 
-/// 
+/// Charges let an Item such as a wand or a staff be used again and again, until they run out;
+/// e.g. "max:5". An Item is spawned fully charged.
 #[derive(Component, Serialize, Deserialize, Clone, Debug)]
-pub struct Potion {}
+pub struct Charges {
+    pub charges: i32,
+    pub max_charges: i32,
+}
+impl Build<Charges> for Charges {
+    fn create(key: String) -> Option<Charges> {
+        let mut max_charges = 0;
+        for item in key.split_ascii_whitespace() {
+            let entry: Vec<&str> = item.split(":").collect();
+            if entry[0] == "max" {
+                max_charges = entry[1].parse().unwrap_or(0);
+            }
+        }
+        if max_charges < 1 {
+            return None;
+        }
+        Some(Charges { charges: max_charges, max_charges })
+    }
+}
 
-fn healing_potion(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Item {})
-        .with(Name {
-            name: "Healing Potion".into(),
-        })
-        .with(Position::new(x, y))
-        .with(Renderable {
-            glyph: rltk::to_cp437('|'),
-            fg: rltk::RGB::named(rltk::WHITE),
-            bg: rltk::RGB::named(rltk::BLACK),
-            render_order: 3,
-        })
-        .with(Potion {})
-        .with(Consumable {
-            use_verb: "quaff".into(),
-        })
-        .with(ProvidesHealing { heal_amount: 15 })
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+/// Recharges makes an Item, such as the Scroll of Recharging, restore every charge to the
+/// wands and staves in its user's backpack.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Recharges {}
+#[allow(unused_variables)]
+impl Build<Recharges> for Recharges {
+    fn create(key: String) -> Option<Recharges> {
+        Some(Recharges {})
+    }
 }
 
-fn magic_missile_scroll(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Item {})
-        .with(Name {
-            name: "Magic Missile Scroll".into(),
-        })
-        .with(Position::new(x, y))
-        .with(Renderable {
-            glyph: rltk::to_cp437(')'),
-            fg: RGB::named(rltk::CYAN),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 3,
-        })
-        .with(Consumable {
-            use_verb: "read".into(),
-        })
-        .with(Ranged { range: 6 })
-        .with(InflictsDamage { damage: 8 })
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+/// Durability is how much more wear a weapon or a piece of armour can take in melee, e.g.
+/// "max:30". Once it is worn down to nothing the Item is broken, and adds nothing to melee
+/// power or armour class until it is repaired.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Durability {
+    pub durability: i32,
+    pub max_durability: i32,
+}
+impl Build<Durability> for Durability {
+    fn create(key: String) -> Option<Durability> {
+        let mut max_durability = 0;
+        for item in key.split_ascii_whitespace() {
+            let entry: Vec<&str> = item.split(":").collect();
+            if entry[0] == "max" {
+                max_durability = entry[1].parse().unwrap_or(0);
+            }
+        }
+        if max_durability < 1 {
+            return None;
+        }
+        Some(Durability { durability: max_durability, max_durability })
+    }
 }
 
-fn fireball_scroll(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Item {})
-        .with(Name {
-            name: "Fireball Scroll".into(),
-        })
-        .with(Position::new(x, y))
-        .with(Renderable {
-            glyph: rltk::to_cp437(')'),
-            fg: RGB::named(rltk::ORANGE),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        })
-        .with(Consumable {
-            use_verb: "read".into(),
-        })
-        .with(Ranged { range: 6 })
-        .with(InflictsDamage { damage: 20 })
-        .with(AreaOfEffect { radius: 3 })
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+impl Durability {
+    pub fn is_broken(&self) -> bool {
+        self.durability <= 0
+    }
+
+    /// Whether an Item with this durability, if any, still does its job.
+    pub fn works(durability: Option<&Durability>) -> bool {
+        !durability.is_some_and(|d| d.is_broken())
+    }
 }
 
-fn confusion_scroll(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Item {})
-        .with(Name {
-            name: "Confusion Scroll".into(),
-        })
-        .with(Position::new(x, y))
-        .with(Renderable {
-            glyph: rltk::to_cp437(')'),
-            fg: RGB::named(rltk::PINK),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        })
-        .with(Consumable {
-            use_verb: "read".into(),
-        })
-        .with(Ranged { range: 6 })
-        .with(Confusion { turns: 4 })
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+/// Repairs makes an Item, such as the Repair Kit, restore the durability of everything its
+/// user carries or wears.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Repairs {}
+#[allow(unused_variables)]
+impl Build<Repairs> for Repairs {
+    fn create(key: String) -> Option<Repairs> {
+        Some(Repairs {})
+    }
 }
 
-fn dagger(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Item {})
-        .with(Name {
-            name: "Dagger".to_string(),
-        })
-        .with(Position::new(x, y))
-        .with(Renderable {
-            glyph: rltk::to_cp437('/'),
-            fg: RGB::named(rltk::CYAN),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        })
-        .with(Equippable {
-            slot: EquipmentSlot::Melee,
-        })
-        .with(MeleePowerBonus { power: 2 })
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+/// Enchantment is the magic rolled into a weapon or a piece of armour when it was made: level is
+/// already added to its melee power or defense. Nobody knows what it is until the Item is
+/// first equipped, when the level becomes part of its name (see Identification::item_name).
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Enchantment {
+    pub level: i32,
+    pub known: bool,
 }
 
-fn shield(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Item {})
-        .with(Name {
-            name: "Shield".to_string(),
-        })
-        .with(Position::new(x, y))
-        .with(Renderable {
-            glyph: rltk::to_cp437('('),
-            fg: RGB::named(rltk::CYAN),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        })
-        .with(Equippable {
-            slot: EquipmentSlot::Shield,
-        })
-        .with(DefenseBonus { defense: 1 })
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+/// Cursed equipment cannot be taken off once it has been put on, until the curse is lifted.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Cursed {}
+#[allow(unused_variables)]
+impl Build<Cursed> for Cursed {
+    fn create(key: String) -> Option<Cursed> {
+        Some(Cursed {})
+    }
 }
 
-fn longsword(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Item {})
-        .with(Name {
-            name: "Longsword".to_string(),
-        })
-        .with(Position::new(x, y))
-        .with(Renderable {
-            glyph: rltk::to_cp437('/'),
-            fg: RGB::named(rltk::YELLOW),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        })
-        .with(Equippable {
-            slot: EquipmentSlot::Melee,
-        })
-        .with(MeleePowerBonus { power: 4 })
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+/// RemovesCurse makes an Item, such as the Scroll of Remove Curse, lift the curse from
+/// everything its user carries or wears.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct RemovesCurse {}
+#[allow(unused_variables)]
+impl Build<RemovesCurse> for RemovesCurse {
+    fn create(key: String) -> Option<RemovesCurse> {
+        Some(RemovesCurse {})
+    }
 }
 
-fn tower_shield(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Item {})
-        .with(Name {
-            name: "Tower Shield".to_string(),
-        })
-        .with(Position::new(x, y))
-        .with(Renderable {
-            glyph: rltk::to_cp437('('),
-            fg: RGB::named(rltk::YELLOW),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        })
-        .with(Equippable {
-            slot: EquipmentSlot::Shield,
-        })
-        .with(DefenseBonus { defense: 3 })
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+/// Rolls the Enchantment of a freshly spawned weapon or piece of armour. The deeper the level,
+/// the likelier it is to be enchanted and the stronger the enchantment, up to +3; one enchanted
+/// Item in three is cursed instead, with as strong a negative one.
+pub fn enchant(ecs: &mut World, item: Entity, depth: i32) {
+    if !ecs.read_storage::<MeleePowerBonus>().contains(item) && !ecs.read_storage::<DefenseBonus>().contains(item) {
+        return;
+    }
+    let level = {
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        if rng.roll_dice(1, 100) > i32::min(20 + depth * 5, 70) {
+            return;
+        }
+        let strength = rng.roll_dice(1, i32::clamp(1 + depth / 3, 1, 3));
+        if rng.roll_dice(1, 3) == 1 { -strength } else { strength }
+    };
+
+    if let Some(power) = ecs.write_storage::<MeleePowerBonus>().get_mut(item) {
+        power.power += level;
+    }
+    if let Some(defense) = ecs.write_storage::<DefenseBonus>().get_mut(item) {
+        defense.defense += level;
+    }
+    ecs.write_storage::<Enchantment>()
+        .insert(item, Enchantment { level, known: false })
+        .expect("Unable to insert enchantment");
+    if level < 0 {
+        ecs.write_storage::<Cursed>().insert(item, Cursed {}).expect("Unable to insert curse");
+    }
 }
 
-fn rations(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Item{})
-        .with(Name{ name : "Rations".to_string() })
-        .with(Position::new(x, y))
-        .with(Renderable{
-            glyph: rltk::to_cp437('%'),
-            fg: RGB::named(rltk::GREEN),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2
-        })
-        .with(ProvidesFood{})
-        .with(Consumable{ use_verb: "eat".into() })
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+const POTION_ADJECTIVES: [&str; 10] =
+    ["Swirling", "Bubbling", "Murky", "Fizzing", "Cloudy", "Glowing", "Oily", "Smoking", "Viscous", "Sparkling"];
+const POTION_COLOURS: [&str; 10] =
+    ["Purple", "Red", "Green", "Blue", "Golden", "Black", "Silver", "Amber", "Pink", "White"];
+const SCROLL_SYLLABLES: [&str; 16] =
+    ["xy", "zzy", "fro", "bo", "zz", "ka", "lum", "ner", "tha", "gor", "ish", "pla", "vex", "mu", "dra", "qua"];
+
+/// Identification is the run's record of which magic items the player has learned to
+/// recognise, and of the names the others go by until then. It is a resource, saved along
+/// with the game, and every run rolls its own names from the run seed.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Identification {
+    /// The made-up name of every MagicItem, keyed by its real name.
+    aliases: HashMap<String, String>,
+    identified: HashSet<String>,
 }
 
-fn magic_mapping_scroll(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position::new( x, y ))
-        .with(Renderable{
-            glyph: rltk::to_cp437(')'),
-            fg: RGB::named(rltk::CYAN3),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2
-        })
-        .with(Name{ name : "Scroll of Magic Mapping".to_string() })
-        .with(Item{})
-        .with(MagicMapper{})
-        .with(Consumable{ use_verb: "read".into() })
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+impl Identification {
+    pub fn new(seed: u64) -> Identification {
+        let mut rng = RandomNumberGenerator::seeded(seed.rotate_left(16));
+        let mut aliases = HashMap::new();
+        let mut used: HashSet<String> = HashSet::new();
+        for raw in raws().with_component("MagicItem") {
+            let Some(magic) = MagicItem::create(raw.components["MagicItem"].clone()) else { continue; };
+            let alias = loop {
+                let alias = match magic.kind.as_ref() {
+                    "potion" => format!("{} {} Potion", pick(&mut rng, &POTION_ADJECTIVES), pick(&mut rng, &POTION_COLOURS)),
+                    _ => {
+                        let word: String = (0..rng.range(2, 4)).map(|_| pick(&mut rng, &SCROLL_SYLLABLES)).collect();
+                        format!("Scroll of {}{}", word[..1].to_uppercase(), &word[1..])
+                    }
+                };
+                if used.insert(alias.clone()) {
+                    break alias;
+                }
+            };
+            aliases.insert(raw.name.clone(), alias);
+        }
+        Identification { aliases, identified: HashSet::new() }
+    }
+
+    /// What an item whose real name is real_name is called until it is identified.
+    pub fn display_name(&self, real_name: &str) -> String {
+        match self.aliases.get(real_name) {
+            Some(alias) if !self.identified.contains(real_name) => alias.clone(),
+            _ => real_name.to_string(),
+        }
+    }
+
+    /// True when items whose real name is real_name are known for what they are, because they
+    /// are not magic or have been identified.
+    pub fn knows(&self, real_name: &str) -> bool {
+        !self.aliases.contains_key(real_name) || self.identified.contains(real_name)
+    }
+
+    /// The Name an item spawned from the raw real_name goes by: its made-up name until it is
+    /// identified, with its Enchantment in front once that is known, e.g. "+1 Longsword".
+    pub fn item_name(&self, real_name: &str, enchantment: Option<&Enchantment>) -> String {
+        match enchantment {
+            Some(enchantment) if enchantment.known => format!("{:+} {}", enchantment.level, self.display_name(real_name)),
+            _ => self.display_name(real_name),
+        }
+    }
+
+    /// Identifies the items whose real name is real_name, giving every one of them that exists
+    /// its real name back.
+    pub fn identify<D: Deref<Target = MaskedStorage<Enchantment>>>(
+        &mut self,
+        real_name: &str,
+        names: &mut WriteStorage<Name>,
+        raw_names: &ReadStorage<RawName>,
+        enchantments: &Storage<Enchantment, D>,
+    ) {
+        self.identified.insert(real_name.to_string());
+        for (name, raw_name, enchantment) in (names, raw_names, enchantments.maybe()).join() {
+            if raw_name.name == real_name {
+                name.name = self.item_name(real_name, enchantment);
+            }
+        }
+    }
 }
 
-fn bear_trap(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Name{ name : "Bear Trap".to_string() })
-        .with(Position::new(x, y ))
-        .with(Renderable{
-            glyph: rltk::to_cp437('^'),
-            fg: RGB::named(rltk::RED),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2
-        })
-        .with(Hidden {} )
-        .with(EntryTrigger {})
-        .with(InflictsDamage { damage: 6 })
-        .with(SingleActivation {})
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
-}
\ No newline at end of file
+fn pick<'a>(rng: &mut RandomNumberGenerator, choices: &[&'a str]) -> &'a str {
+    choices[(rng.roll_dice(1, choices.len() as i32) - 1) as usize]
+}
@@ -7,11 +7,11 @@ pub mod item;
 pub mod monster;
 pub mod player;
 pub mod random_table;
+pub mod raws;
 
-use crate::map_builders::map::Map;
-use crate::spawner::monster::MonType;
-use crate::spawner::random_table::*;
-use crate::spawner::{item::Item, monster::Monster, player::Player};
+use crate::map_builders::{level_seed, map::Map};
+use crate::spawner::player::Player;
+use crate::spawner::raws::{raws, spawn_named_entity};
 
 pub enum SpawnSeed {
     Player,
@@ -19,6 +19,25 @@ pub enum SpawnSeed {
     Item,
 }
 
+/// Populates a freshly built level with its Monsters and Items: first whatever the builders
+/// placed in map.spawn_list, then the random spawns for each region.
+///
+/// The RandomNumberGenerator resource is reseeded from the Map's seed and depth first, so the
+/// spawn list for a given (seed, depth) is always the same.
+pub fn spawn_level(ecs: &mut World, map: &Map) {
+    ecs.insert(RandomNumberGenerator::seeded(level_seed(map.seed, map.depth).rotate_left(32)));
+    for (idx, name) in map.spawn_list.iter() {
+        let x = *idx as i32 % map.width;
+        let y = *idx as i32 / map.width;
+        match spawn_named_entity(ecs, name, x, y) {
+            Some(entity) => item::enchant(ecs, entity, map.depth),
+            None => rltk::console::log(format!("WARNING: cannot spawn {}, it is not in the raws", name)),
+        }
+    }
+    spawn(ecs, map, SpawnSeed::Monster);
+    spawn(ecs, map, SpawnSeed::Item);
+}
+
 pub fn spawn(ecs: &mut World, map: &Map, item: SpawnSeed) {
     match item {
         SpawnSeed::Player => {
@@ -34,8 +53,9 @@ pub fn spawn(ecs: &mut World, map: &Map, item: SpawnSeed) {
                 {
                     let mut rng = ecs.write_resource::<RandomNumberGenerator>();
                     let max_mon = map.regions.len() / 27 + 1;
-                    monster_name = monster_table(map).roll(&mut rng);   
-                    let num_monsters = rng.roll_dice(1, max_mon as i32) + (map.depth - 1);
+                    monster_name = raws().monster_table(map.depth).roll(&mut rng);
+                    // Small regions cannot hold more monsters than they have tiles.
+                    let num_monsters = i32::min(rng.roll_dice(1, max_mon as i32) + (map.depth - 1), region.len() as i32);
                     
                     for _i in 0..num_monsters {
                         let mut added = false;
@@ -54,11 +74,7 @@ pub fn spawn(ecs: &mut World, map: &Map, item: SpawnSeed) {
                     for idx in monster_spawn_points.iter() {
                         let x = (*idx % map.width as usize) as i32;
                         let y = (*idx / map.width as usize) as i32;
-                        match monster_name.as_ref() {
-                            "Goblin" => goblin(ecs, x, y),
-                            "Orc" => orc(ecs, x, y),
-                            _ => { }
-                        }
+                        spawn_named_entity(ecs, &monster_name, x, y);
                     }
                 }
             }
@@ -70,7 +86,9 @@ pub fn spawn(ecs: &mut World, map: &Map, item: SpawnSeed) {
                     let mut rng = ecs.write_resource::<RandomNumberGenerator>();
                     number = rng.roll_dice(2, 6);
                 }
+                let item_table = raws().item_table(map.depth);
                 let mut chance;
+                let mut item_name;
                 let mut x;
                 let mut y;
                 {
@@ -82,11 +100,13 @@ pub fn spawn(ecs: &mut World, map: &Map, item: SpawnSeed) {
                             let idx = region[rnd_index as usize];
                             x = idx as i32 % map.width;
                             y = idx as i32 / map.width;
+                            item_name = item_table.roll(&mut rng);
                         }
-                        
+
                         if chance < 2 {
-                    
-                            Item::new(ecs, x, y );
+                            if let Some(entity) = spawn_named_entity(ecs, &item_name, x, y) {
+                                item::enchant(ecs, entity, map.depth);
+                            }
                         }
                     }
                 }
@@ -95,16 +115,32 @@ pub fn spawn(ecs: &mut World, map: &Map, item: SpawnSeed) {
     }
 }
 
-fn monster_table(map: &Map) -> RandomTable {
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spawner::components::{Name, Position};
+    use crate::map_builders::build_random_map;
+    use crate::test_fixture::test_world;
 
-    RandomTable::new()
-        .add("Goblin", 10)
-        .add("Orc", 1 + map.depth)
-}
+    fn spawn_list(seed: u64, depth: i32) -> Vec<(String, i32, i32)> {
+        let mut ecs = test_world();
+        let map = build_random_map(depth, seed);
+        ecs.insert(map.clone());
+        spawn_level(&mut ecs, &map);
 
-fn orc(ecs: &mut World, x: i32, y: i32) {
-    Monster::new(ecs, x, y, MonType::Orc);
-}
-fn goblin(ecs: &mut World, x: i32, y: i32) {
-    Monster::new(ecs, x, y, MonType::Goblin);
+        let names = ecs.read_storage::<Name>();
+        let positions = ecs.read_storage::<Position>();
+        let mut spawned: Vec<(String, i32, i32)> = (&names, &positions).join()
+            .map(|(name, pos)| (name.name.clone(), pos.get_x(), pos.get_y()))
+            .collect();
+        spawned.sort();
+        spawned
+    }
+
+    #[test]
+    fn same_seed_and_depth_spawn_identical_entities() {
+        for seed in 1..=4 {
+            assert_eq!(spawn_list(seed, 2), spawn_list(seed, 2));
+        }
+    }
 }
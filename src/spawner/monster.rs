@@ -1,67 +1,105 @@
-use rltk::{FontCharType, RGB};
+use rltk::Point;
 use specs::*;
 use specs_derive::*;
 use serde::{Serialize, Deserialize};
-use specs::saveload::{MarkedBuilder, SimpleMarker};
 
-use crate::components::SerializeMe;
-use crate::spawner::components::*;
-
-pub enum MonType{
-    Orc,
-    Goblin,
-}
+use crate::spawner::components::Build;
 
+/// Monster tags the entities that the monster_ai_system moves and fights with. Every monster
+/// is defined in the raws.
 #[derive(Component, Serialize, Deserialize, Copy, Clone, Debug)]
 pub struct Monster {}
+#[allow(unused_variables)]
+impl Build<Monster> for Monster {
+    fn create(key: String) -> Option<Monster> {
+        Some(Monster {})
+    }
+}
+
+/// What a monster is doing at the moment. The monster_ai_system moves monsters between these
+/// states as they see, lose and get hurt by the player.
+#[derive(PartialEq, Copy, Clone, Serialize, Deserialize, Debug)]
+pub enum AiState {
+    /// Nothing to do: stands still, or wanders if its Behaviour allows it.
+    Idle,
+    /// Can see the player and goes after them.
+    Chase,
+    /// Lost sight of the player and heads for where they were last seen, then looks around
+    /// there for a few turns.
+    Search { turns: i32 },
+    /// Hurt badly enough to run away from the player.
+    Flee,
+    /// Gave up the search and goes back to where it was spawned.
+    ReturnHome,
+}
+
+/// Behaviour is how a monster acts when it is not fighting, set per monster in the raws, e.g.
+/// "wander flee:25 home". wander lets an idle monster roam, flee makes it run from the player
+/// below that percentage of its hit points and home sends it back to its spawn point once it
+/// has lost the player.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Behaviour {
+    pub state: AiState,
+    pub wanders: bool,
+    pub flee_below: i32,
+    pub returns_home: bool,
+    pub home: Option<Point>,
+    pub last_seen: Option<Point>,
+}
+
+impl Behaviour {
+    /// How many turns a monster looks around where it lost the player.
+    pub const SEARCH_TURNS: i32 = 5;
+}
 
-impl Monster {
-    #[allow(dead_code)]
-    pub fn new(ecs: &mut World, x: i32, y: i32, mon: MonType) {
-        let glyph: FontCharType;
-        let name: String;
-        let hp: i32;
-        let hp_max: i32;
-        let attack: i32;
-        let defense: i32;
-        match mon {
-            MonType::Orc => {
-                glyph = rltk::to_cp437('o');
-                name = "Orc".into();
-                hp = 18;
-                hp_max = 18;
-                attack = 4;
-                defense = 1;
-            },
-            MonType::Goblin => {
-                glyph = rltk::to_cp437('g');
-                name = "Goblin".into();
-                hp = 16;
-                hp_max = 16;
-                attack = 4;
-                defense = 2;
+impl Default for Behaviour {
+    fn default() -> Behaviour {
+        Behaviour {
+            state: AiState::Idle,
+            wanders: false,
+            flee_below: 0,
+            returns_home: false,
+            home: None,
+            last_seen: None,
+        }
+    }
+}
+
+impl Build<Behaviour> for Behaviour {
+    fn create(key: String) -> Option<Behaviour> {
+        let mut behaviour = Behaviour::default();
+        for item in key.split_ascii_whitespace() {
+            let entry:Vec<&str> = item.split(":").collect();
+            match entry[0] {
+                "wander" => { behaviour.wanders = true; },
+                "flee" => { behaviour.flee_below = entry.get(1).and_then(|v| v.parse().ok()).unwrap_or(25); },
+                "home" => { behaviour.returns_home = true; },
+                _ => {}
             }
         }
+        Some(behaviour)
+    }
+}
 
-        ecs.create_entity()
-            .with(Actor {})
-            .with(Monster {})
-            .with(Position::new(x, y))
-            .with(Renderable {
-                glyph,
-                fg: RGB::named(rltk::YELLOW),
-                bg: RGB::named(rltk::BLACK),
-                render_order: 2,
-            })
-            .with(Name { name } )
-            .with(BlocksTile {} )
-            .with(CombatStats { hp, hp_max, attack, defense, is_dead: false})
-            .with(Viewshed {
-                visible_tiles: Vec::new(),
-                range: 8,
-                dirty: true,
-            })
-            .marked::<SimpleMarker<SerializeMe>>()
-            .build();
+/// Faction is the side a creature is on, e.g. "orcs" or "vermin". How factions treat each
+/// other is set in the raws' faction_table.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Faction {
+    pub name: String,
+}
+impl Build<Faction> for Faction {
+    fn create(key: String) -> Option<Faction> {
+        let name = key.trim();
+        if name.is_empty() { return None; }
+        Some(Faction { name: name.to_string() })
     }
 }
+
+/// What a member of one Faction does on seeing a member of another.
+#[derive(PartialEq, Copy, Clone, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Reaction {
+    Attack,
+    Ignore,
+    Flee,
+}
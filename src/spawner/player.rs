@@ -4,11 +4,12 @@ use specs_derive::*;
 use serde::{Serialize, Deserialize};
 use specs::saveload::{MarkedBuilder, SimpleMarker};
 
-use crate::components::{EntityMoved, WantsToMelee, WantsToPickupItem, SerializeMe};
+use crate::components::{EntityMoved, InBackpack, WantsToMelee, WantsToPickupItem, SerializeMe};
 use crate::gamelog::GameLog;
-use crate::map_builders::{common::xy_idx, map::Map, map::TileType};
-use crate::spawner::{components::*, item::Item, monster::Monster};
+use crate::map_builders::{map::Map, map::TileType};
+use crate::spawner::{components::*, item::Item, monster::Faction, monster::Monster};
 use crate::state_machine::{RunState, State};
+use crate::systems::pathing_system::PathingMaps;
 
 #[derive(Component, Serialize, Deserialize, Copy, Clone, Debug)]
 pub struct Player {}
@@ -16,8 +17,10 @@ pub struct Player {}
 impl Player {
     #[allow(dead_code)]
     pub fn new(ecs: &mut World, x: i32, y: i32) {
+        let attributes = GeneralStats::roll(&mut ecs.write_resource::<rltk::RandomNumberGenerator>());
+        let hp_max = 90 + attributes.hp_bonus();
         let player = ecs.create_entity()
-            .with(Actor {})
+            .with(Actor::new(Actor::NORMAL_SPEED))
             .with(Player {})
             .with(Position::new(x, y))
             .with(Renderable {
@@ -31,8 +34,8 @@ impl Player {
             })
             .with(BlocksTile {} )
             .with(CombatStats {
-                hp: 90,
-                hp_max: 90,
+                hp: hp_max,
+                hp_max,
                 attack: 6,
                 defense: 2,
                 is_dead: false
@@ -43,6 +46,10 @@ impl Player {
                 dirty: true,
             })
             .with(HungerClock { state: HungerState::WellFed, duration: 20 })
+            .with(attributes)
+            .with(NaturalAttack { name: "fists".to_string(), damage: DiceRoll::new(1, 3, 0) })
+            .with(Experience { level: 1, xp: 0 })
+            .with(Faction { name: "player".to_string() })
             .marked::<SimpleMarker<SerializeMe>>()
             .build();
         ecs.insert(player);
@@ -50,69 +57,138 @@ impl Player {
     }
 }
 
-pub fn player_input(gs: &mut State, ctx: &mut rltk::Rltk) -> RunState {
-    use rltk::VirtualKeyCode;
+/// PlayerCommand is everything the player can ask for on their turn. Keyboard input is
+/// translated into these by player_input, while the headless driver feeds them in directly.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum PlayerCommand {
+    Move { delta_x: i32, delta_y: i32 },
+    PickUp,
+    Wait,
+    Descend,
+    Ascend,
+    Fire,
+    ShowInventory,
+    ShowDropItem,
+    ShowRemoveItem,
+    ShowEquipment,
+    CloseDoor,
+    LockDoor,
+    SaveGame,
+}
 
+pub fn player_input(gs: &mut State, ctx: &mut rltk::Rltk) -> RunState {
     let runstate: RunState;
     {
         runstate = *gs.ecs.fetch::<RunState>();
     }
     if runstate == RunState::ShowInventory { return runstate; }
-    // Player movement
-    match ctx.key {
-        None => {
-            return RunState::AwaitingInput;
-        } // Nothing happened
-        Some(key) => match key {
-            VirtualKeyCode::Left | VirtualKeyCode::Numpad4 | VirtualKeyCode::H => {
-                try_move_player(&mut gs.ecs, -1, 0)
-            }
-            VirtualKeyCode::Right | VirtualKeyCode::Numpad6 | VirtualKeyCode::L => {
-                try_move_player(&mut gs.ecs, 1, 0)
-            }
-            VirtualKeyCode::Up | VirtualKeyCode::Numpad8 | VirtualKeyCode::J => {
-                try_move_player(&mut gs.ecs, 0, -1)
-            }
-            VirtualKeyCode::Down | VirtualKeyCode::Numpad2 | VirtualKeyCode::K => {
-                try_move_player(&mut gs.ecs, 0, 1)
-            }
-            // Diagonals
-            VirtualKeyCode::Numpad9 | VirtualKeyCode::Y => try_move_player(&mut gs.ecs, 1, -1),
 
-            VirtualKeyCode::Numpad7 | VirtualKeyCode::U => try_move_player(&mut gs.ecs, -1, -1),
+    match ctx.key.and_then(key_to_command) {
+        None => RunState::AwaitingInput, // Nothing happened
+        Some(command) => perform_command(&mut gs.ecs, command),
+    }
+}
 
-            VirtualKeyCode::Numpad3 | VirtualKeyCode::N => try_move_player(&mut gs.ecs, 1, 1),
+/// Maps a key press to the PlayerCommand it stands for, if any.
+pub fn key_to_command(key: rltk::VirtualKeyCode) -> Option<PlayerCommand> {
+    use rltk::VirtualKeyCode;
 
-            VirtualKeyCode::Numpad1 | VirtualKeyCode::B => try_move_player(&mut gs.ecs, -1, 1),
+    let command = match key {
+        // Player movement
+        VirtualKeyCode::Left | VirtualKeyCode::Numpad4 | VirtualKeyCode::H => {
+            PlayerCommand::Move { delta_x: -1, delta_y: 0 }
+        }
+        VirtualKeyCode::Right | VirtualKeyCode::Numpad6 | VirtualKeyCode::L => {
+            PlayerCommand::Move { delta_x: 1, delta_y: 0 }
+        }
+        VirtualKeyCode::Up | VirtualKeyCode::Numpad8 | VirtualKeyCode::J => {
+            PlayerCommand::Move { delta_x: 0, delta_y: -1 }
+        }
+        VirtualKeyCode::Down | VirtualKeyCode::Numpad2 | VirtualKeyCode::K => {
+            PlayerCommand::Move { delta_x: 0, delta_y: 1 }
+        }
+        // Diagonals
+        VirtualKeyCode::Numpad9 | VirtualKeyCode::Y => PlayerCommand::Move { delta_x: 1, delta_y: -1 },
 
-            VirtualKeyCode::G => get_item(&mut gs.ecs),
+        VirtualKeyCode::Numpad7 | VirtualKeyCode::U => PlayerCommand::Move { delta_x: -1, delta_y: -1 },
 
-            VirtualKeyCode::I => return RunState::ShowInventory,
+        VirtualKeyCode::Numpad3 | VirtualKeyCode::N => PlayerCommand::Move { delta_x: 1, delta_y: 1 },
 
-            VirtualKeyCode::D => return RunState::ShowDropItem,
+        VirtualKeyCode::Numpad1 | VirtualKeyCode::B => PlayerCommand::Move { delta_x: -1, delta_y: 1 },
 
-            VirtualKeyCode::Escape => return RunState::SaveGame,
+        VirtualKeyCode::G => PlayerCommand::PickUp,
 
-            VirtualKeyCode::R => return RunState::ShowRemoveItem,
+        VirtualKeyCode::I => PlayerCommand::ShowInventory,
 
-            // Skip Turn
-            VirtualKeyCode::Numpad5 => return skip_turn(&mut gs.ecs),
+        VirtualKeyCode::D => PlayerCommand::ShowDropItem,
 
-            VirtualKeyCode::Space => return skip_turn(&mut gs.ecs),
+        VirtualKeyCode::Escape => PlayerCommand::SaveGame,
 
-            // Level changes
-            VirtualKeyCode::Period => {
-                if try_next_level(&mut gs.ecs) {
-                    return RunState::NextLevel;
-                }
-            }
+        VirtualKeyCode::R => PlayerCommand::ShowRemoveItem,
+
+        VirtualKeyCode::E => PlayerCommand::ShowEquipment,
+
+        VirtualKeyCode::F => PlayerCommand::Fire,
+
+        VirtualKeyCode::C => PlayerCommand::CloseDoor,
+
+        // Turn a key in a lock
+        VirtualKeyCode::T => PlayerCommand::LockDoor,
 
-            _ => {
-                return RunState::AwaitingInput;
+        // Skip Turn
+        VirtualKeyCode::Numpad5 | VirtualKeyCode::Space => PlayerCommand::Wait,
+
+        // Level changes
+        VirtualKeyCode::Period => PlayerCommand::Descend,
+        VirtualKeyCode::Comma => PlayerCommand::Ascend,
+
+        _ => return None,
+    };
+    Some(command)
+}
+
+/// Carries out a PlayerCommand and returns the RunState the game should move to.
+pub fn perform_command(ecs: &mut World, command: PlayerCommand) -> RunState {
+    match command {
+        PlayerCommand::Move { delta_x, delta_y } => {
+            let (delta_x, delta_y) = stagger(ecs, delta_x, delta_y);
+            return try_move_player(ecs, delta_x, delta_y);
+        }
+        PlayerCommand::PickUp => get_item(ecs),
+        PlayerCommand::Wait => return skip_turn(ecs),
+        PlayerCommand::Descend => {
+            if try_next_level(ecs) {
+                return RunState::NextLevel;
+            }
+        }
+        PlayerCommand::Ascend => {
+            if try_previous_level(ecs) {
+                return RunState::PreviousLevel;
             }
-        },
+        }
+        PlayerCommand::Fire => return try_fire(ecs),
+        PlayerCommand::ShowInventory => return RunState::ShowInventory,
+        PlayerCommand::ShowDropItem => return RunState::ShowDropItem,
+        PlayerCommand::ShowRemoveItem => return RunState::ShowRemoveItem,
+        PlayerCommand::ShowEquipment => return RunState::ShowEquipment,
+        PlayerCommand::CloseDoor => return try_close_door(ecs),
+        PlayerCommand::LockDoor => return try_lock_door(ecs),
+        PlayerCommand::SaveGame => return RunState::SaveGame,
+    }
+    RunState::Ticking
+}
+
+/// A confused player stumbles in a random direction instead of the one they asked for.
+fn stagger(ecs: &World, delta_x: i32, delta_y: i32) -> (i32, i32) {
+    let player_entity = ecs.fetch::<Entity>();
+    let confused = ecs.read_storage::<StatusEffects>()
+        .get(*player_entity)
+        .is_some_and(|s| s.has(StatusKind::Confusion));
+    if !confused {
+        return (delta_x, delta_y);
     }
-    return RunState::PlayerTurn;
+    let mut rng = ecs.write_resource::<rltk::RandomNumberGenerator>();
+    (rng.range(-1, 2), rng.range(-1, 2))
 }
 
 fn skip_turn(ecs: &mut World) -> RunState {
@@ -128,7 +204,7 @@ fn skip_turn(ecs: &mut World) -> RunState {
         let mut can_heal = true;
         let viewshed = viewshed_components.get(entity).unwrap();
         for tile in viewshed.visible_tiles.iter() {
-            let idx = xy_idx(tile.x, tile.y);
+            let idx = worldmap_resource.xy_idx(tile.x, tile.y);
             for entity_id in worldmap_resource.tile_content[idx].iter() {
                 let mob = monsters.get(*entity_id);
                 match mob {
@@ -155,13 +231,13 @@ fn skip_turn(ecs: &mut World) -> RunState {
         }
     }
 
-    RunState::PlayerTurn
+    RunState::Ticking
 }
 
 pub fn try_next_level(ecs: &mut World) -> bool {
     let player_pos = ecs.fetch::<Point>();
     let map = ecs.fetch::<Map>();
-    let player_idx = xy_idx(player_pos.x, player_pos.y);
+    let player_idx = map.xy_idx(player_pos.x, player_pos.y);
     if map.tiles[player_idx] == TileType::DownStairs {
         true
     } else {
@@ -171,6 +247,209 @@ pub fn try_next_level(ecs: &mut World) -> bool {
     }
 }
 
+pub fn try_previous_level(ecs: &mut World) -> bool {
+    let player_pos = ecs.fetch::<Point>();
+    let map = ecs.fetch::<Map>();
+    let player_idx = map.xy_idx(player_pos.x, player_pos.y);
+    let mut gamelog = ecs.fetch_mut::<GameLog>();
+    if map.tiles[player_idx] != TileType::UpStairs {
+        gamelog.entries.push("There is no way up from here.".to_string());
+        false
+    } else if map.depth <= 1 {
+        gamelog.entries.push("The way out of the dungeon is sealed behind you.".to_string());
+        false
+    } else {
+        true
+    }
+}
+
+/// Checks that the player has a ranged weapon ready and, for a bow or crossbow, something to
+/// shoot with it. If so the target is picked next; if not, no turn is spent.
+fn try_fire(ecs: &mut World) -> RunState {
+    let player_entity = ecs.fetch::<Entity>();
+    let entities = ecs.entities();
+    let equipped = ecs.read_storage::<Equipped>();
+    let ranged_weapons = ecs.read_storage::<RangedWeapon>();
+    let ammunition = ecs.read_storage::<Ammunition>();
+    let backpack = ecs.read_storage::<InBackpack>();
+    let names = ecs.read_storage::<Name>();
+    let mut gamelog = ecs.fetch_mut::<GameLog>();
+
+    let weapon = (&entities, &ranged_weapons, &equipped)
+        .join()
+        .find(|(_e, _w, equipped_by)| equipped_by.owner == *player_entity);
+    let Some((weapon_entity, weapon, _equipped_by)) = weapon else {
+        gamelog.entries.push("You have no ranged weapon ready.".to_string());
+        return RunState::AwaitingInput;
+    };
+
+    if let Some(kind) = &weapon.ammo {
+        let loaded = (&ammunition, &backpack)
+            .join()
+            .any(|(ammo, in_pack)| in_pack.owner == *player_entity && ammo.kind == *kind);
+        if !loaded {
+            gamelog.entries.push(format!(
+                "You have nothing to shoot from the {}.",
+                names.get(weapon_entity).map_or("weapon", |n| n.name.as_str())
+            ));
+            return RunState::AwaitingInput;
+        }
+    }
+
+    RunState::ShowFireTargeting { range: weapon.range }
+}
+
+/// Closes an open Door next to the player. Nothing happens, and no turn is spent, when there
+/// is no open Door to close or something stands in the doorway.
+fn try_close_door(ecs: &mut World) -> RunState {
+    let player_pos = *ecs.fetch::<Point>();
+    let entities = ecs.entities();
+    let positions = ecs.read_storage::<Position>();
+    let mut doors = ecs.write_storage::<Door>();
+    let mut renderables = ecs.write_storage::<Renderable>();
+    let mut viewsheds = ecs.write_storage::<Viewshed>();
+    let map = ecs.fetch::<Map>();
+    let mut gamelog = ecs.fetch_mut::<GameLog>();
+
+    let door = (&entities, &doors, &positions).join().find(|(_e, door, pos)| {
+        door.open
+            && i32::abs(pos.get_x() - player_pos.x) <= 1
+            && i32::abs(pos.get_y() - player_pos.y) <= 1
+            && (pos.get_x(), pos.get_y()) != (player_pos.x, player_pos.y)
+    });
+    let Some((door_entity, _door, pos)) = door else {
+        gamelog.entries.push("There is no open door next to you.".to_string());
+        return RunState::AwaitingInput;
+    };
+    if map.tile_content[map.xy_idx(pos.get_x(), pos.get_y())].iter().any(|e| *e != door_entity) {
+        gamelog.entries.push("Something is in the way.".to_string());
+        return RunState::AwaitingInput;
+    }
+
+    let door = doors.get_mut(door_entity).unwrap();
+    door.set_open(false, renderables.get_mut(door_entity));
+    Viewshed::look_again_at(&mut viewsheds, Point::new(pos.get_x(), pos.get_y()));
+    gamelog.entries.push("You close the door.".to_string());
+    RunState::Ticking
+}
+
+/// Locks a closed Door next to the player with a Key they carry. Nothing happens, and no turn
+/// is spent, when there is no such Door or none of their keys fits it.
+fn try_lock_door(ecs: &mut World) -> RunState {
+    let player_pos = *ecs.fetch::<Point>();
+    let player_entity = ecs.fetch::<Entity>();
+    let entities = ecs.entities();
+    let positions = ecs.read_storage::<Position>();
+    let mut doors = ecs.write_storage::<Door>();
+    let keys = ecs.read_storage::<Key>();
+    let backpack = ecs.read_storage::<InBackpack>();
+    let names = ecs.read_storage::<Name>();
+    let mut map = ecs.fetch_mut::<Map>();
+    let mut pathing = ecs.write_resource::<PathingMaps>();
+    let mut gamelog = ecs.fetch_mut::<GameLog>();
+
+    let door = (&entities, &doors, &positions).join().find(|(_e, door, pos)| {
+        !door.open
+            && !door.locked
+            && i32::abs(pos.get_x() - player_pos.x) <= 1
+            && i32::abs(pos.get_y() - player_pos.y) <= 1
+    });
+    let Some((door_entity, door, pos)) = door else {
+        gamelog.entries.push("There is no closed door next to you to lock.".to_string());
+        return RunState::AwaitingInput;
+    };
+    let key = (&entities, &keys, &backpack)
+        .join()
+        .find(|(_e, key, in_pack)| in_pack.owner == *player_entity && door.fits(key));
+    let Some((key_entity, _key, _in_pack)) = key else {
+        gamelog.entries.push("You have no key that fits this door.".to_string());
+        return RunState::AwaitingInput;
+    };
+
+    doors.get_mut(door_entity).unwrap().locked = true;
+    // Monsters have to find another way round from now on.
+    let idx = map.xy_idx(pos.get_x(), pos.get_y());
+    map.locked.insert(idx);
+    pathing.dirty = true;
+    let key_name = names.get(key_entity).map_or("key", |n| n.name.as_str());
+    gamelog.entries.push(format!("You lock the door with the {}.", key_name));
+    RunState::Ticking
+}
+
+/// LevelUpChoice is what the player picks on reaching a new level: one more point in an
+/// attribute, or the Toughness perk for extra hit points.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum LevelUpChoice {
+    Strength,
+    Dexterity,
+    Constitution,
+    Intelligence,
+    Toughness,
+}
+
+impl LevelUpChoice {
+    pub const ALL: [LevelUpChoice; 5] = [
+        LevelUpChoice::Strength,
+        LevelUpChoice::Dexterity,
+        LevelUpChoice::Constitution,
+        LevelUpChoice::Intelligence,
+        LevelUpChoice::Toughness,
+    ];
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            LevelUpChoice::Strength => "Strength +1: hit harder and carry more",
+            LevelUpChoice::Dexterity => "Dexterity +1: hit and dodge more often",
+            LevelUpChoice::Constitution => "Constitution +1: more hit points",
+            LevelUpChoice::Intelligence => "Intelligence +1: stronger magic",
+            LevelUpChoice::Toughness => "Toughness: 10 more hit points",
+        }
+    }
+}
+
+/// Hit points gained with every level, before the constitution modifier.
+const LEVEL_UP_HP: i32 = 10;
+
+/// True when the player has the experience for another level.
+pub fn can_level_up(ecs: &World) -> bool {
+    let player_entity = ecs.fetch::<Entity>();
+    ecs.read_storage::<Experience>().get(*player_entity).is_some_and(|xp| xp.can_level_up())
+}
+
+/// Takes the player up one level: more hit points, a full heal and the chosen improvement.
+pub fn level_up(ecs: &mut World, choice: LevelUpChoice) {
+    let player_entity = *ecs.fetch::<Entity>();
+    let mut experience = ecs.write_storage::<Experience>();
+    let mut attributes = ecs.write_storage::<GeneralStats>();
+    let mut combat_stats = ecs.write_storage::<CombatStats>();
+    let mut gamelog = ecs.fetch_mut::<GameLog>();
+
+    let Some(experience) = experience.get_mut(player_entity) else { return; };
+    experience.level += 1;
+
+    let mut hp_gain = LEVEL_UP_HP;
+    if let Some(attributes) = attributes.get_mut(player_entity) {
+        let old_hp_bonus = attributes.hp_bonus();
+        match choice {
+            LevelUpChoice::Strength => attributes.strength += 1,
+            LevelUpChoice::Dexterity => attributes.dexterity += 1,
+            LevelUpChoice::Constitution => attributes.constitution += 1,
+            LevelUpChoice::Intelligence => attributes.intelligence += 1,
+            LevelUpChoice::Toughness => {}
+        }
+        hp_gain += GeneralStats::modifier(attributes.constitution) + attributes.hp_bonus() - old_hp_bonus;
+    }
+    if choice == LevelUpChoice::Toughness {
+        hp_gain += 10;
+    }
+
+    if let Some(stats) = combat_stats.get_mut(player_entity) {
+        stats.hp_max += i32::max(1, hp_gain);
+        stats.hp = stats.hp_max;
+    }
+    gamelog.entries.push(format!("Congratulations, you are now level {}!", experience.level));
+}
+
 fn get_item(ecs: &World) {
     let player_pos = ecs.fetch::<Point>();
     let player_entity = ecs.fetch::<Entity>();
@@ -195,17 +474,27 @@ fn get_item(ecs: &World) {
     }
 }
 
-pub fn try_move_player(ecs: &mut World, delta_x: i32, delta_y: i32) {
+/// Moves the player, or opens the closed door or attacks the creature in the way. Bumping into a
+/// locked door without its key does nothing, and costs no turn.
+pub fn try_move_player(ecs: &mut World, delta_x: i32, delta_y: i32) -> RunState {
     let mut positions = ecs.write_storage::<Position>();
     let mut players = ecs.write_storage::<Player>();
     let mut viewsheds = ecs.write_storage::<Viewshed>();
     let combat_stats = ecs.read_storage::<CombatStats>();
     let mut wants_to_melee = ecs.write_storage::<WantsToMelee>();
     let mut entity_moved = ecs.write_storage::<EntityMoved>();
+    let mut doors = ecs.write_storage::<Door>();
+    let mut renderables = ecs.write_storage::<Renderable>();
+    let keys = ecs.read_storage::<Key>();
+    let backpack = ecs.read_storage::<InBackpack>();
+    let names = ecs.read_storage::<Name>();
     let entities = ecs.entities();
-    let map = ecs.fetch::<Map>();
+    let mut map = ecs.fetch_mut::<Map>();
+    let mut pathing = ecs.write_resource::<PathingMaps>();
+    let mut gamelog = ecs.fetch_mut::<GameLog>();
+    let mut opened_door = None;
 
-    for (pos, _player, viewshed, entity) in
+    'player: for (pos, _player, viewshed, entity) in
         (&mut positions, &mut players, &mut viewsheds, &entities).join()
     {
         
@@ -217,9 +506,32 @@ pub fn try_move_player(ecs: &mut World, delta_x: i32, delta_y: i32) {
             entity_moved.insert(entity, EntityMoved{}).expect("Unable to insert marker");
             viewshed.dirty = true;
         } else {
-            let destination_idx = xy_idx(pos.get_x() + delta_x, pos.get_y() + delta_y);
+            let destination_idx = map.xy_idx(pos.get_x() + delta_x, pos.get_y() + delta_y);
 
             for potential_target in map.tile_content[destination_idx].iter() {
+                // Walking into a closed door opens it, if it is not locked against the player.
+                if let Some(door) = doors.get_mut(*potential_target).filter(|door| !door.open) {
+                    if door.locked {
+                        let key = (&entities, &keys, &backpack)
+                            .join()
+                            .find(|(_e, key, in_pack)| in_pack.owner == entity && door.fits(key));
+                        match key {
+                            None => {
+                                gamelog.entries.push("The door is locked.".to_string());
+                                return RunState::AwaitingInput;
+                            }
+                            Some((key_entity, _key, _in_pack)) => {
+                                let key_name = names.get(key_entity).map_or("key", |n| n.name.as_str());
+                                gamelog.entries.push(format!("You unlock the door with the {}.", key_name));
+                                door.locked = false;
+                            }
+                        }
+                    }
+                    door.set_open(true, renderables.get_mut(*potential_target));
+                    viewshed.dirty = true;
+                    opened_door = Some(Point::new(pos.get_x() + delta_x, pos.get_y() + delta_y));
+                    break 'player;
+                }
                 let target = combat_stats.get(*potential_target);
                 if let Some(_target) = target {
                     wants_to_melee
@@ -230,9 +542,134 @@ pub fn try_move_player(ecs: &mut World, delta_x: i32, delta_y: i32) {
                             },
                         )
                         .expect("Add target failed");
-                    return;
+                    return RunState::Ticking;
                 }
             }
         }
     }
-}
\ No newline at end of file
+
+    // Anything else that could see the door now sees past it, and if it was locked monsters
+    // can now path through it.
+    if let Some(at) = opened_door {
+        Viewshed::look_again_at(&mut viewsheds, at);
+        let idx = map.xy_idx(at.x, at.y);
+        if map.locked.remove(&idx) {
+            pathing.dirty = true;
+        }
+    }
+    RunState::Ticking
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::SufferDamage;
+    use crate::spawner::raws::spawn_named_entity;
+    use crate::systems::damage_system::DamageSystem;
+    use crate::test_fixture::{carry, floor_row, test_world};
+
+    #[test]
+    fn killing_monsters_earns_levels() {
+        let mut ecs = test_world();
+        ecs.insert(Map::new(4, 80, 43));
+        Player::new(&mut ecs, 10, 10);
+        let player = *ecs.fetch::<Entity>();
+
+        let orc = spawn_named_entity(&mut ecs, "Orc", 11, 10).unwrap();
+        let goblin = spawn_named_entity(&mut ecs, "Goblin", 9, 10).unwrap();
+        {
+            let mut damage = ecs.write_storage::<SufferDamage>();
+            SufferDamage::new_damage(&mut damage, orc, 100, true);
+            SufferDamage::new_damage(&mut damage, goblin, 100, false);
+        }
+        DamageSystem {}.run_now(&ecs);
+
+        // Only the Orc was the player's kill: 20 xp on depth 4.
+        assert_eq!(ecs.read_storage::<Experience>().get(player).unwrap().xp, 80);
+        assert!(!can_level_up(&ecs));
+
+        ecs.write_storage::<Experience>().get_mut(player).unwrap().xp = 100;
+        assert!(can_level_up(&ecs));
+        let hp_max = ecs.read_storage::<CombatStats>().get(player).unwrap().hp_max;
+        let strength = ecs.read_storage::<GeneralStats>().get(player).unwrap().strength;
+        level_up(&mut ecs, LevelUpChoice::Strength);
+
+        assert_eq!(ecs.read_storage::<Experience>().get(player).unwrap().level, 2);
+        assert_eq!(ecs.read_storage::<GeneralStats>().get(player).unwrap().strength, strength + 1);
+        assert!(ecs.read_storage::<CombatStats>().get(player).unwrap().hp_max > hp_max);
+        assert!(!can_level_up(&ecs));
+    }
+
+    #[test]
+    fn doors_open_on_bump_and_lock_with_a_key() {
+        use crate::systems::map_indexing_system::MapIndexingSystem;
+
+        let mut ecs = test_world();
+        floor_row(&ecs, 5..10, 10);
+        Player::new(&mut ecs, 5, 10);
+        let player = *ecs.fetch::<Entity>();
+        let door = spawn_named_entity(&mut ecs, "Locked Door", 6, 10).unwrap();
+        let bump = |ecs: &mut World| {
+            MapIndexingSystem {}.run_now(ecs);
+            perform_command(ecs, PlayerCommand::Move { delta_x: 1, delta_y: 0 })
+        };
+
+        // A locked door does not budge, and trying it costs no turn.
+        assert!(bump(&mut ecs) == RunState::AwaitingInput);
+        assert_eq!(ecs.fetch::<GameLog>().entries.last().unwrap(), "The door is locked.");
+        assert!(!ecs.read_storage::<Door>().get(door).unwrap().open);
+        {
+            use rltk::BaseMap;
+            // No path leads through a locked door.
+            let map = ecs.fetch::<Map>();
+            let exits = map.get_available_exits(map.xy_idx(5, 10));
+            assert!(!exits.iter().any(|(idx, _cost)| *idx == map.xy_idx(6, 10)));
+        }
+
+        carry(&mut ecs, player, "Iron Key");
+        bump(&mut ecs);
+        assert_eq!(ecs.fetch::<GameLog>().entries.last().unwrap(), "You unlock the door with the Iron Key.");
+        assert!(ecs.read_storage::<Door>().get(door).unwrap().open);
+        bump(&mut ecs);
+        assert_eq!(ecs.read_storage::<Position>().get(player).unwrap().get_x(), 6);
+
+        // Standing in the doorway keeps it open; from the far side it closes, and only locks
+        // when asked to.
+        MapIndexingSystem {}.run_now(&ecs);
+        assert!(perform_command(&mut ecs, PlayerCommand::CloseDoor) == RunState::AwaitingInput);
+        bump(&mut ecs);
+        MapIndexingSystem {}.run_now(&ecs);
+        assert!(perform_command(&mut ecs, PlayerCommand::CloseDoor) == RunState::Ticking);
+        assert_eq!(ecs.fetch::<GameLog>().entries.last().unwrap(), "You close the door.");
+        assert!(!ecs.read_storage::<Door>().get(door).unwrap().locked);
+        assert!(perform_command(&mut ecs, PlayerCommand::LockDoor) == RunState::Ticking);
+        assert_eq!(ecs.fetch::<GameLog>().entries.last().unwrap(), "You lock the door with the Iron Key.");
+        assert!(ecs.read_storage::<Door>().get(door).unwrap().locked);
+    }
+
+    #[test]
+    fn a_closed_door_hides_the_room_behind_it_at_once() {
+        use crate::systems::map_indexing_system::MapIndexingSystem;
+        use crate::systems::visiblity_system::VisibilitySystem;
+
+        let mut ecs = test_world();
+        floor_row(&ecs, 5..15, 10);
+        Player::new(&mut ecs, 6, 10);
+        spawn_named_entity(&mut ecs, "Door", 7, 10).unwrap();
+        let behind = |ecs: &World| {
+            let map = ecs.fetch::<Map>();
+            map.visible_tiles[map.xy_idx(12, 10)]
+        };
+
+        MapIndexingSystem {}.run_now(&ecs);
+        VisibilitySystem {}.run_now(&ecs);
+        assert!(!behind(&ecs));
+        perform_command(&mut ecs, PlayerCommand::Move { delta_x: 1, delta_y: 0 });
+        VisibilitySystem {}.run_now(&ecs);
+        assert!(behind(&ecs));
+
+        MapIndexingSystem {}.run_now(&ecs);
+        assert!(perform_command(&mut ecs, PlayerCommand::CloseDoor) == RunState::Ticking);
+        VisibilitySystem {}.run_now(&ecs);
+        assert!(!behind(&ecs));
+    }
+}
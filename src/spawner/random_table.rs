@@ -31,17 +31,36 @@ impl RandomTable {
     pub fn roll(&self, rng : &mut RandomNumberGenerator) -> String {
         if self.total_weight == 0 { return "None".to_string(); }
         let mut roll = rng.roll_dice(1, self.total_weight)-1;
-        let mut index : usize = 0;
 
-        while roll > 0 {
-            if roll < self.entries[index].weight {
-                return self.entries[index].name.clone();
+        for entry in self.entries.iter() {
+            if roll < entry.weight {
+                return entry.name.clone();
             }
 
-            roll -= self.entries[index].weight;
-            index += 1;
+            roll -= entry.weight;
         }
 
         "None".to_string()
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_entry_can_be_rolled() {
+        let table = RandomTable::new().add("First", 3).add("Second", 1).add("Third", 1);
+        let mut rng = RandomNumberGenerator::seeded(7);
+        let mut counts = [0; 3];
+        for _ in 0..500 {
+            match table.roll(&mut rng).as_ref() {
+                "First" => counts[0] += 1,
+                "Second" => counts[1] += 1,
+                "Third" => counts[2] += 1,
+                other => panic!("rolled {}", other),
+            }
+        }
+        assert!(counts.iter().all(|c| *c > 0), "{:?}", counts);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,309 @@
+use serde::Deserialize;
+use specs::prelude::*;
+use specs::saveload::{MarkedBuilder, SimpleMarker};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+
+use crate::components::SerializeMe;
+use crate::spawner::components::*;
+use crate::spawner::{
+    item::Charges, item::Cursed, item::Durability, item::Identification, item::Identifies, item::Item, item::MagicItem, item::Potion, item::Recharges, item::RemovesCurse, item::Repairs, item::Stackable, monster::Behaviour, monster::Faction, monster::Monster, monster::Reaction,
+    random_table::RandomTable,
+};
+
+/// The definitions of every Monster, Item and prop, embedded into the binary so that the game
+/// never has to find the file at runtime.
+const SPAWNS_JSON: &str = include_str!("../../raws/spawns.json");
+
+static RAWS: OnceLock<RawMaster> = OnceLock::new();
+
+/// One spawnable thing. Each entry of components maps a component name to the "key:value"
+/// string its Build implementation parses, e.g. "CombatStats": "hp:16 attack:4 defense:2".
+/// Tag components take an empty string.
+#[derive(Deserialize, Debug)]
+pub struct RawEntity {
+    pub name: String,
+    pub components: BTreeMap<String, String>,
+}
+
+/// A line of a spawn table. With add_map_depth the map depth is added to weight, so the entry
+/// grows more common further down; entries whose weight is not positive are left out.
+#[derive(Deserialize, Debug)]
+pub struct SpawnTableEntry {
+    pub name: String,
+    pub weight: i32,
+    #[serde(default)]
+    pub min_depth: i32,
+    #[serde(default = "default_max_depth")]
+    pub max_depth: i32,
+    #[serde(default)]
+    pub add_map_depth: bool,
+}
+
+fn default_max_depth() -> i32 {
+    i32::MAX
+}
+
+/// How the members of the faction called name react to each other faction. The "default"
+/// response covers any faction not listed; without one, and towards their own faction,
+/// creatures ignore each other.
+#[derive(Deserialize, Debug)]
+pub struct FactionInfo {
+    pub name: String,
+    pub responses: HashMap<String, Reaction>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Raws {
+    pub monsters: Vec<RawEntity>,
+    pub items: Vec<RawEntity>,
+    pub props: Vec<RawEntity>,
+    pub monster_spawns: Vec<SpawnTableEntry>,
+    pub item_spawns: Vec<SpawnTableEntry>,
+    #[serde(default)]
+    pub faction_table: Vec<FactionInfo>,
+}
+
+/// RawMaster holds every parsed definition along with a lookup from name to definition.
+pub struct RawMaster {
+    entities: Vec<RawEntity>,
+    index: HashMap<String, usize>,
+    monster_spawns: Vec<SpawnTableEntry>,
+    item_spawns: Vec<SpawnTableEntry>,
+    factions: HashMap<String, HashMap<String, Reaction>>,
+}
+
+impl RawMaster {
+    pub fn load(json: &str) -> RawMaster {
+        let raws: Raws = serde_json::from_str(json).expect("Unable to parse the spawn raws");
+        let mut entities = raws.monsters;
+        entities.extend(raws.items);
+        entities.extend(raws.props);
+
+        let mut index = HashMap::new();
+        for (i, raw) in entities.iter().enumerate() {
+            if index.insert(raw.name.clone(), i).is_some() {
+                rltk::console::log(format!("WARNING: duplicate raw entity name {}", raw.name));
+            }
+        }
+        let factions = raws.faction_table.into_iter().map(|f| (f.name, f.responses)).collect();
+        RawMaster { entities, index, monster_spawns: raws.monster_spawns, item_spawns: raws.item_spawns, factions }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&RawEntity> {
+        self.index.get(name).map(|i| &self.entities[*i])
+    }
+
+    /// Every raw entity that has the named component, in the order they are defined.
+    pub fn with_component<'a>(&'a self, component: &'a str) -> impl Iterator<Item = &'a RawEntity> {
+        self.entities.iter().filter(move |raw| raw.components.contains_key(component))
+    }
+
+    /// How a member of faction my_faction reacts to one of their_faction.
+    pub fn faction_reaction(&self, my_faction: &str, their_faction: &str) -> Reaction {
+        if my_faction == their_faction {
+            return Reaction::Ignore;
+        }
+        self.factions
+            .get(my_faction)
+            .and_then(|responses| responses.get(their_faction).or_else(|| responses.get("default")))
+            .copied()
+            .unwrap_or(Reaction::Ignore)
+    }
+
+    pub fn monster_table(&self, depth: i32) -> RandomTable {
+        spawn_table(&self.monster_spawns, depth)
+    }
+
+    pub fn item_table(&self, depth: i32) -> RandomTable {
+        spawn_table(&self.item_spawns, depth)
+    }
+}
+
+/// The RawMaster built from the embedded raws, loaded the first time it is asked for.
+pub fn raws() -> &'static RawMaster {
+    RAWS.get_or_init(|| RawMaster::load(SPAWNS_JSON))
+}
+
+fn spawn_table(entries: &[SpawnTableEntry], depth: i32) -> RandomTable {
+    let mut table = RandomTable::new();
+    for entry in entries.iter().filter(|e| depth >= e.min_depth && depth <= e.max_depth) {
+        let weight = if entry.add_map_depth { entry.weight + depth } else { entry.weight };
+        if weight > 0 {
+            table = table.add(&entry.name, weight);
+        }
+    }
+    table
+}
+
+/// Creates the raw entity called name at (x, y). Returns None, and spawns nothing, when there
+/// is no such entity in the raws.
+pub fn spawn_named_entity(ecs: &mut World, name: &str, x: i32, y: i32) -> Option<Entity> {
+    let raw = raws().get(name)?;
+
+    let mut builder = ecs
+        .create_entity()
+        .with(Position::new(x, y))
+        .with(Name { name: raw.name.clone() })
+        .with(RawName { name: raw.name.clone() });
+    for (component, key) in raw.components.iter() {
+        builder = match component.as_ref() {
+            "Actor" => with_built::<Actor>(builder, raw, component, key),
+            "Ammunition" => with_built::<Ammunition>(builder, raw, component, key),
+            "AreaOfEffect" => with_built::<AreaOfEffect>(builder, raw, component, key),
+            "Behaviour" => with_built::<Behaviour>(builder, raw, component, key),
+            "BlocksTile" => with_built::<BlocksTile>(builder, raw, component, key),
+            "Charges" => with_built::<Charges>(builder, raw, component, key),
+            "CombatStats" => with_built::<CombatStats>(builder, raw, component, key),
+            "Consumable" => with_built::<Consumable>(builder, raw, component, key),
+            "Cursed" => with_built::<Cursed>(builder, raw, component, key),
+            "DefenseBonus" => with_built::<DefenseBonus>(builder, raw, component, key),
+            "Door" => with_built::<Door>(builder, raw, component, key),
+            "Durability" => with_built::<Durability>(builder, raw, component, key),
+            "EntryTrigger" => with_built::<EntryTrigger>(builder, raw, component, key),
+            "Equippable" => with_built::<Equippable>(builder, raw, component, key),
+            "ExperienceValue" => with_built::<ExperienceValue>(builder, raw, component, key),
+            "Faction" => with_built::<Faction>(builder, raw, component, key),
+            "GeneralStats" => with_built::<GeneralStats>(builder, raw, component, key),
+            "Hidden" => with_built::<Hidden>(builder, raw, component, key),
+            "Identifies" => with_built::<Identifies>(builder, raw, component, key),
+            "InflictsDamage" => with_built::<InflictsDamage>(builder, raw, component, key),
+            "InflictsStatus" => with_built::<InflictsStatus>(builder, raw, component, key),
+            "Item" => with_built::<Item>(builder, raw, component, key),
+            "Key" => with_built::<Key>(builder, raw, component, key),
+            "MagicItem" => with_built::<MagicItem>(builder, raw, component, key),
+            "MagicMapper" => with_built::<MagicMapper>(builder, raw, component, key),
+            "MeleePowerBonus" => with_built::<MeleePowerBonus>(builder, raw, component, key),
+            "Monster" => with_built::<Monster>(builder, raw, component, key),
+            "Name" => with_built::<Name>(builder, raw, component, key),
+            "NaturalAttack" => with_built::<NaturalAttack>(builder, raw, component, key),
+            "Potion" => with_built::<Potion>(builder, raw, component, key),
+            "ProvidesFood" => with_built::<ProvidesFood>(builder, raw, component, key),
+            "ProvidesHealing" => with_built::<ProvidesHealing>(builder, raw, component, key),
+            "Ranged" => with_built::<Ranged>(builder, raw, component, key),
+            "RangedWeapon" => with_built::<RangedWeapon>(builder, raw, component, key),
+            "Recharges" => with_built::<Recharges>(builder, raw, component, key),
+            "RemovesCurse" => with_built::<RemovesCurse>(builder, raw, component, key),
+            "Renderable" => with_built::<Renderable>(builder, raw, component, key),
+            "Repairs" => with_built::<Repairs>(builder, raw, component, key),
+            "SingleActivation" => with_built::<SingleActivation>(builder, raw, component, key),
+            "Stackable" => with_built::<Stackable>(builder, raw, component, key),
+            "Viewshed" => with_built::<Viewshed>(builder, raw, component, key),
+            _ => {
+                rltk::console::log(format!("WARNING: {} has unknown component {}", raw.name, component));
+                builder
+            }
+        };
+    }
+
+    let entity = builder.marked::<SimpleMarker<SerializeMe>>().build();
+
+    // Constitution is applied once everything is built, whatever order the components came in.
+    let attributes = ecs.read_storage::<GeneralStats>().get(entity).cloned();
+    if let (Some(attributes), Some(stats)) = (attributes, ecs.write_storage::<CombatStats>().get_mut(entity)) {
+        stats.hp_max = i32::max(1, stats.hp_max + attributes.hp_bonus());
+        stats.hp = stats.hp_max;
+    }
+
+    // A magic item the player has not identified yet goes by its made-up name.
+    if ecs.read_storage::<MagicItem>().contains(entity) {
+        if let Some(identification) = ecs.try_fetch::<Identification>() {
+            if let Some(name) = ecs.write_storage::<Name>().get_mut(entity) {
+                name.name = identification.display_name(&raw.name);
+            }
+        }
+    }
+
+    Some(entity)
+}
+
+fn with_built<'a, T>(builder: EntityBuilder<'a>, raw: &RawEntity, component: &str, key: &str) -> EntityBuilder<'a>
+where
+    T: Build<T> + Component + Send + Sync,
+{
+    match T::create(key.to_string()) {
+        Some(built) => builder.with(built),
+        None => {
+            rltk::console::log(format!("WARNING: {} has an unusable {} \"{}\"", raw.name, component, key));
+            builder
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixture::test_world;
+
+    #[test]
+    fn every_spawn_table_entry_names_a_raw() {
+        let raws = raws();
+        for entry in raws.monster_spawns.iter().chain(raws.item_spawns.iter()) {
+            assert!(raws.get(&entry.name).is_some(), "{} is not defined in the raws", entry.name);
+        }
+    }
+
+    #[test]
+    fn raw_entities_spawn_with_their_components() {
+        let mut ecs = test_world();
+
+        let goblin = spawn_named_entity(&mut ecs, "Goblin", 3, 4).unwrap();
+        let potion = spawn_named_entity(&mut ecs, "Healing Potion", 5, 6).unwrap();
+        assert!(spawn_named_entity(&mut ecs, "Dragon", 1, 1).is_none());
+
+        let stats = ecs.read_storage::<CombatStats>();
+        assert_eq!(stats.get(goblin).map(|s| (s.hp, s.hp_max, s.attack, s.defense)), Some((16, 16, 4, 2)));
+        assert!(ecs.read_storage::<Monster>().get(goblin).is_some());
+        assert_eq!(ecs.read_storage::<Renderable>().get(goblin).unwrap().glyph, rltk::to_cp437('g'));
+        assert_eq!(ecs.read_storage::<ProvidesHealing>().get(potion).unwrap().heal_amount, 15);
+        assert_eq!(ecs.read_storage::<RawName>().get(potion).unwrap().name, "Healing Potion");
+        assert_eq!(
+            ecs.read_storage::<Name>().get(potion).unwrap().name,
+            ecs.fetch::<Identification>().display_name("Healing Potion")
+        );
+        assert_eq!(ecs.read_storage::<Position>().get(potion).unwrap().get_x(), 5);
+    }
+
+    #[test]
+    fn attributes_adjust_monster_hit_points() {
+        let mut ecs = test_world();
+
+        let orc = spawn_named_entity(&mut ecs, "Orc", 3, 4).unwrap();
+        let rat = spawn_named_entity(&mut ecs, "Giant Rat", 5, 6).unwrap();
+
+        let attributes = ecs.read_storage::<GeneralStats>();
+        let stats = ecs.read_storage::<CombatStats>();
+        assert_eq!(attributes.get(orc).unwrap().constitution, 13);
+        assert_eq!(stats.get(orc).map(|s| (s.hp, s.hp_max)), Some((21, 21)));
+        assert_eq!(stats.get(rat).map(|s| (s.hp, s.hp_max)), Some((3, 3)));
+        assert_eq!(GeneralStats::modifier(9), -1);
+        assert_eq!(GeneralStats::modifier(11), 0);
+    }
+
+    #[test]
+    fn weapons_and_monsters_carry_damage_dice() {
+        let mut ecs = test_world();
+
+        let longsword = spawn_named_entity(&mut ecs, "Longsword", 1, 1).unwrap();
+        let goblin = spawn_named_entity(&mut ecs, "Goblin", 2, 2).unwrap();
+
+        let weapons = ecs.read_storage::<MeleePowerBonus>();
+        let weapon = weapons.get(longsword).unwrap();
+        assert_eq!((weapon.power, weapon.damage), (1, DiceRoll::new(1, 8, 0)));
+        assert_eq!(ecs.read_storage::<NaturalAttack>().get(goblin).unwrap().damage, DiceRoll::new(1, 4, 0));
+
+        assert_eq!(DiceRoll::parse("2d6+1"), Some(DiceRoll::new(2, 6, 1)));
+        assert_eq!(DiceRoll::parse("1d4-1"), Some(DiceRoll::new(1, 4, -1)));
+        assert_eq!(DiceRoll::parse("d"), None);
+    }
+
+    #[test]
+    fn spawn_tables_follow_depth() {
+        let mut rng = rltk::RandomNumberGenerator::seeded(1);
+        let shallow = raws().item_table(1);
+        for _ in 0..200 {
+            let name = shallow.roll(&mut rng);
+            assert!(name != "Longsword" && name != "Scroll of Magic Mapping", "{} rolled on depth 1", name);
+        }
+    }
+}
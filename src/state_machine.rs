@@ -1,25 +1,34 @@
 use rltk::{GameState, Point, Rltk};
 use specs::*;
+use specs::saveload::SimpleMarkerAllocator;
+use std::collections::HashMap;
 use std::{time::Duration, thread::sleep};
 
-use crate::components::{InBackpack, WantsToDropItem, WantsToRemoveItem, WantsToUseItem};
+use crate::components::{self, OtherLevelPosition, SerializeMe, WantsToDropItem, WantsToRemoveItem, WantsToShoot, WantsToUseItem};
 use crate::gamelog;
-use crate::map_builders::{build_random_map, map::Map};
+use crate::map_builders::{build_random_map, dungeon::MasterDungeonMap, map::Map};
 use crate::spawner::{
-    components::{CombatStats, Equipped, Position, Ranged, Viewshed},
-    player::{player_input, Player},
-    spawn, SpawnSeed,
+    components::{register_spawns, CombatStats, Position, Ranged, Viewshed},
+    item::{split_stack, Identification, Stackable},
+    player::{can_level_up, level_up, player_input, Player},
+    spawn, spawn_level, SpawnSeed,
 };
 use crate::systems::{
     damage_system::DamageSystem,
+    hazard_system::HazardSystem,
     hunger_system::HungerSystem,
-    inventory_systems::{ItemCollectionSystem, ItemDropSystem, ItemRemoveSystem, ItemUseSystem},
+    initiative_system::InitiativeSystem,
+    inventory_systems::{EncumbranceSystem, ItemCollectionSystem, ItemDropSystem, ItemRemoveSystem, ItemUseSystem},
     map_indexing_system::MapIndexingSystem,
     melee_combat_system::*,
     monster_ai_system::MonsterAI,
+    ranged_combat_system::RangedCombatSystem,
     particle_system::*,
+    pathing_system::{PathingMaps, PathingSystem},
     saveload_system, ui_system,
+    status_effect_system::StatusEffectSystem,
     trigger_system::*,
+    turn_system::TurnSystem,
     ui_system::*, ui_system::main_menu::*,
     visiblity_system::VisibilitySystem,
 };
@@ -28,20 +37,29 @@ use crate::systems::{
 pub enum RunState {
     AwaitingInput,
     PreRun,
-    PlayerTurn,
-    MonsterTurn,
+    Ticking,
     ShowInventory,
     ShowDropItem,
+    ShowDropQuantity {
+        item: Entity,
+        quantity: i32,
+    },
     ShowTargeting {
         range: i32,
         item: Entity,
     },
+    ShowFireTargeting {
+        range: i32,
+    },
     MainMenu {
         menu_selection: ui_system::MainMenuSelection,
     },
     SaveGame,
     NextLevel,
+    PreviousLevel,
+    LevelUp,
     ShowRemoveItem,
+    ShowEquipment,
     GameOver
 }
 
@@ -50,13 +68,62 @@ pub struct State {
 }
 
 impl State {
-    fn run_systems(&mut self, ctx: &mut Rltk) {
-        ctx.cls();        
-        cull_dead_particles(&mut self.ecs, ctx);
+    /// Creates a new game world for the run started with seed: registers every component,
+    /// inserts the resources the systems expect and builds and populates the first level.
+    pub fn new(seed: u64) -> State {
+        let mut gs = State {
+            ecs: World::new()
+        };
+
+        components::register_components(&mut gs.ecs);
+        register_spawns(&mut gs.ecs);
+
+        gs.ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+
+        let map = build_random_map(1, seed);
+
+        gs.ecs.insert(rltk::RandomNumberGenerator::seeded(seed));
+        gs.ecs.insert(RunState::MainMenu { menu_selection: MainMenuSelection::NewGame} );
+        gs.ecs.insert(gamelog::GameLog{ entries : vec![
+            "Welcome to Rusty Roguelike".to_string(),
+            format!("Dungeon seed: {}", seed),
+        ] });
+        gs.ecs.insert(ParticleBuilder::new());
+        gs.ecs.insert(Particles { particle : HashMap::new() });
+        gs.ecs.insert(map.clone());
+        gs.ecs.insert(MasterDungeonMap::new());
+        gs.ecs.insert(Identification::new(seed));
+        gs.ecs.insert(PathingMaps::default());
+
+        spawn(&mut gs.ecs, &map, SpawnSeed::Player);
+
+        spawn_level(&mut gs.ecs, &map);
+
+        gs
+    }
+
+    /// Draws the map, the interface and the particles. This is the only part of a game turn
+    /// that needs the Rltk context.
+    fn render(&mut self, ctx: &mut Rltk) {
+        ctx.cls();
+        cull_dead_particles(&mut self.ecs, ctx.frame_time_ms);
         let mut uis = UISystem { ctx };
         uis.run_now(&self.ecs);
+    }
+
+    /// Runs every gameplay system once. Nothing here draws, so the headless driver can call
+    /// it without a window.
+    pub fn run_systems(&mut self) {
         let mut vis = VisibilitySystem {};
         vis.run_now(&self.ecs);
+        let mut initiative = InitiativeSystem {};
+        initiative.run_now(&self.ecs);
+        let mut ses = StatusEffectSystem {};
+        ses.run_now(&self.ecs);
+        let mut hazards = HazardSystem {};
+        hazards.run_now(&self.ecs);
+        let mut pathing = PathingSystem {};
+        pathing.run_now(&self.ecs);
         let mut mai = MonsterAI {};
         mai.run_now(&self.ecs);
         let mut tgs = TriggerSystem{};
@@ -65,6 +132,8 @@ impl State {
         mis.run_now(&self.ecs);
         let mut mcs = MeleeCombatSystem {};
         mcs.run_now(&self.ecs);
+        let mut rcs = RangedCombatSystem {};
+        rcs.run_now(&self.ecs);
         let mut dam = DamageSystem {};
         dam.run_now(&self.ecs);
         let mut ins = ItemCollectionSystem {};
@@ -75,77 +144,132 @@ impl State {
         ids.run_now(&self.ecs);
         let mut irs = ItemRemoveSystem {};
         irs.run_now(&self.ecs);
+        let mut ens = EncumbranceSystem {};
+        ens.run_now(&self.ecs);
         let mut hus = HungerSystem {};
         hus.run_now(&self.ecs);
         let mut pss = ParticleSpawnSystem {};
         pss.run_now(&self.ecs);
-        
+        let mut turns = TurnSystem {};
+        turns.run_now(&self.ecs);
+
         self.ecs.maintain();
     }
 
-    fn entities_to_remove_on_level_change(&mut self) -> Vec<Entity> {
-        let entities = self.ecs.entities();
-        let player = self.ecs.read_storage::<Player>();
-        let backpack = self.ecs.read_storage::<InBackpack>();
-        let equipped = self.ecs.read_storage::<Equipped>();
-        let player_entity = self.ecs.fetch::<Entity>();
-
-        let mut to_delete: Vec<Entity> = Vec::new();
-        for entity in entities.join() {
-            let mut should_delete = true;
-
-            // Don't delete the player
-            let p = player.get(entity);
-            if let Some(_p) = p {
-                should_delete = false;
+    /// Plays out a RunState that needs neither input nor the screen: PreRun, Ticking and the
+    /// level changes. Returns the RunState to move to; any other RunState is returned as it
+    /// is. Both tick and the headless driver turn the game with this.
+    pub fn run_turn(&mut self, runstate: RunState) -> RunState {
+        match runstate {
+            RunState::PreRun => {
+                self.run_systems();
+                RunState::AwaitingInput
             }
-
-            // Don't delete the player's equipment
-            let bp = backpack.get(entity);
-            if let Some(bp) = bp {
-                if bp.owner == *player_entity {
-                    should_delete = false;
+            RunState::Ticking => {
+                // Keep handing out turns until it is the player's again, or the player has died.
+                let mut newrunstate = runstate;
+                while newrunstate == RunState::Ticking {
+                    self.run_systems();
+                    delete_the_dead(&mut self.ecs);
+                    newrunstate = *self.ecs.fetch::<RunState>();
                 }
-            }
-
-            // Don't delete wielded equipment.
-            let eq = equipped.get(entity);
-            if let Some(eq) = eq {
-                if eq.owner == *player_entity {
-                    should_delete = false;
+                if newrunstate == RunState::AwaitingInput && can_level_up(&self.ecs) {
+                    RunState::LevelUp
+                } else {
+                    newrunstate
                 }
             }
-
-            if should_delete {
-                to_delete.push(entity);
+            RunState::NextLevel => {
+                self.goto_next_level();
+                RunState::PreRun
             }
+            RunState::PreviousLevel => {
+                self.goto_previous_level();
+                RunState::PreRun
+            }
+            _ => runstate,
         }
+    }
 
-        to_delete
+    /// Swaps the Position of everything on the current level except the player for an
+    /// OtherLevelPosition, so that the level's monsters and items wait there for the player.
+    /// Carried items have no Position and so travel with the player.
+    fn freeze_level_entities(&mut self) {
+        let entities = self.ecs.entities();
+        let mut positions = self.ecs.write_storage::<Position>();
+        let mut other_level_positions = self.ecs.write_storage::<OtherLevelPosition>();
+        let player_entity = self.ecs.fetch::<Entity>();
+        let depth = self.ecs.fetch::<Map>().depth;
+
+        let mut to_freeze: Vec<Entity> = Vec::new();
+        for (entity, pos) in (&entities, &positions).join() {
+            if entity != *player_entity {
+                other_level_positions
+                    .insert(entity, OtherLevelPosition { x: pos.get_x(), y: pos.get_y(), depth })
+                    .expect("Unable to insert other level position");
+                to_freeze.push(entity);
+            }
+        }
+        for entity in to_freeze {
+            positions.remove(entity);
+        }
     }
 
-    fn goto_next_level(&mut self) {
-        // Delete entities that aren't the player or his/her equipment
-        let to_delete = self.entities_to_remove_on_level_change();
-        for target in to_delete {
-            self.ecs
-                .delete_entity(target)
-                .expect("Unable to delete entity");
+    /// Puts back everything that was frozen on the current level.
+    fn thaw_level_entities(&mut self) {
+        let entities = self.ecs.entities();
+        let mut positions = self.ecs.write_storage::<Position>();
+        let mut other_level_positions = self.ecs.write_storage::<OtherLevelPosition>();
+        let depth = self.ecs.fetch::<Map>().depth;
+
+        let mut to_thaw: Vec<Entity> = Vec::new();
+        for (entity, pos) in (&entities, &other_level_positions).join() {
+            if pos.depth == depth {
+                positions
+                    .insert(entity, Position::new(pos.x, pos.y))
+                    .expect("Unable to insert position");
+                to_thaw.push(entity);
+            }
         }
-        // Build a new map and place the player
-        let worldmap;
-        {
+        for entity in to_thaw {
+            other_level_positions.remove(entity);
+        }
+    }
+
+    /// Moves the player to new_depth. The level being left is stored, with its entities, in the
+    /// MasterDungeonMap; a level that has been visited before is taken back out of it, and any
+    /// other level is built and populated from the run seed.
+    fn change_level(&mut self, new_depth: i32) {
+        self.freeze_level_entities();
+
+        let (worldmap, (player_x, player_y), first_visit) = {
             let mut worldmap_resource = self.ecs.write_resource::<Map>();
-            let current_depth = worldmap_resource.depth;
-            *worldmap_resource = build_random_map(current_depth + 1);
-            worldmap = worldmap_resource.clone();
+            let mut dungeon_master = self.ecs.write_resource::<MasterDungeonMap>();
+            dungeon_master.store_map(&worldmap_resource);
+            let going_down = new_depth > worldmap_resource.depth;
+
+            let (new_map, first_visit) = match dungeon_master.get_map(new_depth) {
+                Some(stored) => (stored, false),
+                None => (build_random_map(new_depth, worldmap_resource.seed), true),
+            };
+            // Arrive on the stairs at the other end of the flight that was taken.
+            let arrival = if going_down {
+                new_map.get_upstairs()
+            } else {
+                new_map.get_downstairs().unwrap_or_else(|| new_map.get_upstairs())
+            };
+            *worldmap_resource = new_map;
+            (worldmap_resource.clone(), arrival, first_visit)
+        };
+
+        if first_visit {
+            // Spawn bad guys
+            spawn_level(&mut self.ecs, &worldmap);
+        } else {
+            self.thaw_level_entities();
         }
-        // Spawn bad guys
-        spawn(&mut self.ecs, &worldmap, SpawnSeed::Monster);
-        spawn(&mut self.ecs, &worldmap, SpawnSeed::Item);
 
         // Place the player and update resources
-        let (player_x, player_y) = worldmap.get_upstairs();
         let mut player_position = self.ecs.write_resource::<Point>();
         *player_position = Point::new(player_x, player_y);
         let mut position_components = self.ecs.write_storage::<Position>();
@@ -161,7 +285,14 @@ impl State {
         if let Some(vs) = vs {
             vs.dirty = true;
         }
+    }
+
+    pub fn goto_next_level(&mut self) {
+        let current_depth = self.ecs.fetch::<Map>().depth;
+        self.change_level(current_depth + 1);
+
         // Notify the player and give them some health
+        let player_entity = self.ecs.fetch::<Entity>();
         let mut gamelog = self.ecs.fetch_mut::<gamelog::GameLog>();
         gamelog
             .entries
@@ -173,6 +304,28 @@ impl State {
         }
     }
 
+    pub fn goto_previous_level(&mut self) {
+        let current_depth = self.ecs.fetch::<Map>().depth;
+        self.change_level(current_depth - 1);
+
+        let mut gamelog = self.ecs.fetch_mut::<gamelog::GameLog>();
+        gamelog
+            .entries
+            .push("You climb back up to the previous level.".to_string());
+    }
+
+    /// Has the player drop item, on their next turn.
+    fn drop_item(&mut self, item: Entity) {
+        let entities = self.ecs.entities();
+        let players = self.ecs.read_storage::<Player>();
+        let mut intent = self.ecs.write_storage::<WantsToDropItem>();
+        for (entity, _player) in (&entities, &players).join() {
+            intent
+                .insert(entity, WantsToDropItem { item })
+                .expect("Unable to insert intent");
+        }
+    }
+
     fn game_over_cleanup(&mut self) {
         // Delete everything
         let mut to_delete = Vec::new();
@@ -183,14 +336,19 @@ impl State {
             self.ecs.delete_entity(*del).expect("Deletion failed");
         }
     
-        // Build a new map and place the player
-        let worldmap = build_random_map(1);
+        // Build a new map for a new run and place the player
+        let seed = self.ecs.write_resource::<rltk::RandomNumberGenerator>().next_u64();
+        let worldmap = build_random_map(1, seed);
         self.ecs.insert(worldmap.clone());
+        self.ecs.insert(MasterDungeonMap::new());
+        self.ecs.insert(Identification::new(seed));
         spawn(&mut self.ecs, &worldmap, SpawnSeed::Player);
 
         // Spawn bad guys
-        spawn(&mut self.ecs, &worldmap, SpawnSeed::Monster);
-        spawn(&mut self.ecs, &worldmap, SpawnSeed::Item);
+        spawn_level(&mut self.ecs, &worldmap);
+
+        let mut gamelog = self.ecs.fetch_mut::<gamelog::GameLog>();
+        gamelog.entries.push(format!("Dungeon seed: {}", seed));
     }
     
 }
@@ -205,12 +363,12 @@ impl GameState for State {
         }
 
         match newrunstate {
-            RunState::PreRun => {
-                self.run_systems(ctx);
-                newrunstate = RunState::AwaitingInput;
+            RunState::PreRun | RunState::Ticking => {
+                self.render(ctx);
+                newrunstate = self.run_turn(newrunstate);
             }
             RunState::AwaitingInput => {
-                cull_dead_particles(&mut self.ecs, ctx);
+                cull_dead_particles(&mut self.ecs, ctx.frame_time_ms);
                 let mut pss = ParticleSpawnSystem {};
                 pss.run_now(&self.ecs);
                 let mut uis = UISystem { ctx };
@@ -218,13 +376,15 @@ impl GameState for State {
                 self.ecs.maintain();
                 newrunstate = player_input(self, ctx);
             }
-            RunState::PlayerTurn => {
-                self.run_systems(ctx);
-                newrunstate = RunState::MonsterTurn;
-            }
-            RunState::MonsterTurn => {
-                self.run_systems(ctx);
-                newrunstate = RunState::AwaitingInput;
+            RunState::LevelUp => {
+                let result = ui_system::level_up_menu(self, ctx);
+                if let (ui_system::ItemMenuResult::Selected, Some(choice)) = result {
+                    level_up(&mut self.ecs, choice);
+                    // Enough experience for two levels at once gets a second choice.
+                    if !can_level_up(&self.ecs) {
+                        newrunstate = RunState::AwaitingInput;
+                    }
+                }
             }
             RunState::ShowInventory => {
                 let result = ui_system::show_inventory(self, ctx);
@@ -255,7 +415,7 @@ impl GameState for State {
                                     )
                                     .expect("Unable to insert intent");
                             }
-                            newrunstate = RunState::PlayerTurn;
+                            newrunstate = RunState::Ticking;
                         }
                     }
                 }
@@ -266,16 +426,29 @@ impl GameState for State {
                     ui_system::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
                     ui_system::ItemMenuResult::NoResponse => {}
                     ui_system::ItemMenuResult::Selected => {
-                        let entities = self.ecs.entities();
-                        let players = self.ecs.read_storage::<Player>();
                         let item_entity = result.1.unwrap();
-                        let mut intent = self.ecs.write_storage::<WantsToDropItem>();
-                        for (entity, _player) in (&entities, &players).join() {
-                            intent
-                                .insert(entity, WantsToDropItem { item: item_entity })
-                                .expect("Unable to insert intent");
+                        let count = self.ecs.read_storage::<Stackable>().get(item_entity).map_or(1, |stack| stack.count);
+                        if count > 1 {
+                            newrunstate = RunState::ShowDropQuantity { item: item_entity, quantity: count };
+                        } else {
+                            self.drop_item(item_entity);
+                            newrunstate = RunState::Ticking;
+                        }
+                    }
+                }
+            }
+            RunState::ShowDropQuantity { item, quantity } => {
+                let result = ui_system::drop_quantity_menu(self, ctx, item, quantity);
+                match result.0 {
+                    ui_system::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    ui_system::ItemMenuResult::NoResponse => {
+                        newrunstate = RunState::ShowDropQuantity { item, quantity: result.1 };
+                    }
+                    ui_system::ItemMenuResult::Selected => {
+                        if let Some(part) = split_stack(&mut self.ecs, item, result.1) {
+                            self.drop_item(part);
                         }
-                        newrunstate = RunState::PlayerTurn;
+                        newrunstate = RunState::Ticking;
                     }
                 }
             }
@@ -301,7 +474,28 @@ impl GameState for State {
                                 )
                                 .expect("Unable to insert intent");
                         }
-                        newrunstate = RunState::PlayerTurn;
+                        newrunstate = RunState::Ticking;
+                    }
+                }
+            }
+            RunState::ShowFireTargeting { range } => {
+                let mut uis = UISystem { ctx };
+                uis.run_now(&self.ecs);
+                let result = ui_system::ranged_target(self, ctx, range);
+                match result.0 {
+                    ui_system::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    ui_system::ItemMenuResult::NoResponse => {}
+                    ui_system::ItemMenuResult::Selected => {
+                        if let Some(target) = result.1 {
+                            let player_entity = self.ecs.fetch::<Entity>();
+                            let mut intent = self.ecs.write_storage::<WantsToShoot>();
+                            intent
+                                .insert(*player_entity, WantsToShoot { target })
+                                .expect("Unable to insert intent");
+                            newrunstate = RunState::Ticking;
+                        } else {
+                            newrunstate = RunState::AwaitingInput;
+                        }
                     }
                 }
             }
@@ -334,7 +528,8 @@ impl GameState for State {
                             ctx.cls();
                             match snapshot {
                                 Some(t) => { 
-                                    map.draw_map(t, ctx); 
+                                    let camera = camera::Camera::new(&map, Point::new(map.upstairs.0, map.upstairs.1));
+                                    map.draw_map(t, &camera, ctx);
                                     newrunstate = RunState::MainMenu { menu_selection: MainMenuSelection::DebugMapCont };
                                 },
                                 None => {
@@ -352,7 +547,8 @@ impl GameState for State {
                             ctx.cls();
                             match snapshot {
                                 Some(t) => { 
-                                    map.draw_map(t, ctx); 
+                                    let camera = camera::Camera::new(&map, Point::new(map.upstairs.0, map.upstairs.1));
+                                    map.draw_map(t, &camera, ctx);
                                     newrunstate = RunState::MainMenu { menu_selection: MainMenuSelection::DebugMapCont };
                                 },
                                 None => {
@@ -375,9 +571,8 @@ impl GameState for State {
                     menu_selection: MainMenuSelection::LoadGame,
                 };
             }
-            RunState::NextLevel => {
-                self.goto_next_level();
-                newrunstate = RunState::PreRun;
+            RunState::NextLevel | RunState::PreviousLevel => {
+                newrunstate = self.run_turn(newrunstate);
             }
             RunState::ShowRemoveItem => {
                 let result = ui_system::remove_item_menu(self, ctx);
@@ -393,7 +588,25 @@ impl GameState for State {
                                 WantsToRemoveItem { item: item_entity },
                             )
                             .expect("Unable to insert intent");
-                        newrunstate = RunState::PlayerTurn;
+                        newrunstate = RunState::Ticking;
+                    }
+                }
+            }
+            RunState::ShowEquipment => {
+                let result = ui_system::show_equipment(self, ctx);
+                match result.0 {
+                    ui_system::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    ui_system::ItemMenuResult::NoResponse => {}
+                    ui_system::ItemMenuResult::Selected => {
+                        let item_entity = result.1.unwrap();
+                        let mut intent = self.ecs.write_storage::<WantsToRemoveItem>();
+                        intent
+                            .insert(
+                                *self.ecs.fetch::<Entity>(),
+                                WantsToRemoveItem { item: item_entity },
+                            )
+                            .expect("Unable to insert intent");
+                        newrunstate = RunState::Ticking;
                     }
                 }
             }
@@ -1,31 +1,54 @@
-use specs::prelude::{Join, System, WriteStorage, ReadStorage, WriteExpect, Entities};
+use specs::prelude::{Join, System, WriteStorage, ReadStorage, ReadExpect, WriteExpect, Entities, Entity};
 
 use crate::components::SufferDamage;
-use crate::spawner::components::{CombatStats, Position};
-use crate::map_builders::{common::xy_idx, map::Map};
+use crate::gamelog::GameLog;
+use crate::spawner::components::{CombatStats, Experience, ExperienceValue, Position};
+use crate::map_builders::{map::Map};
 
 pub struct DamageSystem {}
 
 impl<'a> System<'a> for DamageSystem {
+    #[allow(clippy::type_complexity)]
     type SystemData = ( WriteStorage<'a, CombatStats>,
                         WriteStorage<'a, SufferDamage>,
                         ReadStorage<'a, Position>,
                         WriteExpect<'a, Map>,
-                        Entities<'a> );
+                        Entities<'a>,
+                        ReadExpect<'a, Entity>, // The player
+                        ReadStorage<'a, ExperienceValue>,
+                        WriteStorage<'a, Experience>,
+                        WriteExpect<'a, GameLog> );
 
     fn run(&mut self, data : Self::SystemData) {
-        let (mut stats, mut damage, positions, mut map, entities) = data;
+        let (mut stats, mut damage, positions, mut map, entities, player_entity, xp_values, mut experience,
+            mut log) = data;
 
+        let mut xp_gained = 0;
         #[allow(unused_mut)]
         for (entity, mut stats, damage) in (&entities, &mut stats, &damage).join() {
-            stats.damage( damage.amount.iter().sum::<i32>() );
+            let was_dead = stats.is_dead;
+            stats.damage( damage.amount.iter().map(|(amount, _from_player)| amount).sum::<i32>() );
             let pos = positions.get(entity);
             if let Some(pos) = pos {
-                let idx = xy_idx(pos.get_x(), pos.get_y());
+                let idx = map.xy_idx(pos.get_x(), pos.get_y());
                 map.blood_stains.insert(idx);
             }
+
+            // The kill is the player's if any of the damage that finished it came from them.
+            if stats.is_dead && !was_dead && damage.amount.iter().any(|(_amount, from_player)| *from_player) {
+                if let Some(value) = xp_values.get(entity) {
+                    xp_gained += value.xp * map.depth;
+                }
+            }
+        }
+
+        if xp_gained > 0 {
+            if let Some(experience) = experience.get_mut(*player_entity) {
+                experience.xp += xp_gained;
+                log.entries.push(format!("You gain {} experience.", xp_gained));
+            }
         }
 
         damage.clear();
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,81 @@
+use specs::prelude::*;
+
+use crate::components::{MyTurn, SufferDamage};
+use crate::gamelog::GameLog;
+use crate::map_builders::{map::Map, map::TileType};
+use crate::spawner::components::{CombatStats, Name, Position};
+
+/// How much standing in lava hurts, every turn.
+const LAVA_DAMAGE: i32 = 8;
+
+/// The HazardSystem hurts whoever starts their turn standing on dangerous terrain. For now
+/// that is only lava.
+pub struct HazardSystem {}
+
+impl<'a> System<'a> for HazardSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, MyTurn>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, CombatStats>,
+        WriteStorage<'a, SufferDamage>,
+        ReadStorage<'a, Name>,
+        ReadExpect<'a, Map>,
+        ReadExpect<'a, Entity>, // The player
+        WriteExpect<'a, GameLog>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, turns, positions, combat_stats, mut inflict_damage, names, map, player_entity, mut log) =
+            data;
+
+        for (entity, _turn, pos, stats) in (&entities, &turns, &positions, &combat_stats).join() {
+            let idx = map.xy_idx(pos.get_x(), pos.get_y());
+            if stats.is_dead || map.tiles[idx] != TileType::Lava {
+                continue;
+            }
+            SufferDamage::new_damage(&mut inflict_damage, entity, LAVA_DAMAGE, false);
+            if entity == *player_entity {
+                log.entries.push(format!("The lava burns you, for {} hp!", LAVA_DAMAGE));
+            } else if map.visible_tiles[idx] {
+                if let Some(name) = names.get(entity) {
+                    log.entries.push(format!("{} is burned by the lava.", &name.name));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixture::test_world;
+
+    #[test]
+    fn lava_burns_only_those_standing_in_it() {
+        let mut ecs = test_world();
+        let mut map = Map::new(1, 80, 43);
+        let idx = map.xy_idx(5, 5);
+        map.tiles[idx] = TileType::Lava;
+        let idx = map.xy_idx(6, 5);
+        map.tiles[idx] = TileType::Floor;
+        ecs.insert(map);
+        let mut creature_at = |x: i32| {
+            ecs.create_entity()
+                .with(Position::new(x, 5))
+                .with(CombatStats { hp: 20, hp_max: 20, attack: 0, defense: 0, is_dead: false })
+                .with(MyTurn {})
+                .build()
+        };
+        let player = creature_at(5);
+        let bystander = creature_at(6);
+        ecs.insert(player);
+
+        HazardSystem {}.run_now(&ecs);
+        let damage = ecs.read_storage::<SufferDamage>();
+        assert_eq!(damage.get(player).unwrap().amount, vec![(LAVA_DAMAGE, false)]);
+        assert!(damage.get(bystander).is_none());
+        assert_eq!(ecs.fetch::<GameLog>().entries.last().unwrap(), "The lava burns you, for 8 hp!");
+    }
+}
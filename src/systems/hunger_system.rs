@@ -1,8 +1,7 @@
 use specs::prelude::*;
-use crate::components::SufferDamage;
 use crate::spawner::components::{HungerClock, HungerState};
 use crate::gamelog::GameLog;
-use crate::state_machine::RunState;
+use crate::components::{MyTurn, SufferDamage};
 
 pub struct HungerSystem {}
 
@@ -12,63 +11,46 @@ impl<'a> System<'a> for HungerSystem {
                         Entities<'a>,
                         WriteStorage<'a, HungerClock>,
                         ReadExpect<'a, Entity>, // The player
-                        ReadExpect<'a, RunState>,
+                        ReadStorage<'a, MyTurn>,
                         WriteStorage<'a, SufferDamage>,
                         WriteExpect<'a, GameLog>
                       );
 
     fn run(&mut self, data : Self::SystemData) {
-        let (entities, mut hunger_clock, player_entity, runstate, mut inflict_damage, mut log) = data;
+        let (entities, mut hunger_clock, player_entity, turns, mut inflict_damage, mut log) = data;
 
-        for (entity, mut clock) in (&entities, &mut hunger_clock).join() {
-            let mut proceed = false;
-
-            match *runstate {
-                RunState::PlayerTurn => {
-                    if entity == *player_entity {
-                        proceed = true;
-                    }
-                }
-                RunState::MonsterTurn => {
-                    if entity != *player_entity {
-                        proceed = true;
-                    }
-                }
-                _ => proceed = false
-            }
-
-            if proceed {
-                clock.duration -= 1;
-                if clock.duration < 1 {
-                    match clock.state {
-                        HungerState::WellFed => {
-                            clock.state = HungerState::Normal;
-                            clock.duration = 200;
-                            if entity == *player_entity {
-                                log.entries.push("You are no longer well fed.".to_string());
-                            }
+        // Hunger only counts down on the turns of the Entity that is getting hungry.
+        for (entity, clock, _turn) in (&entities, &mut hunger_clock, &turns).join() {
+            clock.duration -= 1;
+            if clock.duration < 1 {
+                match clock.state {
+                    HungerState::WellFed => {
+                        clock.state = HungerState::Normal;
+                        clock.duration = 200;
+                        if entity == *player_entity {
+                            log.entries.push("You are no longer well fed.".to_string());
                         }
-                        HungerState::Normal => {
-                            clock.state = HungerState::Hungry;
-                            clock.duration = 200;
-                            if entity == *player_entity {
-                                log.entries.push("You are hungry.".to_string());
-                            }
+                    }
+                    HungerState::Normal => {
+                        clock.state = HungerState::Hungry;
+                        clock.duration = 200;
+                        if entity == *player_entity {
+                            log.entries.push("You are hungry.".to_string());
                         }
-                        HungerState::Hungry => {
-                            clock.state = HungerState::Starving;
-                            clock.duration = 200;
-                            if entity == *player_entity {
-                                log.entries.push("You are starving!".to_string());
-                            }
+                    }
+                    HungerState::Hungry => {
+                        clock.state = HungerState::Starving;
+                        clock.duration = 200;
+                        if entity == *player_entity {
+                            log.entries.push("You are starving!".to_string());
                         }
-                        HungerState::Starving => {
-                            // Inflict damage from hunger
-                            if entity == *player_entity {
-                                log.entries.push("Your hunger pangs are getting painful! You suffer 1 hp damage.".to_string());
-                            }
-                            SufferDamage::new_damage(&mut inflict_damage, entity, 1);  
+                    }
+                    HungerState::Starving => {
+                        // Inflict damage from hunger
+                        if entity == *player_entity {
+                            log.entries.push("Your hunger pangs are getting painful! You suffer 1 hp damage.".to_string());
                         }
+                        SufferDamage::new_damage(&mut inflict_damage, entity, 1, false);  
                     }
                 }
             }
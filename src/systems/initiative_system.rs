@@ -1,41 +1,91 @@
-use rltk::RandomNumberGenerator;
 use specs::*;
 
-use crate::components::InCombat;
-use crate::spawner::components::{Actor, Position};
-use crate::map_builders::{ common::xy_idx, map::Map };
+use crate::components::{Encumbrance, MyTurn};
+use crate::spawner::components::{Actor, Position, StatusEffects, StatusKind};
+use crate::state_machine::RunState;
 
-pub struct InitiativeSystem {
-    dirty: bool,
-}
-
-impl InitiativeSystem {
-    pub fn new() -> InitiativeSystem {
-        InitiativeSystem { dirty: true}
-    }
-}
+/// The InitiativeSystem decides who acts next while the game is Ticking. It hands every Actor on
+/// the level its speed in energy, round after round, until at least one of them can pay
+/// Actor::TURN_COST; those Actors get MyTurn. When the player is among them the game goes back
+/// to AwaitingInput. Haste doubles an Actor's speed and Slow halves it, and a heavy load
+/// slows it further.
+pub struct InitiativeSystem {}
 
 impl<'a> System<'a> for InitiativeSystem {
     #[allow(clippy::type_complexity)]
-    type SystemData = ( ReadExpect<'a, Map>,
-                        ReadStorage<'a, Actor>,
+    type SystemData = ( WriteStorage<'a, Actor>,
                         Entities<'a>,
                         ReadStorage<'a, Position>,
-                        WriteExpect<'a, RandomNumberGenerator>,
-                        WriteStorage<'a, InCombat>,
+                        WriteStorage<'a, MyTurn>,
+                        ReadExpect<'a, Entity>, // The player
+                        WriteExpect<'a, RunState>,
+                        ReadStorage<'a, StatusEffects>,
+                        ReadStorage<'a, Encumbrance>,
                     );
-    
+
     fn run(&mut self, data : Self::SystemData) {
-        let (map, actors, entities, positions, mut rng, mut in_combat) = data;
+        let (mut actors, entities, positions, mut turns, player_entity, mut runstate, status_effects, encumbrance) = data;
 
-        if self.dirty {
-            for (entity, _actor, pos) in (&entities, &actors, &positions).join() {
-                let idx = xy_idx(pos.get_x(), pos.get_y());
+        if *runstate != RunState::Ticking { return; }
 
-                if map.visible_tiles[idx] == true {
-                    in_combat.insert(entity, InCombat { initiative: rng.roll_dice(1, 20) as i32}).expect("Unable to insert InCombat");
+        // Entities left on other levels have no Position, and so wait for the player to return.
+        let speed = |entity: Entity, actor: &Actor| {
+            let speed = status_effects.get(entity).map_or(actor.speed, |s| s.adjust_speed(actor.speed));
+            encumbrance.get(entity).map_or(speed, |e| e.tier.adjust_speed(speed)).max(0)
+        };
+        let total_speed: i32 = (&entities, &actors, &positions).join().map(|(entity, actor, _pos)| speed(entity, actor)).sum();
+        if total_speed == 0 {
+            *runstate = RunState::AwaitingInput;
+            return;
+        }
+
+        let mut someone_acts = false;
+        while !someone_acts {
+            for (entity, actor, _pos) in (&entities, &mut actors, &positions).join() {
+                actor.energy += speed(entity, actor);
+                if actor.energy >= Actor::TURN_COST {
+                    actor.energy -= Actor::TURN_COST;
+                    turns.insert(entity, MyTurn {}).expect("Unable to insert turn");
+                    someone_acts = true;
+
+                    // A paralysed player's turn passes without asking for input.
+                    let paralysed = status_effects.get(entity).is_some_and(|s| s.has(StatusKind::Paralysis));
+                    if entity == *player_entity && !paralysed {
+                        *runstate = RunState::AwaitingInput;
+                    }
                 }
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::systems::turn_system::TurnSystem;
+    use crate::test_fixture::test_world;
+
+    #[test]
+    fn fast_actors_act_more_often() {
+        let mut ecs = test_world();
+        let player = ecs.create_entity().with(Actor::new(Actor::NORMAL_SPEED)).with(Position::new(1, 1)).build();
+        let rat = ecs.create_entity().with(Actor::new(Actor::NORMAL_SPEED * 2)).with(Position::new(2, 2)).build();
+        let frozen = ecs.create_entity().with(Actor::new(Actor::NORMAL_SPEED * 2)).build();
+        ecs.insert(player);
+
+        let mut rat_turns = 0;
+        for _player_turn in 0..5 {
+            ecs.insert(RunState::Ticking);
+            while *ecs.fetch::<RunState>() == RunState::Ticking {
+                InitiativeSystem {}.run_now(&ecs);
+                if ecs.read_storage::<MyTurn>().get(rat).is_some() {
+                    rat_turns += 1;
+                }
+                assert!(ecs.read_storage::<MyTurn>().get(frozen).is_none());
+                TurnSystem {}.run_now(&ecs);
+                ecs.maintain();
+            }
+        }
+        assert_eq!(rat_turns, 10);
+    }
+}
@@ -0,0 +1,103 @@
+use specs::prelude::*;
+use std::collections::HashMap;
+
+use crate::components::{Encumbrance, EncumbranceTier, InBackpack};
+use crate::gamelog::GameLog;
+use crate::spawner::components::{Equipped, GeneralStats};
+use crate::spawner::item::{Item, Stackable};
+
+/// The EncumbranceSystem weighs everything each creature with GeneralStats carries or wears,
+/// and gives it the Encumbrance that its strength makes of the load. The player is told
+/// whenever that changes.
+pub struct EncumbranceSystem {}
+
+impl<'a> System<'a> for EncumbranceSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Entity>, // The player
+        WriteExpect<'a, GameLog>,
+        ReadStorage<'a, GeneralStats>,
+        WriteStorage<'a, Encumbrance>,
+        ReadStorage<'a, Item>,
+        ReadStorage<'a, InBackpack>,
+        ReadStorage<'a, Equipped>,
+        ReadStorage<'a, Stackable>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, player_entity, mut gamelog, attributes, mut encumbrance, items, backpack, equipped, stackables) =
+            data;
+
+        let mut carried: HashMap<Entity, f32> = HashMap::new();
+        for (item_entity, item) in (&entities, &items).join() {
+            let owner = backpack.get(item_entity).map(|b| b.owner).or(equipped.get(item_entity).map(|e| e.owner));
+            if let Some(owner) = owner {
+                *carried.entry(owner).or_insert(0.0) +=
+                    item.total_weight(stackables.get(item_entity));
+            }
+        }
+
+        for (entity, attributes) in (&entities, &attributes).join() {
+            let carried = carried.get(&entity).copied().unwrap_or(0.0);
+            let tier = EncumbranceTier::for_load(carried, attributes.carry_capacity());
+            let was = encumbrance.get(entity).map_or(EncumbranceTier::Unburdened, |e| e.tier);
+            if entity == *player_entity && tier != was {
+                gamelog.entries.push(match tier {
+                    EncumbranceTier::Unburdened => "You are no longer burdened.".to_string(),
+                    _ => format!("You are {} by everything you carry.", tier.describe()),
+                });
+            }
+            encumbrance
+                .insert(entity, Encumbrance { carried, tier })
+                .expect("Unable to insert encumbrance");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::WantsToPickupItem;
+    use crate::spawner::components::Position;
+    use crate::spawner::raws::spawn_named_entity;
+    use crate::systems::inventory_systems::ItemCollectionSystem;
+    use crate::test_fixture::test_world;
+
+    fn pick_up(ecs: &mut World, name: &str) -> Entity {
+        let player = *ecs.fetch::<Entity>();
+        let item = spawn_named_entity(ecs, name, 10, 10).unwrap();
+        ecs.write_storage::<WantsToPickupItem>()
+            .insert(player, WantsToPickupItem { collected_by: player, item })
+            .unwrap();
+        ItemCollectionSystem {}.run_now(ecs);
+        EncumbranceSystem {}.run_now(ecs);
+        ecs.maintain();
+        item
+    }
+
+    #[test]
+    fn heavy_loads_burden_and_then_refuse() {
+        let mut ecs = test_world();
+        let stats = GeneralStats { strength: 10, dexterity: 10, constitution: 10, intelligence: 10 };
+        let player = ecs.create_entity().with(stats).with(Position::new(10, 10)).build();
+        ecs.insert(player);
+        let tier = |ecs: &World| ecs.read_storage::<Encumbrance>().get(player).unwrap().tier;
+
+        // Average strength carries 60 pounds with ease, and up to 120 at all.
+        pick_up(&mut ecs, "Chain Mail");
+        pick_up(&mut ecs, "Tower Shield");
+        assert_eq!(tier(&ecs), EncumbranceTier::Unburdened);
+        pick_up(&mut ecs, "Leather Armour");
+        assert_eq!(tier(&ecs), EncumbranceTier::Burdened);
+        assert!(ecs.fetch::<GameLog>().entries.contains(&"You are burdened by everything you carry.".to_string()));
+        pick_up(&mut ecs, "Chain Mail");
+        assert_eq!(tier(&ecs), EncumbranceTier::Strained);
+        assert_eq!(EncumbranceTier::Strained.adjust_speed(100), 50);
+
+        let refused = pick_up(&mut ecs, "Tower Shield");
+        assert!(ecs.read_storage::<InBackpack>().get(refused).is_none());
+        assert!(ecs.read_storage::<Position>().get(refused).is_some());
+        assert!(ecs.fetch::<GameLog>().entries.last().unwrap().contains("too heavy"));
+    }
+}
@@ -1,8 +1,9 @@
 use specs::prelude::*;
 
-use crate::components::{InBackpack, WantsToPickupItem};
+use crate::components::{Encumbrance, InBackpack, WantsToPickupItem};
 use crate::gamelog::GameLog;
-use crate::spawner::components::{Name, Position};
+use crate::spawner::components::{GeneralStats, Name, Position, RawName};
+use crate::spawner::item::{Item, Stackable};
 
 pub struct ItemCollectionSystem {}
 
@@ -14,14 +15,69 @@ impl<'a> System<'a> for ItemCollectionSystem {
         WriteStorage<'a, WantsToPickupItem>,
         WriteStorage<'a, Position>,
         ReadStorage<'a, Name>,
+        ReadStorage<'a, RawName>,
         WriteStorage<'a, InBackpack>,
+        ReadStorage<'a, GeneralStats>,
+        WriteStorage<'a, Stackable>,
+        ReadStorage<'a, Item>,
+        ReadStorage<'a, Encumbrance>,
+        Entities<'a>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (player_entity, mut gamelog, mut wants_pickup, mut positions, names, mut backpack) =
-            data;
+        let (
+            player_entity,
+            mut gamelog,
+            mut wants_pickup,
+            mut positions,
+            names,
+            raw_names,
+            mut backpack,
+            attributes,
+            mut stackables,
+            items,
+            encumbrance,
+            entities,
+        ) = data;
 
         for pickup in wants_pickup.join() {
+            // Strength limits how much can be carried; creatures without attributes have no limit.
+            if let Some(attributes) = attributes.get(pickup.collected_by) {
+                let carried = encumbrance.get(pickup.collected_by).map_or(0.0, |e| e.carried);
+                let weight = items.get(pickup.item).map_or(0.0, |item| {
+                    item.total_weight(stackables.get(pickup.item))
+                });
+                if carried + weight > attributes.max_load() {
+                    if pickup.collected_by == *player_entity {
+                        gamelog.entries.push(format!(
+                            "The {} is too heavy: it weighs {:.1} lbs, and you can carry only {:.1} lbs more.",
+                            names.get(pickup.item).unwrap().name,
+                            weight,
+                            f32::max(0.0, attributes.max_load() - carried)
+                        ));
+                    }
+                    continue;
+                }
+            }
+
+            // Anything that stacks, such as potions and arrows, joins a stack spawned from the same
+            // raw that is already carried.
+            if let (Some(picked_up), Some(raw_name)) = (stackables.get(pickup.item).cloned(), raw_names.get(pickup.item)) {
+                let name = &names.get(pickup.item).unwrap().name;
+                let stack = (&entities, &mut stackables, &backpack, &raw_names).join().find(|(_e, _stack, in_pack, stack_raw)| {
+                    in_pack.owner == pickup.collected_by && stack_raw.name == raw_name.name
+                });
+                if let Some((_stack_entity, stack, _in_pack, _raw_name)) = stack {
+                    stack.count += picked_up.count;
+                    if pickup.collected_by == *player_entity {
+                        gamelog.entries.push(format!("You pick up the {}, and now have {}.", name, stack.count));
+                    }
+                    positions.remove(pickup.item);
+                    entities.delete(pickup.item).expect("Unable to delete");
+                    continue;
+                }
+            }
+
             positions.remove(pickup.item);
             backpack
                 .insert(
@@ -3,6 +3,7 @@ use specs::prelude::*;
 use crate::components::{InBackpack, WantsToDropItem};
 use crate::gamelog::GameLog;
 use crate::spawner::components::{Name, Position};
+use crate::spawner::item::Stackable;
 use crate::spawner::player::Player;
 
 pub struct ItemDropSystem {}
@@ -17,10 +18,11 @@ impl<'a> System<'a> for ItemDropSystem {
         ReadStorage<'a, Name>,
         WriteStorage<'a, Position>,
         WriteStorage<'a, InBackpack>,
+        ReadStorage<'a, Stackable>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (player, mut gamelog, entities, mut wants_drop, names, mut positions, mut backpack) =
+        let (player, mut gamelog, entities, mut wants_drop, names, mut positions, mut backpack, stackables) =
             data;
 
         for (entity, to_drop) in (&entities, &wants_drop).join() {
@@ -41,10 +43,13 @@ impl<'a> System<'a> for ItemDropSystem {
             // If this is the player, tell us they dropped the item
             let _p: Option<&Player> = player.get(entity);
             if let Some(_p) = _p {
-                gamelog.entries.push(format!(
-                    "You drop the {}.",
-                    names.get(to_drop.item).unwrap().name
-                ));
+                let name = &names.get(to_drop.item).unwrap().name;
+                match stackables.get(to_drop.item) {
+                    Some(stack) if stack.count > 1 => {
+                        gamelog.entries.push(format!("You drop {}.", Stackable::label(name, Some(stack))));
+                    }
+                    _ => gamelog.entries.push(format!("You drop the {}.", name)),
+                }
             }
         }
 
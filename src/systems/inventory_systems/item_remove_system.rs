@@ -1,7 +1,9 @@
 use specs::prelude::*;
 
 use crate::components::{InBackpack, WantsToRemoveItem};
-use crate::spawner::components::Equipped;
+use crate::gamelog::GameLog;
+use crate::spawner::components::{Equipped, Name};
+use crate::spawner::item::Cursed;
 
 pub struct ItemRemoveSystem {}
 
@@ -12,12 +14,26 @@ impl<'a> System<'a> for ItemRemoveSystem {
         WriteStorage<'a, WantsToRemoveItem>,
         WriteStorage<'a, Equipped>,
         WriteStorage<'a, InBackpack>,
+        ReadStorage<'a, Cursed>,
+        ReadStorage<'a, Name>,
+        ReadExpect<'a, Entity>, // The player
+        WriteExpect<'a, GameLog>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (entities, mut wants_remove, mut equipped, mut backpack) = data;
+        let (entities, mut wants_remove, mut equipped, mut backpack, cursed, names, player_entity, mut gamelog) = data;
 
         for (entity, to_remove) in (&entities, &wants_remove).join() {
+            // A curse holds the item in place until it is lifted.
+            if cursed.contains(to_remove.item) {
+                if entity == *player_entity {
+                    gamelog.entries.push(format!(
+                        "You cannot take off the {}: it is cursed!",
+                        names.get(to_remove.item).unwrap().name
+                    ));
+                }
+                continue;
+            }
             equipped.remove(to_remove.item);
             backpack
                 .insert(to_remove.item, InBackpack { owner: entity })
@@ -3,10 +3,14 @@ use specs::prelude::*;
 
 use crate::components::{InBackpack, SufferDamage, WantsToUseItem};
 use crate::gamelog::GameLog;
-use crate::map_builders::{common::xy_idx, map::Map};
+use crate::map_builders::{map::Map};
+use crate::spawner::item::{
+    Charges, Cursed, Durability, Enchantment, Identification, Identifies, MagicItem, Recharges, RemovesCurse, Repairs, Stackable,
+};
 use crate::spawner::components::{
-    AreaOfEffect, CombatStats, Confusion, Consumable, Equippable, Equipped, HungerClock,
-    HungerState, InflictsDamage, MagicMapper, Name, Position, ProvidesFood, ProvidesHealing,
+    AreaOfEffect, CombatStats, Consumable, EquipmentSlot, Equippable, Equipped, GeneralStats, HungerClock,
+    HungerState, InflictsDamage, InflictsStatus, MagicMapper, Name, Position, ProvidesFood, ProvidesHealing, RawName,
+    StatusEffects,
 };
 use crate::systems::particle_system::{OnDeathAction, ParticleBuilder};
 
@@ -20,22 +24,27 @@ impl<'a> System<'a> for ItemUseSystem {
         ReadExpect<'a, Map>,
         Entities<'a>,
         WriteStorage<'a, WantsToUseItem>,
-        ReadStorage<'a, Name>,
+        WriteStorage<'a, Name>,
         ReadStorage<'a, Consumable>,
         ReadStorage<'a, ProvidesHealing>,
         ReadStorage<'a, InflictsDamage>,
         WriteStorage<'a, CombatStats>,
         WriteStorage<'a, SufferDamage>,
         ReadStorage<'a, AreaOfEffect>,
-        WriteStorage<'a, Confusion>,
+        ReadStorage<'a, InflictsStatus>,
+        WriteStorage<'a, StatusEffects>,
         ReadStorage<'a, Equippable>,
         WriteStorage<'a, Equipped>,
         WriteStorage<'a, InBackpack>,
         WriteExpect<'a, ParticleBuilder>,
         ReadStorage<'a, Position>,
-        WriteStorage<'a, HungerClock>,
-        ReadStorage<'a, ProvidesFood>,
+        (WriteStorage<'a, HungerClock>, ReadStorage<'a, ProvidesFood>),
         ReadStorage<'a, MagicMapper>,
+        ReadStorage<'a, GeneralStats>,
+        (WriteExpect<'a, Identification>, ReadStorage<'a, MagicItem>, ReadStorage<'a, Identifies>, ReadStorage<'a, RawName>),
+        WriteStorage<'a, Stackable>,
+        (WriteStorage<'a, Charges>, ReadStorage<'a, Recharges>, WriteStorage<'a, Durability>, ReadStorage<'a, Repairs>),
+        (WriteStorage<'a, Cursed>, WriteStorage<'a, Enchantment>, ReadStorage<'a, RemovesCurse>),
     );
 
     #[allow(clippy::cognitive_complexity)]
@@ -46,27 +55,50 @@ impl<'a> System<'a> for ItemUseSystem {
             map,
             entities,
             mut wants_use,
-            names,
+            mut names,
             consumables,
             healing,
             inflict_damage,
             mut combat_stats,
             mut suffer_damage,
             aoe,
-            mut confused,
+            inflicts_status,
+            mut status_effects,
             equippable,
             mut equipped,
             mut backpack,
             mut particle_builder,
             positions,
-            mut hunger_clocks,
-            provides_food,
+            (mut hunger_clocks, provides_food),
             magic_mapper,
+            attributes,
+            (mut identification, magic_items, identifies, raw_names),
+            mut stackables,
+            (mut charges, recharges, mut durability, repairs),
+            (mut cursed, mut enchantments, removes_curse),
         ) = data;
 
         for (entity, useitem, position) in (&entities, &wants_use, &positions).join() {
             let mut used_item = true;
 
+            // A wand or a staff with no charges left does nothing at all.
+            if charges.get(useitem.item).is_some_and(|c| c.charges < 1) {
+                if entity == *player_entity {
+                    gamelog.entries.push(format!("The {} is out of charges.", names.get(useitem.item).unwrap().name));
+                }
+                continue;
+            }
+
+            // Using a magic item is the surest way to find out what it is.
+            if entity == *player_entity && magic_items.contains(useitem.item) {
+                let real_name = raw_names.get(useitem.item).map_or(String::new(), |r| r.name.clone());
+                if !identification.knows(&real_name) {
+                    let shown_name = identification.display_name(&real_name);
+                    identification.identify(&real_name, &mut names, &raw_names, &enchantments);
+                    gamelog.entries.push(format!("The {} is a {}.", shown_name, real_name));
+                }
+            }
+
             // Targeting
             let mut targets: Vec<Entity> = Vec::new();
             match useitem.target {
@@ -78,7 +110,7 @@ impl<'a> System<'a> for ItemUseSystem {
                     match area_effect {
                         None => {
                             // Single target in tile
-                            let idx = xy_idx(target.x, target.y);
+                            let idx = map.xy_idx(target.x, target.y);
                             for mob in map.tile_content[idx].iter() {
                                 targets.push(*mob);
                             }
@@ -91,7 +123,7 @@ impl<'a> System<'a> for ItemUseSystem {
                                 p.x > 0 && p.x < map.width - 1 && p.y > 0 && p.y < map.height - 1
                             });
                             for tile_idx in blast_tiles.iter() {
-                                let idx = xy_idx(tile_idx.x, tile_idx.y);
+                                let idx = map.xy_idx(tile_idx.x, tile_idx.y);
                                 for mob in map.tile_content[idx].iter() {
                                     targets.push(*mob);
                                 }
@@ -118,20 +150,48 @@ impl<'a> System<'a> for ItemUseSystem {
                     let target_slot = can_equip.slot;
                     let target = targets[0];
 
-                    // Remove any items the target has in the item's slot
+                    // Make room in the item's slot, and free both hands for a two-handed weapon
+                    // or a shield.
+                    let worn = (&equipped).join()
+                        .filter(|e| e.owner == target && e.slot == target_slot)
+                        .count();
+                    // Cursed items stay where they are, so a slot full of them cannot be freed.
+                    let mut slot_to_free = (worn + 1).saturating_sub(target_slot.capacity());
                     let mut to_unequip: Vec<Entity> = Vec::new();
-                    for (item_entity, already_equipped, name) in
-                        (&entities, &equipped, &names).join()
-                    {
-                        if already_equipped.owner == target && already_equipped.slot == target_slot
-                        {
-                            to_unequip.push(item_entity);
-                            if target == *player_entity {
-                                gamelog.entries.push(format!("You unequip {}.", name.name));
+                    let mut stuck_in_slot: Option<Entity> = None;
+                    let mut stuck_in_hands: Option<Entity> = None;
+                    for (item_entity, already_equipped) in (&entities, &equipped).join() {
+                        if already_equipped.owner != target {
+                            continue;
+                        }
+                        let in_slot = already_equipped.slot == target_slot;
+                        let frees_hands = (can_equip.two_handed && already_equipped.slot == EquipmentSlot::Shield)
+                            || (target_slot == EquipmentSlot::Shield
+                                && equippable.get(item_entity).is_some_and(|e| e.two_handed));
+                        if cursed.contains(item_entity) {
+                            if frees_hands {
+                                stuck_in_hands = Some(item_entity);
+                            } else if in_slot {
+                                stuck_in_slot = Some(item_entity);
+                            }
+                        } else if frees_hands || (in_slot && slot_to_free > 0) {
+                            if in_slot && slot_to_free > 0 {
+                                slot_to_free -= 1;
                             }
+                            to_unequip.push(item_entity);
                         }
                     }
+                    let stuck = stuck_in_hands.or(stuck_in_slot.filter(|_item| slot_to_free > 0));
+                    if let Some(stuck) = stuck {
+                        if target == *player_entity {
+                            gamelog.entries.push(format!("You cannot take off the {}: it is cursed!", names.get(stuck).unwrap().name));
+                        }
+                        continue;
+                    }
                     for item in to_unequip.iter() {
+                        if target == *player_entity {
+                            gamelog.entries.push(format!("You unequip {}.", names.get(*item).unwrap().name));
+                        }
                         equipped.remove(*item);
                         backpack
                             .insert(*item, InBackpack { owner: target })
@@ -155,6 +215,26 @@ impl<'a> System<'a> for ItemUseSystem {
                             names.get(useitem.item).unwrap().name
                         ));
                     }
+
+                    // Putting it on shows what magic is in it, for better or worse.
+                    if let Some(enchantment) = enchantments.get_mut(useitem.item) {
+                        if !enchantment.known {
+                            enchantment.known = true;
+                            let real_name = raw_names.get(useitem.item).map_or("", |r| r.name.as_str());
+                            if let Some(name) = names.get_mut(useitem.item) {
+                                name.name = identification.item_name(real_name, Some(enchantment));
+                                if target == *player_entity {
+                                    gamelog.entries.push(format!("It is a {}.", name.name));
+                                }
+                            }
+                        }
+                    }
+                    if cursed.contains(useitem.item) && target == *player_entity {
+                        gamelog.entries.push(format!(
+                            "The {} is cursed! It will not come off.",
+                            names.get(useitem.item).unwrap().name
+                        ));
+                    }
                 }
             }
 
@@ -243,20 +323,102 @@ impl<'a> System<'a> for ItemUseSystem {
                 }
             }
 
+            // Does it identify the magic items its user carries?
+            if identifies.contains(useitem.item) {
+                used_item = true;
+                let mut unknown: Vec<String> = (&backpack, &magic_items, &raw_names)
+                    .join()
+                    .filter(|(pack, _magic, raw_name)| pack.owner == entity && !identification.knows(&raw_name.name))
+                    .map(|(_pack, _magic, raw_name)| raw_name.name.clone())
+                    .collect();
+                unknown.sort();
+                unknown.dedup();
+                if entity == *player_entity && unknown.is_empty() {
+                    gamelog.entries.push("You learn nothing new.".to_string());
+                }
+                for real_name in unknown.iter() {
+                    if entity == *player_entity {
+                        gamelog.entries.push(format!("You identify the {}: it is a {}.", identification.display_name(real_name), real_name));
+                    }
+                    identification.identify(real_name, &mut names, &raw_names, &enchantments);
+                }
+            }
+
+            // Does it recharge the wands and staves its user carries?
+            if recharges.contains(useitem.item) {
+                used_item = true;
+                let mut recharged = false;
+                for (item, pack, item_charges) in (&entities, &backpack, &mut charges).join() {
+                    if pack.owner == entity && item != useitem.item && item_charges.charges < item_charges.max_charges {
+                        item_charges.charges = item_charges.max_charges;
+                        recharged = true;
+                        if entity == *player_entity {
+                            gamelog.entries.push(format!("Your {} is fully charged.", names.get(item).unwrap().name));
+                        }
+                    }
+                }
+                if entity == *player_entity && !recharged {
+                    gamelog.entries.push("You have nothing in need of recharging.".to_string());
+                }
+            }
+
+            // Does it mend the weapons and armour its user carries or wears?
+            if repairs.contains(useitem.item) {
+                used_item = true;
+                let mut repaired = false;
+                for (item, item_durability) in (&entities, &mut durability).join() {
+                    let owner = backpack.get(item).map(|b| b.owner).or(equipped.get(item).map(|e| e.owner));
+                    if owner == Some(entity) && item_durability.durability < item_durability.max_durability {
+                        item_durability.durability = item_durability.max_durability;
+                        repaired = true;
+                        if entity == *player_entity {
+                            gamelog.entries.push(format!("You repair your {}.", names.get(item).unwrap().name));
+                        }
+                    }
+                }
+                if entity == *player_entity && !repaired {
+                    gamelog.entries.push("You have nothing in need of repair.".to_string());
+                }
+            }
+
+            // Does it lift the curses on what its user carries or wears?
+            if removes_curse.contains(useitem.item) {
+                used_item = true;
+                let uncursed: Vec<Entity> = (&entities, &cursed)
+                    .join()
+                    .filter(|(item, _cursed)| {
+                        backpack.get(*item).map(|b| b.owner).or(equipped.get(*item).map(|e| e.owner)) == Some(entity)
+                    })
+                    .map(|(item, _cursed)| item)
+                    .collect();
+                for item in uncursed.iter() {
+                    cursed.remove(*item);
+                    if entity == *player_entity {
+                        gamelog.entries.push(format!("Your {} glows with a soft light.", names.get(*item).unwrap().name));
+                    }
+                }
+                if entity == *player_entity && uncursed.is_empty() {
+                    gamelog.entries.push("You feel as if someone is watching over you.".to_string());
+                }
+            }
+
             // If it inflicts damage, apply it to the target cell
             let item_damages = inflict_damage.get(useitem.item);
             match item_damages {
                 None => {}
                 Some(damage) => {
                     used_item = false;
+                    // A clever user gets more out of the magic, a dull one less.
+                    let spell_power = attributes.get(entity).map_or(0, |a| a.spell_power());
+                    let amount = i32::max(1, damage.damage + spell_power);
                     for mob in targets.iter() {
-                        SufferDamage::new_damage(&mut suffer_damage, *mob, damage.damage);
+                        SufferDamage::new_damage(&mut suffer_damage, *mob, amount, entity == *player_entity);
                         if entity == *player_entity {
                             let mob_name = names.get(*mob).unwrap();
                             let item_name = names.get(useitem.item).unwrap();
                             gamelog.entries.push(format!(
                                 "You use {} on {}, inflicting {} hp.",
-                                item_name.name, mob_name.name, damage.damage
+                                item_name.name, mob_name.name, amount
                             ));
 
                             let pos = positions.get(*mob);
@@ -278,55 +440,66 @@ impl<'a> System<'a> for ItemUseSystem {
                 }
             }
 
-            // Can it pass along confusion? Note the use of scopes to escape from the borrow checker!
-            let mut add_confusion = Vec::new();
-            {
-                let causes_confusion = confused.get(useitem.item);
-                match causes_confusion {
-                    None => {}
-                    Some(confusion) => {
-                        used_item = false;
-                        for mob in targets.iter() {
-                            add_confusion.push((*mob, confusion.turns));
-                            if entity == *player_entity {
-                                let mob_name = names.get(*mob).unwrap();
-                                let item_name = names.get(useitem.item).unwrap();
-                                gamelog.entries.push(format!(
-                                    "You use {} on {}, confusing them.",
-                                    item_name.name, mob_name.name
-                                ));
+            // Does it put a status effect on its targets?
+            let item_status = inflicts_status.get(useitem.item);
+            match item_status {
+                None => {}
+                Some(inflicts) => {
+                    used_item = false;
+                    for mob in targets.iter() {
+                        if combat_stats.get(*mob).is_none() {
+                            continue;
+                        }
+                        StatusEffects::inflict(&mut status_effects, *mob, inflicts.effect);
+                        used_item = true;
 
-                                let pos = positions.get(*mob);
-                                if let Some(pos) = pos {
-                                    particle_builder.request(
-                                        pos.get_x(),
-                                        pos.get_y(),
-                                        rltk::RGB::named(rltk::MAGENTA),
-                                        rltk::RGB::named(rltk::BLACK),
-                                        rltk::to_cp437('?'),
-                                        200.0,
-                                        OnDeathAction::NoAction,
-                                    );
-                                }
-                            }
+                        if *mob == *player_entity {
+                            gamelog.entries.push(format!("You are {}!", inflicts.effect.kind.adjective()));
+                        } else if entity == *player_entity {
+                            let mob_name = names.get(*mob).unwrap();
+                            let item_name = names.get(useitem.item).unwrap();
+                            gamelog.entries.push(format!(
+                                "You use {} on {}, leaving them {}.",
+                                item_name.name, mob_name.name, inflicts.effect.kind.adjective()
+                            ));
+                        }
+
+                        let pos = positions.get(*mob);
+                        if let Some(pos) = pos {
+                            let (glyph, fg) = inflicts.effect.kind.icon();
+                            particle_builder.request(
+                                pos.get_x(),
+                                pos.get_y(),
+                                fg,
+                                rltk::RGB::named(rltk::BLACK),
+                                rltk::to_cp437(glyph),
+                                200.0,
+                                OnDeathAction::NoAction,
+                            );
                         }
                     }
                 }
             }
-            for mob in add_confusion.iter() {
-                confused
-                    .insert(mob.0, Confusion { turns: mob.1 })
-                    .expect("Unable to insert status");
+
+            // A charged item uses up a charge rather than itself.
+            if used_item {
+                if let Some(item_charges) = charges.get_mut(useitem.item) {
+                    item_charges.charges -= 1;
+                    if item_charges.charges < 1 && entity == *player_entity {
+                        gamelog.entries.push(format!("The {} is out of charges.", names.get(useitem.item).unwrap().name));
+                    }
+                }
             }
 
-            // If its a consumable, we delete it on use
+            // If its a consumable, we delete it on use; from a stack, only one is used up.
             if used_item {
                 let consumable = consumables.get(useitem.item);
                 match consumable {
                     None => {}
-                    Some(_) => {
-                        entities.delete(useitem.item).expect("Delete failed");
-                    }
+                    Some(_) => match stackables.get_mut(useitem.item) {
+                        Some(stack) if stack.count > 1 => stack.count -= 1,
+                        _ => entities.delete(useitem.item).expect("Delete failed"),
+                    },
                 }
             }
         }
@@ -334,3 +507,237 @@ impl<'a> System<'a> for ItemUseSystem {
         wants_use.clear();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::{WantsToPickupItem, WantsToRemoveItem};
+    use crate::spawner::components::MeleePowerBonus;
+    use crate::spawner::item::{enchant, split_stack};
+    use crate::spawner::player::Player;
+    use crate::systems::inventory_systems::item_collection_system::ItemCollectionSystem;
+    use crate::systems::inventory_systems::item_remove_system::ItemRemoveSystem;
+    use crate::spawner::raws::spawn_named_entity;
+    use crate::test_fixture::{self, test_world};
+
+    fn carry(ecs: &mut World, name: &str) -> Entity {
+        let player = *ecs.fetch::<Entity>();
+        test_fixture::carry(ecs, player, name)
+    }
+
+    fn pick_up(ecs: &mut World, name: &str) {
+        let player = *ecs.fetch::<Entity>();
+        let item = spawn_named_entity(ecs, name, 10, 10).unwrap();
+        ecs.write_storage::<WantsToPickupItem>()
+            .insert(player, WantsToPickupItem { collected_by: player, item })
+            .unwrap();
+        ItemCollectionSystem {}.run_now(ecs);
+        ecs.maintain();
+    }
+
+    fn use_item(ecs: &mut World, item: Entity) {
+        let player = *ecs.fetch::<Entity>();
+        ecs.write_storage::<WantsToUseItem>().insert(player, WantsToUseItem { item, target: None }).unwrap();
+        ItemUseSystem {}.run_now(ecs);
+        ecs.maintain();
+    }
+
+    fn equip(ecs: &mut World, name: &str) -> Entity {
+        let item = carry(ecs, name);
+        use_item(ecs, item);
+        item
+    }
+
+    fn player_world() -> World {
+        let mut ecs = test_world();
+        Player::new(&mut ecs, 10, 10);
+        ecs
+    }
+
+    fn name_of(ecs: &World, item: Entity) -> String {
+        ecs.read_storage::<Name>().get(item).unwrap().name.clone()
+    }
+
+    #[test]
+    fn equipment_fills_slots_and_hands() {
+        let mut ecs = player_world();
+
+        let shield = equip(&mut ecs, "Shield");
+        let cap = equip(&mut ecs, "Leather Cap");
+        let greataxe = equip(&mut ecs, "Greataxe");
+        let first_ring = equip(&mut ecs, "Ring of Protection");
+        let second_ring = equip(&mut ecs, "Ring of Might");
+        {
+            let equipped = ecs.read_storage::<Equipped>();
+            // The greataxe needs both hands, so the shield went back into the pack.
+            assert!(equipped.get(shield).is_none());
+            assert!(ecs.read_storage::<InBackpack>().get(shield).is_some());
+            for item in [cap, greataxe, first_ring, second_ring] {
+                assert!(equipped.get(item).is_some());
+            }
+        }
+
+        // A third ring replaces one of the two, and the shield puts the greataxe away.
+        equip(&mut ecs, "Ring of Protection");
+        equip(&mut ecs, "Shield");
+        let equipped = ecs.read_storage::<Equipped>();
+        assert_eq!((&equipped).join().filter(|e| e.slot == EquipmentSlot::Ring).count(), 2);
+        assert!(equipped.get(greataxe).is_none());
+        assert!(equipped.get(cap).is_some());
+    }
+
+    #[test]
+    fn magic_items_are_identified_by_use_or_by_scroll() {
+        let mut ecs = player_world();
+        let first_potion = carry(&mut ecs, "Potion of Haste");
+        let second_potion = carry(&mut ecs, "Potion of Haste");
+        let strength = carry(&mut ecs, "Potion of Giant Strength");
+        let mapping = carry(&mut ecs, "Scroll of Magic Mapping");
+        let haste_alias = name_of(&ecs, first_potion);
+        assert!(haste_alias.ends_with("Potion") && haste_alias != "Potion of Haste");
+        assert!(name_of(&ecs, mapping).starts_with("Scroll of ") && name_of(&ecs, mapping) != "Scroll of Magic Mapping");
+        assert_ne!(name_of(&ecs, strength), haste_alias);
+
+        // Drinking one tells you what the other one is, too.
+        use_item(&mut ecs, first_potion);
+        assert_eq!(name_of(&ecs, second_potion), "Potion of Haste");
+        assert_ne!(name_of(&ecs, strength), "Potion of Giant Strength");
+        assert!(ecs.fetch::<GameLog>().entries.contains(&format!("The {} is a Potion of Haste.", haste_alias)));
+
+        // A Scroll of Identify reveals everything else in the pack, and newly found items
+        // arrive under their real names.
+        let identify = carry(&mut ecs, "Scroll of Identify");
+        use_item(&mut ecs, identify);
+        assert_eq!(name_of(&ecs, strength), "Potion of Giant Strength");
+        assert_eq!(name_of(&ecs, mapping), "Scroll of Magic Mapping");
+        let found = carry(&mut ecs, "Scroll of Magic Mapping");
+        assert_eq!(name_of(&ecs, found), "Scroll of Magic Mapping");
+
+        // Each run has names of its own.
+        let aliases: Vec<String> = (1..=5).map(|seed| Identification::new(seed).display_name("Potion of Haste")).collect();
+        assert!(aliases.iter().any(|alias| *alias != aliases[0]));
+    }
+
+    #[test]
+    fn potions_stack_in_the_pack_and_split_when_dropped() {
+        let mut ecs = player_world();
+        let player = *ecs.fetch::<Entity>();
+        let stack = carry(&mut ecs, "Healing Potion");
+        for _i in 0..2 {
+            pick_up(&mut ecs, "Healing Potion");
+        }
+        assert_eq!(ecs.read_storage::<Stackable>().get(stack).unwrap().count, 3);
+        assert_eq!(ecs.read_storage::<InBackpack>().join().count(), 1);
+
+        // Drinking one leaves the rest.
+        use_item(&mut ecs, stack);
+        assert!(ecs.is_alive(stack));
+        assert_eq!(ecs.read_storage::<Stackable>().get(stack).unwrap().count, 2);
+
+        // Splitting one off leaves two stacks of the same potion in the pack.
+        let part = split_stack(&mut ecs, stack, 1).unwrap();
+        assert_ne!(part, stack);
+        assert_eq!(name_of(&ecs, part), name_of(&ecs, stack));
+        assert_eq!(ecs.read_storage::<Stackable>().get(part).unwrap().count, 1);
+        assert_eq!(ecs.read_storage::<Stackable>().get(stack).unwrap().count, 1);
+        assert_eq!(ecs.read_storage::<InBackpack>().get(part).unwrap().owner, player);
+        assert!(ecs.read_storage::<Position>().get(part).is_none());
+        assert_eq!(split_stack(&mut ecs, stack, 1), Some(stack));
+    }
+
+    #[test]
+    fn stacks_are_kept_by_kind_whatever_they_are_called() {
+        let mut ecs = player_world();
+        let stack = carry(&mut ecs, "Potion of Haste");
+        pick_up(&mut ecs, "Potion of Haste");
+        let alias = name_of(&ecs, stack);
+        let part = split_stack(&mut ecs, stack, 1).unwrap();
+        assert_eq!(name_of(&ecs, part), alias);
+        assert_eq!(ecs.read_storage::<RawName>().get(part).unwrap().name, "Potion of Haste");
+
+        // Once it is known, what is carried and what is found later go by the real name and
+        // share a stack.
+        use_item(&mut ecs, part);
+        assert_eq!(name_of(&ecs, stack), "Potion of Haste");
+        pick_up(&mut ecs, "Potion of Haste");
+        assert_eq!(ecs.read_storage::<Stackable>().get(stack).unwrap().count, 2);
+        assert_eq!(ecs.read_storage::<InBackpack>().join().count(), 1);
+    }
+
+    #[test]
+    fn wands_run_out_of_charges_and_kits_mend_armour() {
+        let mut ecs = player_world();
+        let wand = carry(&mut ecs, "Wand of Confusion");
+        for _i in 0..4 {
+            use_item(&mut ecs, wand);
+        }
+        assert!(ecs.is_alive(wand));
+        assert_eq!(ecs.read_storage::<Charges>().get(wand).unwrap().charges, 0);
+        assert!(ecs.fetch::<GameLog>().entries.contains(&"The Wand of Confusion is out of charges.".to_string()));
+
+        // An empty wand does nothing, until it is recharged.
+        ecs.write_storage::<StatusEffects>().remove(*ecs.fetch::<Entity>());
+        use_item(&mut ecs, wand);
+        assert!(ecs.read_storage::<StatusEffects>().get(*ecs.fetch::<Entity>()).is_none());
+        let recharging = carry(&mut ecs, "Scroll of Recharging");
+        use_item(&mut ecs, recharging);
+        assert_eq!(ecs.read_storage::<Charges>().get(wand).unwrap().charges, 4);
+
+        // A Repair Kit mends what is worn as well as what is carried.
+        let mail = equip(&mut ecs, "Chain Mail");
+        let shield = carry(&mut ecs, "Shield");
+        ecs.write_storage::<Durability>().get_mut(mail).unwrap().durability = 0;
+        ecs.write_storage::<Durability>().get_mut(shield).unwrap().durability = 3;
+        let kit = carry(&mut ecs, "Repair Kit");
+        use_item(&mut ecs, kit);
+        assert_eq!(ecs.read_storage::<Durability>().get(mail).unwrap().durability, 40);
+        assert_eq!(ecs.read_storage::<Durability>().get(shield).unwrap().durability, 30);
+        assert!(!ecs.is_alive(kit));
+    }
+
+    #[test]
+    fn cursed_equipment_sticks_until_the_curse_is_lifted() {
+        let mut ecs = player_world();
+        let player = *ecs.fetch::<Entity>();
+
+        // Deep down most weapons are enchanted, some of them for the worse.
+        let levels: Vec<i32> = (0..40)
+            .filter_map(|_i| {
+                let sword = spawn_named_entity(&mut ecs, "Longsword", 0, 0).unwrap();
+                enchant(&mut ecs, sword, 9);
+                let level = ecs.read_storage::<Enchantment>().get(sword).map(|e| e.level)?;
+                assert_eq!(ecs.read_storage::<MeleePowerBonus>().get(sword).unwrap().power, 1 + level);
+                assert_eq!(ecs.read_storage::<Cursed>().contains(sword), level < 0);
+                Some(level)
+            })
+            .collect();
+        assert!(levels.iter().all(|level| *level != 0 && (-3..=3).contains(level)));
+        assert!(levels.iter().any(|level| *level > 0) && levels.iter().any(|level| *level < 0));
+
+        let sword = carry(&mut ecs, "Longsword");
+        ecs.write_storage::<Enchantment>().insert(sword, Enchantment { level: -2, known: false }).unwrap();
+        ecs.write_storage::<Cursed>().insert(sword, Cursed {}).unwrap();
+        assert_eq!(name_of(&ecs, sword), "Longsword");
+        use_item(&mut ecs, sword);
+        assert_eq!(name_of(&ecs, sword), "-2 Longsword");
+        assert!(ecs.fetch::<GameLog>().entries.contains(&"The -2 Longsword is cursed! It will not come off.".to_string()));
+
+        // Neither another weapon nor taking it off will shift it.
+        let dagger = equip(&mut ecs, "Dagger");
+        assert!(ecs.read_storage::<Equipped>().get(dagger).is_none());
+        let remove = |ecs: &mut World| {
+            ecs.write_storage::<WantsToRemoveItem>().insert(player, WantsToRemoveItem { item: sword }).unwrap();
+            ItemRemoveSystem {}.run_now(ecs);
+            ecs.maintain();
+        };
+        remove(&mut ecs);
+        assert!(ecs.read_storage::<Equipped>().get(sword).is_some());
+
+        let scroll = carry(&mut ecs, "Scroll of Remove Curse");
+        use_item(&mut ecs, scroll);
+        assert!(!ecs.read_storage::<Cursed>().contains(sword));
+        remove(&mut ecs);
+        assert!(ecs.read_storage::<Equipped>().get(sword).is_none());
+        assert!(ecs.read_storage::<InBackpack>().get(sword).is_some());
+    }
+}
@@ -1,8 +1,10 @@
+pub mod encumbrance_system;
 pub mod item_collection_system;
 pub mod item_drop_system;
 pub mod item_remove_system;
 pub mod item_use_system; 
 
+pub use encumbrance_system::EncumbranceSystem;
 pub use item_collection_system::ItemCollectionSystem;
 pub use item_drop_system::ItemDropSystem;
 pub use item_remove_system::ItemRemoveSystem;
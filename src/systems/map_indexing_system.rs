@@ -1,7 +1,7 @@
 use specs::prelude::*;
 
-use crate::spawner::components::{Position, BlocksTile};
-use crate::map_builders::{map::Map, common::xy_idx};
+use crate::spawner::components::{Position, BlocksTile, Door};
+use crate::map_builders::{map::Map};
 
 pub struct MapIndexingSystem {}
 
@@ -9,21 +9,26 @@ impl<'a> System<'a> for MapIndexingSystem {
     type SystemData = ( WriteExpect<'a, Map>,
                         ReadStorage<'a, Position>,
                         ReadStorage<'a, BlocksTile>,
+                        ReadStorage<'a, Door>,
                         Entities<'a>,);
 
     fn run(&mut self, data : Self::SystemData) {
-        let (mut map, positions, blockers, entities) = data;
+        let (mut map, positions, blockers, doors, entities) = data;
 
         map.populate_blocked();
         map.clear_content_index();
+        map.locked.clear();
         for (entity, position) in (&entities, &positions).join() {
-            let idx = xy_idx(position.get_x(), position.get_y());
+            let idx = map.xy_idx(position.get_x(), position.get_y());
 
-            // If they block, update the blocking list
+            // If they block, update the blocking list. Doors only block while they are closed.
             let _p : Option<&BlocksTile> = blockers.get(entity);
-            if let Some(_p) = _p {
+            if _p.is_some() || doors.get(entity).is_some_and(|door| !door.open) {
                 map.blocked[idx] = true;
             }
+            if doors.get(entity).is_some_and(|door| door.locked && !door.open) {
+                map.locked.insert(idx);
+            }
 
             // Push the entity to the appropriate index slot. It's a Copy
             // type, so we don't need to clone it (we want to avoid moving it out of the ECS!)
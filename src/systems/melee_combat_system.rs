@@ -1,14 +1,23 @@
+use rltk::RandomNumberGenerator;
 use specs::prelude::*;
+use specs::storage::MaskedStorage;
+use std::ops::Deref;
 
-use crate::components::{SufferDamage, WantsToMelee};
+use crate::components::{Encumbrance, SufferDamage, WantsToMelee};
 use crate::gamelog::GameLog;
 use crate::spawner::components::{
-    CombatStats, DefenseBonus, Equipped, HungerClock, HungerState, MeleePowerBonus, Name, Position,
+    CombatStats, DefenseBonus, DiceRoll, EquipmentSlot, Equipped, GeneralStats, HungerClock, HungerState, MeleePowerBonus, Name,
+    NaturalAttack, Position, StatusEffects, StatusKind,
 };
+use crate::spawner::item::Durability;
 use crate::spawner::player::Player;
 use crate::state_machine::RunState;
 use crate::systems::particle_system::{OnDeathAction, ParticleBuilder};
 
+/// Each blow that lands has one chance in this many to wear down the weapon that struck it, and
+/// the same chance to wear down a piece of the armour it struck.
+const WEAR_ONE_IN: i32 = 4;
+
 pub struct MeleeCombatSystem {}
 
 impl<'a> System<'a> for MeleeCombatSystem {
@@ -25,6 +34,13 @@ impl<'a> System<'a> for MeleeCombatSystem {
         WriteExpect<'a, ParticleBuilder>,
         ReadStorage<'a, Position>,
         ReadStorage<'a, HungerClock>,
+        ReadStorage<'a, GeneralStats>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        ReadExpect<'a, Entity>,
+        ReadStorage<'a, NaturalAttack>,
+        ReadStorage<'a, StatusEffects>,
+        ReadStorage<'a, Encumbrance>,
+        WriteStorage<'a, Durability>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -41,25 +57,43 @@ impl<'a> System<'a> for MeleeCombatSystem {
             mut particle_builder,
             positions,
             hunger_clock,
+            attributes,
+            mut rng,
+            player_entity,
+            natural_attacks,
+            status_effects,
+            encumbrance,
+            mut durability,
         ) = data;
 
         for (entity, wants_melee, name, stats) in
             (&entities, &wants_melee, &names, &combat_stats).join()
         {
             if !stats.is_dead {
-                let mut offensive_bonus = 0;
-                for (_item_entity, power_bonus, equipped_by) in
+                // The attacker's weapon, or failing that the way it fights bare handed. A broken
+                // weapon is no better than none.
+                let mut weapon = None;
+                let mut weapon_power = 0;
+                let mut weapon_damage = natural_attacks.get(entity).map_or(DiceRoll::new(1, 2, 0), |a| a.damage);
+                let mut weapon_name = natural_attacks.get(entity).map(|a| a.name.clone());
+                for (item_entity, power_bonus, equipped_by) in
                     (&entities, &melee_power_bonus, &equipped).join()
                 {
-                    if equipped_by.owner == entity {
-                        offensive_bonus += power_bonus.power;
+                    if equipped_by.owner == entity && Durability::works(durability.get(item_entity)) {
+                        weapon_power += power_bonus.power;
+                        if equipped_by.slot == EquipmentSlot::Melee {
+                            weapon = Some(item_entity);
+                            weapon_damage = power_bonus.damage;
+                            weapon_name = names.get(item_entity).map(|n| n.name.clone());
+                        }
                     }
                 }
 
+                let mut hit_bonus = stats.attack + weapon_power - combat_penalty(entity, &encumbrance);
                 let hc = hunger_clock.get(entity);
                 if let Some(hc) = hc {
                     if hc.state == HungerState::WellFed {
-                        offensive_bonus += 1;
+                        hit_bonus += 1;
                     }
                 }
 
@@ -68,14 +102,15 @@ impl<'a> System<'a> for MeleeCombatSystem {
                     Some(statistics) => {
                         if statistics.hp > 0 {
                             let target_name = names.get(wants_melee.target).unwrap();
-                            let mut defensive_bonus = 0;
-                            for (_item_entity, defense_bonus, equipped_by) in
-                                (&entities, &defense_bonus, &equipped).join()
-                            {
-                                if equipped_by.owner == wants_melee.target {
-                                    defensive_bonus += defense_bonus.defense;
-                                }
-                            }
+                            let armour_class = armour_class(
+                                wants_melee.target,
+                                statistics,
+                                &defense_bonus,
+                                &equipped,
+                                &attributes,
+                                &encumbrance,
+                                &durability,
+                            );
 
                             let pos = positions.get(wants_melee.target);
                             if let Some(pos) = pos {
@@ -90,26 +125,62 @@ impl<'a> System<'a> for MeleeCombatSystem {
                                 );
                             }
 
-                            let damage = i32::max(
-                                0,
-                                (stats.attack + offensive_bonus)
-                                    - (statistics.defense + defensive_bonus),
-                            );
-                            if damage == 0 {
+                            // Dexterity helps land a blow, strength makes it hurt.
+                            let modifier = |e: Entity, attribute: fn(&GeneralStats) -> i32| {
+                                attributes.get(e).map_or(0, |a| GeneralStats::modifier(attribute(a)))
+                            };
+                            hit_bonus += modifier(entity, |a| a.dexterity);
+
+                            // A natural 20 always hits and rolls the damage dice twice; a natural 1
+                            // always misses.
+                            let natural_roll = rng.roll_dice(1, 20);
+                            let critical = natural_roll == 20;
+                            let with = weapon_name.map_or(String::new(), |w| format!(" with {}", w));
+                            if natural_roll == 1 {
+                                log.entries.push(format!(
+                                    "{} fumbles an attack on {}.",
+                                    &name.name, &target_name.name
+                                ));
+                            } else if !critical && natural_roll + hit_bonus < armour_class {
                                 log.entries.push(format!(
-                                    "{} is unable to hurt {}",
+                                    "{} misses {}.",
                                     &name.name, &target_name.name
                                 ));
                             } else {
+                                let mut damage = weapon_damage.roll(&mut rng);
+                                if critical {
+                                    damage += weapon_damage.roll(&mut rng);
+                                }
+                                damage += status_effects.get(entity).map_or(0, |s| s.potency(StatusKind::Strength));
+                                damage = i32::max(1, damage + weapon_power + modifier(entity, |a| a.strength));
                                 log.entries.push(format!(
-                                    "{} hits {}, for {} hp.",
-                                    &name.name, &target_name.name, damage
+                                    "{}{} hits {}{}, for {} hp.",
+                                    if critical { "Critical! " } else { "" },
+                                    &name.name, &target_name.name, with, damage
                                 ));
                                 SufferDamage::new_damage(
                                     &mut inflict_damage,
                                     wants_melee.target,
                                     damage,
+                                    entity == *player_entity,
                                 );
+
+                                // Both the weapon and the armour it struck may take some wear.
+                                let worn: Vec<Entity> = (&entities, &defense_bonus, &equipped, &durability)
+                                    .join()
+                                    .filter(|(_e, _bonus, equipped_by, d)| equipped_by.owner == wants_melee.target && !d.is_broken())
+                                    .map(|(e, _bonus, _equipped_by, _d)| e)
+                                    .collect();
+                                let armour = match worn.len() {
+                                    0 => None,
+                                    n => Some(worn[rng.roll_dice(1, n as i32) as usize - 1]),
+                                };
+                                for (item, owner) in [(weapon, entity), (armour, wants_melee.target)] {
+                                    let Some(item) = item else { continue };
+                                    if wear(item, &mut durability, &mut rng) && owner == *player_entity {
+                                        log.entries.push(format!("Your {} breaks!", names.get(item).unwrap().name));
+                                    }
+                                }
                             }
                         }
                     }
@@ -122,6 +193,44 @@ impl<'a> System<'a> for MeleeCombatSystem {
     }
 }
 
+/// Wears an item down by a point, one time in WEAR_ONE_IN. Returns whether that broke it.
+fn wear(item: Entity, durability: &mut WriteStorage<Durability>, rng: &mut RandomNumberGenerator) -> bool {
+    match durability.get_mut(item) {
+        Some(item_durability) if !item_durability.is_broken() && rng.roll_dice(1, WEAR_ONE_IN) == 1 => {
+            item_durability.durability -= 1;
+            item_durability.is_broken()
+        }
+        _ => false,
+    }
+}
+
+/// The number an attack roll has to reach to hit target: 10, plus its defense and the
+/// DefenseBonus of everything it has equipped and not broken, plus its dexterity modifier,
+/// less whatever its load makes it harder to dodge.
+pub fn armour_class<E: Deref<Target = MaskedStorage<Equipped>>, D: Deref<Target = MaskedStorage<Durability>>>(
+    target: Entity,
+    stats: &CombatStats,
+    defense_bonus: &ReadStorage<DefenseBonus>,
+    equipped: &Storage<Equipped, E>,
+    attributes: &ReadStorage<GeneralStats>,
+    encumbrance: &ReadStorage<Encumbrance>,
+    durability: &Storage<Durability, D>,
+) -> i32 {
+    let mut armour_class = 10 + stats.defense;
+    for (defense_bonus, equipped_by, item_durability) in (defense_bonus, equipped, durability.maybe()).join() {
+        if equipped_by.owner == target && Durability::works(item_durability) {
+            armour_class += defense_bonus.defense;
+        }
+    }
+    armour_class + attributes.get(target).map_or(0, |a| GeneralStats::modifier(a.dexterity))
+        - combat_penalty(target, encumbrance)
+}
+
+/// How much an Entity's load hampers it in a fight.
+pub fn combat_penalty(entity: Entity, encumbrance: &ReadStorage<Encumbrance>) -> i32 {
+    encumbrance.get(entity).map_or(0, |e| e.tier.combat_penalty())
+}
+
 pub fn delete_the_dead(ecs: &mut World) {
     let mut dead: Vec<Entity> = Vec::new();
     // Using a scope to make the borrow checker happy
@@ -155,3 +264,51 @@ pub fn delete_the_dead(ecs: &mut World) {
         ecs.delete_entity(victim).expect("Unable to delete");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixture::{test_world, wield};
+
+    #[test]
+    fn blows_wear_down_weapons_and_armour_until_they_break() {
+        let mut ecs = test_world();
+        let fighter = |ecs: &mut World, name: &str, attack: i32| {
+            let stats = CombatStats { hp: 10000, hp_max: 10000, attack, defense: 0, is_dead: false };
+            ecs.create_entity().with(Name { name: name.to_string() }).with(stats).with(Position::new(1, 1)).build()
+        };
+        let player = fighter(&mut ecs, "Player", 100);
+        let orc = fighter(&mut ecs, "Orc", 0);
+        ecs.insert(player);
+        let sword = wield(&mut ecs, player, "Longsword", EquipmentSlot::Melee);
+        let mail = wield(&mut ecs, orc, "Chain Mail", EquipmentSlot::Torso);
+
+        let orc_armour_class = |ecs: &World| {
+            armour_class(
+                orc,
+                ecs.read_storage::<CombatStats>().get(orc).unwrap(),
+                &ecs.read_storage::<DefenseBonus>(),
+                &ecs.read_storage::<Equipped>(),
+                &ecs.read_storage::<GeneralStats>(),
+                &ecs.read_storage::<Encumbrance>(),
+                &ecs.read_storage::<Durability>(),
+            )
+        };
+        assert_eq!(orc_armour_class(&ecs), 13);
+
+        for _blow in 0..400 {
+            ecs.write_storage::<WantsToMelee>().insert(player, WantsToMelee { target: orc }).unwrap();
+            MeleeCombatSystem {}.run_now(&ecs);
+        }
+        let durability = ecs.read_storage::<Durability>();
+        assert!(durability.get(sword).unwrap().is_broken());
+        assert!(durability.get(mail).unwrap().is_broken());
+        drop(durability);
+        assert!(ecs.fetch::<GameLog>().entries.contains(&"Your Longsword breaks!".to_string()));
+
+        // Broken armour no longer counts, and a broken sword fights like a bare fist.
+        assert_eq!(orc_armour_class(&ecs), 10);
+        let last_hit = ecs.fetch::<GameLog>().entries.iter().rev().find(|e| e.contains(" hits ")).cloned().unwrap();
+        assert!(!last_hit.contains("with Longsword"), "{}", last_hit);
+    }
+}
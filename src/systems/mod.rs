@@ -10,6 +10,12 @@ pub mod damage_system;
 /// amounts of food value that different food could have.
 pub mod hunger_system;
 
+/// The hazard_system hurts anything that stands about on lava.
+pub mod hazard_system;
+
+/// initiative_system hands out turns to Actors according to their speed.
+pub mod initiative_system;
+
 /// inventory_systems contian all of the "systems" involved in controlling the Items in
 /// "inventory", that the Player or [future development] Monsters could pick up nd try 
 /// to use.
@@ -29,12 +35,24 @@ pub mod monster_ai_system;
 /// actions are taken.
 pub mod particle_system;
 
+/// The pathing_system builds the flow fields that monsters chase and flee the player with.
+pub mod pathing_system;
+
+/// The ranged_combat_system resolves shots from bows, crossbows and thrown weapons.
+pub mod ranged_combat_system;
+
 /// The saveload_system records game saves to disk and loads them back in.
 pub mod saveload_system;
 
+/// The status_effect_system counts down poison, haste, confusion and the other timed effects.
+pub mod status_effect_system;
+
 /// The traps use the trigger_system tell them when to go off.
 pub mod trigger_system;
 
+/// turn_system takes turns away again once everybody has had theirs.
+pub mod turn_system;
+
 /// All of the routines to display the User information systems are contained here.
 pub mod ui_system;
 
@@ -1,73 +1,348 @@
-use rltk::Point;
-use specs::prelude::{Entities, Join, ReadExpect, ReadStorage, System, WriteExpect, WriteStorage,};
+use rltk::{BaseMap, Point, RandomNumberGenerator};
+use specs::prelude::{Entities, Entity, Join, ReadExpect, ReadStorage, System, WriteExpect, WriteStorage,};
+use std::collections::HashMap;
 
-use crate::components::WantsToMelee;
-use crate::spawner::components::{Position, Viewshed, Confusion};
-use crate::spawner::{player::Player, monster::Monster};
-use crate::map_builders::{map::Map, common::xy_idx};
-use crate::state_machine::RunState;
+use crate::components::{MyTurn, WantsToMelee};
+use crate::spawner::components::{CombatStats, Door, Position, Renderable, Viewshed, StatusEffects};
+use crate::spawner::monster::{AiState, Behaviour, Faction, Monster, Reaction};
+use crate::spawner::raws::raws;
+use crate::map_builders::{map::Map, map::TileType};
+use crate::systems::pathing_system::PathingMaps;
 
+/// The MonsterAI makes the Monsters "think". Every Monster with a Behaviour keeps an AiState:
+/// it chases what its Faction attacks, runs from what its Faction fears or from its prey when
+/// badly hurt, searches where it last saw its prey and otherwise idles, wanders or goes home as
+/// its Behaviour says. Monsters open the doors in their way, unless they are locked.
 pub struct MonsterAI {}
 
+/// A creature a monster might react to, as it stands this turn.
+struct Creature {
+    entity: Entity,
+    at: Point,
+    faction: String,
+}
+
 impl<'a> System<'a> for MonsterAI {
     #[allow(clippy::type_complexity)]
     type SystemData = ( WriteExpect<'a, Map>,
-                        ReadExpect<'a, Point>,
-                        ReadStorage<'a, Player>,
-                        ReadExpect<'a, RunState>,
+                        ReadExpect<'a, Entity>, // The player
                         Entities<'a>,
                         WriteStorage<'a, Viewshed>,
                         ReadStorage<'a, Monster>,
                         WriteStorage<'a, Position>,
                         WriteStorage<'a, WantsToMelee>,
-                        WriteStorage<'a, Confusion>);
+                        ReadStorage<'a, StatusEffects>,
+                        ReadStorage<'a, MyTurn>,
+                        WriteStorage<'a, Behaviour>,
+                        ReadStorage<'a, CombatStats>,
+                        WriteExpect<'a, RandomNumberGenerator>,
+                        ReadExpect<'a, PathingMaps>,
+                        ReadStorage<'a, Faction>,
+                        WriteStorage<'a, Door>,
+                        WriteStorage<'a, Renderable>);
 
     fn run(&mut self, data : Self::SystemData) {
-        let (mut map, player_pos, players, runstate, entities, mut viewshed, monster, mut position, 
-            mut wants_to_melee, mut confused) = data;
+        let (mut map, player_entity, entities, mut viewshed, monster, mut position,
+            mut wants_to_melee, status_effects, turns, mut behaviours, combat_stats, mut rng, pathing,
+            factions, mut doors, mut renderables) = data;
 
-        if *runstate != RunState::MonsterTurn { return; }
+        // Doors a monster can open count as open while it makes up its mind; one that steps
+        // onto a closed door opens it instead.
+        let mut closed_doors: HashMap<usize, Entity> = (&entities, &doors, &position).join()
+            .filter(|(_e, door, _pos)| !door.open && !door.locked)
+            .map(|(e, _door, pos)| (map.xy_idx(pos.get_x(), pos.get_y()), e))
+            .collect();
+        for idx in closed_doors.keys() {
+            map.blocked[*idx] = false;
+        }
 
-        for (entity, mut viewshed, _monster, pos) in (&entities, &mut viewshed, &monster, &mut position).join() {
-            let mut can_act = true;
+        // Everything that belongs to a Faction and is still alive. Monsters that move are
+        // updated here as they go, so that those acting after them see where they went.
+        let mut creatures: Vec<Creature> = (&entities, &position, &factions, &combat_stats).join()
+            .filter(|(_e, _pos, _faction, stats)| !stats.is_dead)
+            .map(|(entity, pos, faction, _stats)| Creature {
+                entity,
+                at: Point::new(pos.get_x(), pos.get_y()),
+                faction: faction.name.clone(),
+            })
+            .collect();
+        let mut opened_doors = Vec::new();
 
-            let is_confused = confused.get_mut(entity);
-            if let Some(i_am_confused) = is_confused {
-                i_am_confused.turns -= 1;
-                if i_am_confused.turns < 1 {
-                    confused.remove(entity);
+        for (entity, viewshed, _monster, pos, _turn, behaviour) in
+            (&entities, &mut viewshed, &monster, &mut position, &turns, (&mut behaviours).maybe()).join()
+        {
+            if status_effects.get(entity).is_some_and(|s| s.prevents_action()) {
+                continue;
+            }
+            // Monsters without a Behaviour only ever chase what they can see.
+            let mut chaser = Behaviour::default();
+            let behaviour = behaviour.unwrap_or(&mut chaser);
+            let here = Point::new(pos.get_x(), pos.get_y());
+            if behaviour.home.is_none() {
+                behaviour.home = Some(here);
+            }
+            let gave_up = if behaviour.returns_home { AiState::ReturnHome } else { AiState::Idle };
+
+            // Find the nearest creature in sight to go for, and the nearest to run from. A
+            // monster without a Faction goes for the player and nobody else.
+            let mut prey: Option<(f32, Entity, Point)> = None;
+            let mut threat: Option<(f32, Entity, Point)> = None;
+            for other in creatures.iter().filter(|c| c.entity != entity && viewshed.visible_tiles.contains(&c.at)) {
+                let reaction = match factions.get(entity) {
+                    Some(faction) => raws().faction_reaction(&faction.name, &other.faction),
+                    None if other.entity == *player_entity => Reaction::Attack,
+                    None => Reaction::Ignore,
+                };
+                let distance = rltk::DistanceAlg::Pythagoras.distance2d(here, other.at);
+                let nearest = match reaction {
+                    Reaction::Attack => &mut prey,
+                    Reaction::Flee => &mut threat,
+                    Reaction::Ignore => continue,
+                };
+                if nearest.is_none_or(|(d, _e, _at)| distance < d) {
+                    *nearest = Some((distance, other.entity, other.at));
                 }
-                can_act = false;
             }
 
-            if can_act {
-                let cur_x = pos.get_x();
-                let cur_y = pos.get_y();
-                let distance = rltk::DistanceAlg::Pythagoras.distance2d(Point::new(cur_x, cur_y), *player_pos);
-                if distance < 1.5 {
-                    for (player_entity, _player) in (&entities, &players).join() {
-                        wants_to_melee.insert(entity, WantsToMelee{ target: player_entity }).expect("Unable to insert attack");
+            let hurt = combat_stats.get(entity).is_some_and(|s| s.hp * 100 < s.hp_max * behaviour.flee_below);
+            if let Some((_d, _e, at)) = prey {
+                behaviour.last_seen = Some(at);
+                if hurt && threat.is_none() {
+                    threat = prey;
+                }
+            }
+            if threat.is_some() {
+                behaviour.state = AiState::Flee;
+            } else if prey.is_some() {
+                behaviour.state = AiState::Chase;
+            } else {
+                match behaviour.state {
+                    AiState::Chase => behaviour.state = AiState::Search { turns: Behaviour::SEARCH_TURNS },
+                    AiState::Flee => behaviour.state = gave_up,
+                    _ => {}
+                }
+            }
+
+            // The PathingMaps lead to and away from the player; anyone else is walked to or
+            // away from directly.
+            match (behaviour.state, prey, threat) {
+                (AiState::Chase, Some((distance, target, at)), _) => {
+                    if distance < 1.5 {
+                        wants_to_melee.insert(entity, WantsToMelee{ target }).expect("Unable to insert attack");
+                    } else if target == *player_entity {
+                        follow(&mut map, pos, viewshed, &pathing.to_player);
+                    } else {
+                        step_towards(&mut map, pos, viewshed, at);
                     }
                 }
-                if viewshed.visible_tiles.contains(&*player_pos) {
-                    // Path to the player
-                    let path = rltk::a_star_search(
-                        xy_idx(cur_x, cur_y),
-                        xy_idx(player_pos.x, player_pos.y),
-                        &mut *map
-                    );
-                    if path.success && path.steps.len() > 1 {
-                        let mut idx = xy_idx(cur_x, cur_y);
-                        map.blocked[idx] = false;
-                        let new_x = path.steps[1] as i32 % map.width;
-                        let new_y = path.steps[1] as i32 / map.width;
-                        pos.try_move (&map, new_x-cur_x, new_y-cur_y);
-                        idx = xy_idx(pos.get_x(), pos.get_y());
-                        map.blocked[idx] = true;
-                        viewshed.dirty = true;
+                (AiState::Flee, _, Some((distance, target, at))) => {
+                    let fled = if target == *player_entity {
+                        follow(&mut map, pos, viewshed, &pathing.flee)
+                    } else {
+                        step_away(&mut map, pos, viewshed, at)
+                    };
+                    // A cornered monster fights back.
+                    if !fled && distance < 1.5 {
+                        wants_to_melee.insert(entity, WantsToMelee{ target }).expect("Unable to insert attack");
+                    }
+                }
+                (AiState::Search { turns }, _, _) => match behaviour.last_seen {
+                    Some(target) if target != here && step_towards(&mut map, pos, viewshed, target) => {}
+                    _ => {
+                        // Got there, or can't get any closer: look around for a while.
+                        behaviour.last_seen = None;
+                        random_step(&mut map, pos, viewshed, &mut rng);
+                        behaviour.state = if turns > 1 { AiState::Search { turns: turns - 1 } } else { gave_up };
+                    }
+                },
+                (AiState::ReturnHome, _, _) => {
+                    let home = behaviour.home.unwrap_or(here);
+                    if home == here || !step_towards(&mut map, pos, viewshed, home) {
+                        behaviour.state = AiState::Idle;
                     }
                 }
+                (AiState::Idle, _, _) if behaviour.wanders => {
+                    random_step(&mut map, pos, viewshed, &mut rng);
+                }
+                _ => {}
+            }
+
+            let idx = map.xy_idx(pos.get_x(), pos.get_y());
+            if let Some(door) = closed_doors.remove(&idx) {
+                opened_doors.push(Point::new(pos.get_x(), pos.get_y()));
+                *pos = Position::new(here.x, here.y);
+                let here_idx = map.xy_idx(here.x, here.y);
+                map.blocked[here_idx] = true;
+                map.blocked[idx] = false;
+                if let Some(door_state) = doors.get_mut(door) {
+                    door_state.set_open(true, renderables.get_mut(door));
+                }
+            }
+
+            if let Some(me) = creatures.iter_mut().find(|c| c.entity == entity) {
+                me.at = Point::new(pos.get_x(), pos.get_y());
             }
         }
+
+        for idx in closed_doors.keys() {
+            map.blocked[*idx] = true;
+        }
+        // Whoever could see those doors now sees past them.
+        for at in opened_doors {
+            Viewshed::look_again_at(&mut viewshed, at);
+        }
+    }
+}
+
+/// Moves pos onto the next tile of the way to target, for the places the PathingMaps do not
+/// lead to. Returns false when there is no way to get closer.
+fn step_towards(map: &mut Map, pos: &mut Position, viewshed: &mut Viewshed, target: Point) -> bool {
+    let path = rltk::a_star_search(map.xy_idx(pos.get_x(), pos.get_y()), map.xy_idx(target.x, target.y), &*map);
+    if !path.success || path.steps.len() < 2 {
+        return false;
+    }
+    let next_x = path.steps[1] as i32 % map.width;
+    let next_y = path.steps[1] as i32 / map.width;
+    move_by(map, pos, viewshed, next_x - pos.get_x(), next_y - pos.get_y())
+}
+
+/// Moves pos one step downhill on a PathingMaps field, around anything in the way. Returns
+/// false when every step that would help is blocked.
+fn follow(map: &mut Map, pos: &mut Position, viewshed: &mut Viewshed, field: &[f32]) -> bool {
+    let idx = map.xy_idx(pos.get_x(), pos.get_y());
+    match PathingMaps::downhill(field, map, idx) {
+        None => false,
+        Some(next) => {
+            let next_x = next as i32 % map.width;
+            let next_y = next as i32 / map.width;
+            move_by(map, pos, viewshed, next_x - pos.get_x(), next_y - pos.get_y())
+        }
+    }
+}
+
+/// Moves pos onto the neighbouring tile furthest from threat, if that is further than it is now.
+fn step_away(map: &mut Map, pos: &mut Position, viewshed: &mut Viewshed, threat: Point) -> bool {
+    let distance = |x: i32, y: i32| rltk::DistanceAlg::Pythagoras.distance2d(Point::new(x, y), threat);
+    let idx = map.xy_idx(pos.get_x(), pos.get_y());
+    let best = map.get_available_exits(idx)
+        .iter()
+        .map(|(exit, _cost)| (*exit as i32 % map.width, *exit as i32 / map.width))
+        .filter(|(x, y)| !map.is_blocked(*x, *y) && distance(*x, *y) > distance(pos.get_x(), pos.get_y()))
+        .max_by(|a, b| distance(a.0, a.1).total_cmp(&distance(b.0, b.1)));
+    match best {
+        None => false,
+        Some((x, y)) => move_by(map, pos, viewshed, x - pos.get_x(), y - pos.get_y()),
+    }
+}
+
+/// Moves pos one step in a random direction, though never into lava.
+fn random_step(map: &mut Map, pos: &mut Position, viewshed: &mut Viewshed, rng: &mut RandomNumberGenerator) -> bool {
+    let (delta_x, delta_y) = (rng.range(-1, 2), rng.range(-1, 2));
+    let destination = map.xy_idx(pos.get_x() + delta_x, pos.get_y() + delta_y);
+    (delta_x, delta_y) != (0, 0)
+        && map.tiles[destination] != TileType::Lava
+        && move_by(map, pos, viewshed, delta_x, delta_y)
+}
+
+fn move_by(map: &mut Map, pos: &mut Position, viewshed: &mut Viewshed, delta_x: i32, delta_y: i32) -> bool {
+    let old_idx = map.xy_idx(pos.get_x(), pos.get_y());
+    if !pos.try_move(map, delta_x, delta_y) {
+        return false;
+    }
+    map.blocked[old_idx] = false;
+    let new_idx = map.xy_idx(pos.get_x(), pos.get_y());
+    map.blocked[new_idx] = true;
+    viewshed.dirty = true;
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spawner::raws::spawn_named_entity;
+    use crate::systems::{
+        map_indexing_system::MapIndexingSystem, pathing_system::PathingSystem, visiblity_system::VisibilitySystem,
+    };
+    use crate::test_fixture::{floor_row, test_world};
+    use specs::prelude::*;
+
+    fn monster_turn(ecs: &mut World, monster: Entity) -> AiState {
+        ecs.write_storage::<Viewshed>().get_mut(monster).unwrap().dirty = true;
+        VisibilitySystem {}.run_now(ecs);
+        MapIndexingSystem {}.run_now(ecs);
+        PathingSystem {}.run_now(ecs);
+        ecs.write_storage::<MyTurn>().insert(monster, MyTurn {}).unwrap();
+        MonsterAI {}.run_now(ecs);
+        ecs.write_storage::<MyTurn>().clear();
+        ecs.write_storage::<WantsToMelee>().clear();
+        ecs.maintain();
+        ecs.read_storage::<Behaviour>().get(monster).unwrap().state
+    }
+
+    fn spawn_player(ecs: &mut World, x: i32, y: i32) -> Entity {
+        let player = ecs.create_entity()
+            .with(Position::new(x, y))
+            .with(CombatStats { hp: 20, hp_max: 20, attack: 0, defense: 0, is_dead: false })
+            .with(Faction { name: "player".to_string() })
+            .build();
+        ecs.insert(player);
+        ecs.insert(Point::new(x, y));
+        player
+    }
+
+    fn corridor_world() -> World {
+        let ecs = test_world();
+        floor_row(&ecs, 1..60, 10);
+        ecs
+    }
+
+    fn x_of(ecs: &World, entity: Entity) -> i32 {
+        ecs.read_storage::<Position>().get(entity).unwrap().get_x()
+    }
+
+    #[test]
+    fn goblins_chase_flee_and_search() {
+        let mut ecs = corridor_world();
+        let player = spawn_player(&mut ecs, 6, 10);
+        let goblin = spawn_named_entity(&mut ecs, "Goblin", 10, 10).unwrap();
+
+        assert!(monster_turn(&mut ecs, goblin) == AiState::Chase);
+        assert_eq!(x_of(&ecs, goblin), 9);
+
+        ecs.write_storage::<CombatStats>().get_mut(goblin).unwrap().hp = 1;
+        assert!(monster_turn(&mut ecs, goblin) == AiState::Flee);
+        assert_eq!(x_of(&ecs, goblin), 10);
+
+        // Out of sight, the goblin goes to look where the player was.
+        ecs.write_storage::<CombatStats>().get_mut(goblin).unwrap().hp = 16;
+        monster_turn(&mut ecs, goblin);
+        ecs.write_storage::<Position>().get_mut(player).unwrap().set_x(40);
+        *ecs.fetch_mut::<Point>() = Point::new(40, 10);
+        assert!(monster_turn(&mut ecs, goblin) == AiState::Search { turns: Behaviour::SEARCH_TURNS });
+        assert_eq!(x_of(&ecs, goblin), 8);
+    }
+
+    #[test]
+    fn factions_pick_their_own_fights() {
+        let mut ecs = corridor_world();
+        spawn_player(&mut ecs, 50, 10);
+        let miner = spawn_named_entity(&mut ecs, "Lost Miner", 7, 10).unwrap();
+        let goblin = spawn_named_entity(&mut ecs, "Goblin", 10, 10).unwrap();
+        let orc = spawn_named_entity(&mut ecs, "Orc", 11, 10).unwrap();
+
+        // The orc goes for the goblin next to it rather than the far off player.
+        ecs.write_storage::<Viewshed>().get_mut(orc).unwrap().dirty = true;
+        VisibilitySystem {}.run_now(&ecs);
+        MapIndexingSystem {}.run_now(&ecs);
+        PathingSystem {}.run_now(&ecs);
+        ecs.write_storage::<MyTurn>().insert(orc, MyTurn {}).unwrap();
+        MonsterAI {}.run_now(&ecs);
+        assert_eq!(ecs.read_storage::<WantsToMelee>().get(orc).map(|w| w.target), Some(goblin));
+        ecs.write_storage::<MyTurn>().clear();
+        ecs.write_storage::<WantsToMelee>().clear();
+
+        // The miner runs from the goblin.
+        assert!(monster_turn(&mut ecs, miner) == AiState::Flee);
+        assert_eq!(x_of(&ecs, miner), 6);
     }
-}
\ No newline at end of file
+}
@@ -1,4 +1,4 @@
-use rltk::{Rltk, RGB};
+use rltk::RGB;
 use specs::prelude::*;
 use specs::saveload::{ConvertSaveload, Marker};
 use specs::error::NoError;
@@ -6,7 +6,7 @@ use specs_derive::*;
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
 
-use crate::map_builders::{common::xy_idx, map::Map};
+use crate::map_builders::{map::Map};
 
 #[derive(Hash, Eq, PartialEq, Serialize, Deserialize, Copy, Clone, Debug)]
 pub enum OnDeathAction {
@@ -64,8 +64,7 @@ impl<'a> System<'a> for ParticleSpawnSystem {
                 lifetime: new_particle.lifetime,
 
             };
-            let idx = xy_idx(new_particle.x, new_particle.y);
-            let key = format!("{} @ {} for {}", p.glyph, idx, p.lifetime);
+            let key = format!("{} @ ({}, {}) for {}", p.glyph, new_particle.x, new_particle.y, p.lifetime);
             particles.particle.insert(key, p);
         }
         particle_builder.requests.clear();
@@ -116,7 +115,9 @@ impl ParticleBuilder {
     }
 }
 
-pub fn cull_dead_particles(ecs: &mut World, ctx: &Rltk) {
+/// Ages every particle by frame_time_ms and removes the ones that have expired. This takes the
+/// elapsed time rather than the Rltk context so it can also be driven without a window.
+pub fn cull_dead_particles(ecs: &mut World, frame_time_ms: f32) {
     let mut particles = ecs.fetch_mut::<Particles>();
     let mut dead_particles = Vec::new();
     {
@@ -124,7 +125,7 @@ pub fn cull_dead_particles(ecs: &mut World, ctx: &Rltk) {
         let mut map = ecs.fetch_mut::<Map>();
         let mut particle_builder = ecs.fetch_mut::<ParticleBuilder>();
         for (key, particle) in particles.particle.iter_mut() {
-            particle.lifetime -= ctx.frame_time_ms;
+            particle.lifetime -= frame_time_ms;
             if particle.lifetime > 0.0 {
                 match particle.action {
                     OnDeathAction::RevealMap => { 
@@ -0,0 +1,135 @@
+use rltk::{BaseMap, DijkstraMap, Point};
+use specs::prelude::*;
+use std::collections::VecDeque;
+
+use crate::map_builders::{map::Map};
+
+/// How far, in steps, the pathing maps reach out from the player.
+const MAX_DEPTH: f32 = 200.0;
+
+/// Running away weighs this much more than being far away, so that fleeing monsters head for
+/// open ground rather than the nearest dead end.
+const FLEE_WEIGHT: f32 = -1.2;
+
+/// PathingMaps are the flow fields every monster walks on. to_player holds how many steps each
+/// tile is from the player, so walking downhill closes in on them; flee is walked downhill to
+/// get away. They follow the map's exits, so each step costs what its terrain does and walls,
+/// deep water and locked doors cannot be crossed at all. Other creatures are obstacles of the
+/// moment, left for the monster_ai_system to step around.
+#[derive(Default)]
+pub struct PathingMaps {
+    pub to_player: Vec<f32>,
+    pub flee: Vec<f32>,
+    /// Set when the way across the map changes, such as a door being locked or unlocked, so
+    /// that the maps are built again even though the player has not moved.
+    pub dirty: bool,
+    built_for: Option<(Point, i32, u64)>,
+}
+
+impl PathingMaps {
+    /// Picks the unblocked neighbour of idx that is lowest on field, if it is lower than idx
+    /// itself. Returns its index.
+    pub fn downhill(field: &[f32], map: &Map, idx: usize) -> Option<usize> {
+        map.get_available_exits(idx)
+            .iter()
+            .map(|(exit, _cost)| *exit)
+            .filter(|exit| !map.blocked[*exit] && field[*exit] < field[idx])
+            .min_by(|a, b| field[*a].total_cmp(&field[*b]))
+    }
+}
+
+/// The PathingSystem builds the PathingMaps. They only change when the player moves, the level
+/// changes or they are marked dirty, so they are built at most once a turn however many
+/// monsters use them.
+pub struct PathingSystem {}
+
+impl<'a> System<'a> for PathingSystem {
+    type SystemData = (
+        ReadExpect<'a, Map>,
+        ReadExpect<'a, Point>, // The player's position
+        WriteExpect<'a, PathingMaps>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (map, player_pos, mut pathing) = data;
+
+        let key = Some((*player_pos, map.depth, map.seed));
+        if pathing.built_for == key && !pathing.dirty {
+            return;
+        }
+        pathing.built_for = key;
+        pathing.dirty = false;
+
+        let player_idx = map.xy_idx(player_pos.x, player_pos.y);
+        let mut to_player = DijkstraMap::new(map.width, map.height, &[player_idx], &*map, MAX_DEPTH).map;
+        to_player[player_idx] = 0.0;
+
+        // The flee map starts from the distances turned upside down and is then relaxed like
+        // any other Dijkstra map, so that every tile leads towards the best place to run to.
+        let mut flee: Vec<f32> = to_player
+            .iter()
+            .map(|d| if *d < MAX_DEPTH { d * FLEE_WEIGHT } else { f32::MAX })
+            .collect();
+        let mut open_list: VecDeque<usize> = (0..flee.len()).filter(|idx| flee[*idx] < f32::MAX).collect();
+        while let Some(idx) = open_list.pop_front() {
+            for (exit, cost) in map.get_available_exits(idx) {
+                if flee[idx] + cost < flee[exit] {
+                    flee[exit] = flee[idx] + cost;
+                    open_list.push_back(exit);
+                }
+            }
+        }
+
+        pathing.to_player = to_player;
+        pathing.flee = flee;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixture::{floor_row, test_world};
+
+    #[test]
+    fn monsters_step_around_each_other() {
+        let mut ecs = test_world();
+        for y in 5..10 {
+            floor_row(&ecs, 5..10, y);
+        }
+        ecs.fetch_mut::<Map>().populate_blocked();
+        ecs.insert(Point::new(9, 7));
+        PathingSystem {}.run_now(&ecs);
+
+        let pathing = ecs.fetch::<PathingMaps>();
+        let mut map = ecs.fetch_mut::<Map>();
+        let from = map.xy_idx(5, 7);
+        assert_eq!(PathingMaps::downhill(&pathing.to_player, &map, from), Some(map.xy_idx(6, 7)));
+
+        // With the way ahead taken, a diagonal still gets closer; running away leads west.
+        let idx = map.xy_idx(6, 7);
+        map.blocked[idx] = true;
+        let step = PathingMaps::downhill(&pathing.to_player, &map, from).unwrap();
+        assert!(step == map.xy_idx(6, 6) || step == map.xy_idx(6, 8));
+        assert_eq!(PathingMaps::downhill(&pathing.flee, &map, map.xy_idx(6, 6)).map(|i| i as i32 % map.width), Some(5));
+    }
+
+    #[test]
+    fn dirty_maps_are_built_again() {
+        let mut ecs = test_world();
+        floor_row(&ecs, 5..10, 7);
+        ecs.fetch_mut::<Map>().populate_blocked();
+        ecs.insert(Point::new(9, 7));
+        PathingSystem {}.run_now(&ecs);
+        let far_end = ecs.fetch::<Map>().xy_idx(5, 7);
+        assert!(ecs.fetch::<PathingMaps>().to_player[far_end] < MAX_DEPTH);
+
+        // A door locked across the corridor cuts the far end off, once the maps know of it.
+        let door = ecs.fetch::<Map>().xy_idx(7, 7);
+        ecs.fetch_mut::<Map>().locked.insert(door);
+        PathingSystem {}.run_now(&ecs);
+        assert!(ecs.fetch::<PathingMaps>().to_player[far_end] < MAX_DEPTH);
+        ecs.fetch_mut::<PathingMaps>().dirty = true;
+        PathingSystem {}.run_now(&ecs);
+        assert!(ecs.fetch::<PathingMaps>().to_player[far_end] >= MAX_DEPTH);
+    }
+}
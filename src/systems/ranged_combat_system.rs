@@ -0,0 +1,254 @@
+use rltk::{LineAlg, Point, RandomNumberGenerator};
+use specs::prelude::*;
+
+use crate::components::{Encumbrance, InBackpack, SufferDamage, WantsToShoot};
+use crate::gamelog::GameLog;
+use crate::map_builders::{map::Map};
+use crate::spawner::components::{
+    Ammunition, CombatStats, DefenseBonus, Equipped, GeneralStats, Name, Position, RangedWeapon,
+};
+use crate::spawner::item::{Durability, Stackable};
+use crate::systems::melee_combat_system::{armour_class, combat_penalty};
+use crate::systems::particle_system::{OnDeathAction, ParticleBuilder};
+
+/// The RangedCombatSystem resolves shots. The projectile flies along a line from the shooter
+/// towards the chosen tile and stops at the first wall, closed door or creature in its way; a
+/// creature is then attacked with the same d20 roll against armour class that melee uses.
+/// Fired Ammunition is used up, while a thrown weapon lands where it stopped.
+pub struct RangedCombatSystem {}
+
+impl<'a> System<'a> for RangedCombatSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, WantsToShoot>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, CombatStats>,
+        WriteStorage<'a, SufferDamage>,
+        WriteExpect<'a, GameLog>,
+        ReadStorage<'a, RangedWeapon>,
+        ReadStorage<'a, Ammunition>,
+        WriteStorage<'a, Stackable>,
+        ReadStorage<'a, InBackpack>,
+        ReadStorage<'a, DefenseBonus>,
+        WriteStorage<'a, Equipped>,
+        WriteStorage<'a, Position>,
+        ReadStorage<'a, GeneralStats>,
+        WriteExpect<'a, ParticleBuilder>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        ReadExpect<'a, Map>,
+        ReadExpect<'a, Entity>,
+        ReadStorage<'a, Encumbrance>,
+        ReadStorage<'a, Durability>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            mut wants_shoot,
+            names,
+            combat_stats,
+            mut inflict_damage,
+            mut log,
+            ranged_weapons,
+            ammunition,
+            mut stackables,
+            backpack,
+            defense_bonus,
+            mut equipped,
+            mut positions,
+            attributes,
+            mut particle_builder,
+            mut rng,
+            map,
+            player_entity,
+            encumbrance,
+            durability,
+        ) = data;
+
+        for (entity, wants_shoot, name, stats) in (&entities, &wants_shoot, &names, &combat_stats).join() {
+            if stats.is_dead {
+                continue;
+            }
+            let Some(start) = positions.get(entity).map(|pos| Point::new(pos.get_x(), pos.get_y())) else {
+                continue;
+            };
+
+            let Some((weapon_entity, weapon)) = (&entities, &ranged_weapons, &equipped)
+                .join()
+                .find(|(_e, _w, equipped_by)| equipped_by.owner == entity)
+                .map(|(e, w, _equipped_by)| (e, w.clone()))
+            else {
+                continue;
+            };
+            let weapon_name = names.get(weapon_entity).map_or(String::new(), |n| n.name.clone());
+
+            // Bows and crossbows need something to shoot; one shot comes off the stack.
+            if let Some(kind) = &weapon.ammo {
+                let quiver = (&entities, &ammunition, &backpack)
+                    .join()
+                    .find(|(_e, ammo, in_pack)| in_pack.owner == entity && ammo.kind == *kind)
+                    .map(|(ammo_entity, _ammo, _in_pack)| ammo_entity);
+                match quiver {
+                    None => {
+                        if entity == *player_entity {
+                            log.entries.push(format!("You have nothing to shoot from the {}.", weapon_name));
+                        }
+                        continue;
+                    }
+                    Some(ammo_entity) => {
+                        let left = stackables.get_mut(ammo_entity).map_or(0, |stack| {
+                            stack.count -= 1;
+                            stack.count
+                        });
+                        if left <= 0 {
+                            entities.delete(ammo_entity).expect("Unable to delete");
+                        }
+                    }
+                }
+            }
+
+            // Follow the line until it leaves the weapon's range, meets a wall or a closed door,
+            // or meets a creature.
+            let mut landing = start;
+            let mut victim = None;
+            for step in rltk::line2d(LineAlg::Bresenham, start, wants_shoot.target).into_iter().skip(1) {
+                if step.x < 0 || step.x >= map.width || step.y < 0 || step.y >= map.height {
+                    break;
+                }
+                if rltk::DistanceAlg::Pythagoras.distance2d(start, step) > weapon.range as f32 {
+                    break;
+                }
+                let idx = map.xy_idx(step.x, step.y);
+                if map.is_opaque(idx) {
+                    break;
+                }
+                landing = step;
+                victim = map.tile_content[idx]
+                    .iter()
+                    .find(|e| **e != entity && combat_stats.get(**e).is_some_and(|s| !s.is_dead))
+                    .copied();
+                if victim.is_some() {
+                    break;
+                }
+            }
+
+            particle_builder.request(
+                landing.x,
+                landing.y,
+                rltk::RGB::named(rltk::CYAN),
+                rltk::RGB::named(rltk::BLACK),
+                rltk::to_cp437('*'),
+                200.0,
+                OnDeathAction::NoAction,
+            );
+
+            match victim {
+                None => {
+                    if entity == *player_entity {
+                        log.entries.push("The shot hits nothing.".to_string());
+                    }
+                }
+                Some(victim) => {
+                    let target_name = names.get(victim).map_or("something".to_string(), |n| n.name.clone());
+                    let target_stats = combat_stats.get(victim).unwrap();
+                    let armour_class = armour_class(victim, target_stats, &defense_bonus, &equipped, &attributes, &encumbrance, &durability);
+                    let hit_bonus = stats.attack
+                        + weapon.power
+                        + attributes.get(entity).map_or(0, |a| GeneralStats::modifier(a.dexterity))
+                        - combat_penalty(entity, &encumbrance);
+
+                    let natural_roll = rng.roll_dice(1, 20);
+                    let critical = natural_roll == 20;
+                    if natural_roll == 1 || (!critical && natural_roll + hit_bonus < armour_class) {
+                        log.entries.push(format!("{} misses {}.", &name.name, target_name));
+                    } else {
+                        let mut damage = weapon.damage.roll(&mut rng);
+                        if critical {
+                            damage += weapon.damage.roll(&mut rng);
+                        }
+                        damage = i32::max(1, damage + weapon.power);
+                        log.entries.push(format!(
+                            "{}{} hits {} with the {}, for {} hp.",
+                            if critical { "Critical! " } else { "" },
+                            &name.name, target_name, weapon_name, damage
+                        ));
+                        SufferDamage::new_damage(&mut inflict_damage, victim, damage, entity == *player_entity);
+                    }
+                }
+            }
+
+            // A thrown weapon leaves the thrower's hand and has to be picked up again.
+            if weapon.ammo.is_none() {
+                equipped.remove(weapon_entity);
+                positions
+                    .insert(weapon_entity, Position::new(landing.x, landing.y))
+                    .expect("Unable to insert position");
+            }
+        }
+
+        wants_shoot.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map_builders::map::TileType;
+    use crate::spawner::components::EquipmentSlot;
+    use crate::spawner::player::Player;
+    use crate::spawner::raws::spawn_named_entity;
+    use crate::systems::map_indexing_system::MapIndexingSystem;
+    use crate::systems::visiblity_system::VisibilitySystem;
+    use crate::test_fixture::{carry, floor_row, test_world, wield};
+
+    fn shoot_at(ecs: &mut World, x: i32, y: i32) {
+        let player = *ecs.fetch::<Entity>();
+        ecs.write_storage::<WantsToShoot>().insert(player, WantsToShoot { target: Point::new(x, y) }).unwrap();
+        MapIndexingSystem {}.run_now(ecs);
+        RangedCombatSystem {}.run_now(ecs);
+        ecs.maintain();
+    }
+
+    #[test]
+    fn walls_stop_arrows() {
+        let mut ecs = test_world();
+        floor_row(&ecs, 5..16, 10);
+        Player::new(&mut ecs, 5, 10);
+        let player = *ecs.fetch::<Entity>();
+
+        wield(&mut ecs, player, "Shortbow", EquipmentSlot::Ranged);
+        let arrows = carry(&mut ecs, player, "Arrows");
+        spawn_named_entity(&mut ecs, "Orc", 12, 10).unwrap();
+
+        let in_the_way = ecs.fetch::<Map>().xy_idx(9, 10);
+        ecs.fetch_mut::<Map>().tiles[in_the_way] = TileType::Wall;
+        shoot_at(&mut ecs, 12, 10);
+        assert_eq!(ecs.read_storage::<Stackable>().get(arrows).unwrap().count, 19);
+        assert_eq!(ecs.fetch::<GameLog>().entries.last().unwrap(), "The shot hits nothing.");
+
+        ecs.fetch_mut::<Map>().tiles[in_the_way] = TileType::Floor;
+        shoot_at(&mut ecs, 12, 10);
+        assert_eq!(ecs.read_storage::<Stackable>().get(arrows).unwrap().count, 18);
+        assert!(ecs.fetch::<GameLog>().entries.last().unwrap().contains("Orc"));
+    }
+
+    #[test]
+    fn closed_doors_stop_arrows() {
+        let mut ecs = test_world();
+        floor_row(&ecs, 5..16, 10);
+        Player::new(&mut ecs, 5, 10);
+        let player = *ecs.fetch::<Entity>();
+        wield(&mut ecs, player, "Shortbow", EquipmentSlot::Ranged);
+        carry(&mut ecs, player, "Arrows");
+        let orc = spawn_named_entity(&mut ecs, "Orc", 12, 10).unwrap();
+        spawn_named_entity(&mut ecs, "Door", 9, 10).unwrap();
+        VisibilitySystem {}.run_now(&ecs);
+
+        let hp = ecs.read_storage::<CombatStats>().get(orc).unwrap().hp;
+        shoot_at(&mut ecs, 12, 10);
+        assert_eq!(ecs.fetch::<GameLog>().entries.last().unwrap(), "The shot hits nothing.");
+        assert!(ecs.read_storage::<SufferDamage>().get(orc).is_none());
+        assert_eq!(ecs.read_storage::<CombatStats>().get(orc).unwrap().hp, hp);
+    }
+}
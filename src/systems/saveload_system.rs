@@ -2,8 +2,8 @@ use specs::prelude::*;
 use specs::saveload::{SimpleMarker, SimpleMarkerAllocator, SerializeComponents, DeserializeComponents, MarkedBuilder};
 use specs::error::NoError;
 use crate::components::*;
-use crate::map_builders::map::Map;
-use crate::spawner::{components::*, item::*, player::Player, monster::Monster};
+use crate::map_builders::{dungeon::MasterDungeonMap, map::Map};
+use crate::spawner::{components::*, item::*, player::Player, monster::Behaviour, monster::Faction, monster::Monster};
 use std::fs::File;
 use std::path::Path;
 use std::fs;
@@ -30,9 +30,11 @@ pub fn save_game(_ecs : &mut World) {
 pub fn save_game(ecs : &mut World) {
     // Create helper
     let mapcopy = ecs.get_mut::<Map>().unwrap().clone();
+    let dungeon_master = ecs.get_mut::<MasterDungeonMap>().unwrap().clone();
+    let identification = ecs.get_mut::<Identification>().unwrap().clone();
     let savehelper = ecs
         .create_entity()
-        .with(SerializationHelper{ map : mapcopy })
+        .with(SerializationHelper{ map : mapcopy, dungeon_master, identification })
         .marked::<SimpleMarker<SerializeMe>>()
         .build();
 
@@ -44,9 +46,12 @@ pub fn save_game(ecs : &mut World) {
         let mut serializer = serde_json::Serializer::new(writer);
         serialize_individually!(ecs, serializer, data, Position, Renderable, Player, Viewshed, Monster,
             Name, BlocksTile, CombatStats, SufferDamage, WantsToMelee, Item, Consumable, Ranged, InflictsDamage,
-            AreaOfEffect, Confusion, ProvidesHealing, InBackpack, WantsToPickupItem, WantsToUseItem,
+            AreaOfEffect, StatusEffects, InflictsStatus, ProvidesHealing, InBackpack, WantsToPickupItem, WantsToUseItem,
             WantsToDropItem, SerializationHelper, Equippable, Equipped, MeleePowerBonus, DefenseBonus,
-            WantsToRemoveItem, HungerClock, Hidden, EntryTrigger, SingleActivation
+            WantsToRemoveItem, HungerClock, Hidden, EntryTrigger, SingleActivation, OtherLevelPosition,
+            Actor, GeneralStats, Experience, ExperienceValue, NaturalAttack, RangedWeapon, Ammunition,
+            WantsToShoot, Behaviour, Faction, Door, Key, MagicItem, Identifies, Stackable,
+            Charges, Recharges, Durability, Repairs, Enchantment, Cursed, RemovesCurse, RawName
         );
     }
 
@@ -93,9 +98,12 @@ pub fn load_game(ecs: &mut World) {
 
         deserialize_individually!(ecs, de, d, Position, Renderable, Player, Viewshed, Monster,
             Name, BlocksTile, CombatStats, SufferDamage, WantsToMelee, Item, Consumable, Ranged, InflictsDamage,
-            AreaOfEffect, Confusion, ProvidesHealing, InBackpack, WantsToPickupItem, WantsToUseItem,
+            AreaOfEffect, StatusEffects, InflictsStatus, ProvidesHealing, InBackpack, WantsToPickupItem, WantsToUseItem,
             WantsToDropItem, SerializationHelper, Equippable, Equipped, MeleePowerBonus, DefenseBonus,
-            WantsToRemoveItem, HungerClock, Hidden, EntryTrigger, SingleActivation
+            WantsToRemoveItem, HungerClock, Hidden, EntryTrigger, SingleActivation, OtherLevelPosition,
+            Actor, GeneralStats, Experience, ExperienceValue, NaturalAttack, RangedWeapon, Ammunition,
+            WantsToShoot, Behaviour, Faction, Door, Key, MagicItem, Identifies, Stackable,
+            Charges, Recharges, Durability, Repairs, Enchantment, Cursed, RemovesCurse, RawName
         );
     }
 
@@ -109,6 +117,10 @@ pub fn load_game(ecs: &mut World) {
             let mut worldmap = ecs.write_resource::<Map>();
             *worldmap = h.map.clone();
             worldmap.tile_content = vec![Vec::new(); (h.map.width * h.map.height) as usize];
+            let mut dungeon_master = ecs.write_resource::<MasterDungeonMap>();
+            *dungeon_master = h.dungeon_master.clone();
+            let mut identification = ecs.write_resource::<Identification>();
+            *identification = h.identification.clone();
             deleteme = Some(e);
         }
         for (e,_p,pos) in (&entities, &player, &position).join() {
@@ -0,0 +1,130 @@
+use specs::prelude::*;
+
+use crate::components::{MyTurn, SufferDamage};
+use crate::gamelog::GameLog;
+use crate::map_builders::{map::Map};
+use crate::spawner::components::{CombatStats, Name, Position, StatusEffects, StatusKind, Viewshed};
+
+/// The StatusEffectSystem counts down timed effects on the turns of the Entity under them, and
+/// applies the ones that work every turn: Poison hurts and Regeneration heals. Expired effects
+/// are reported for the player and for creatures the player can see.
+pub struct StatusEffectSystem {}
+
+impl<'a> System<'a> for StatusEffectSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, StatusEffects>,
+        ReadStorage<'a, MyTurn>,
+        WriteStorage<'a, CombatStats>,
+        WriteStorage<'a, SufferDamage>,
+        WriteStorage<'a, Viewshed>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Position>,
+        ReadExpect<'a, Map>,
+        ReadExpect<'a, Entity>, // The player
+        WriteExpect<'a, GameLog>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            mut status_effects,
+            turns,
+            mut combat_stats,
+            mut inflict_damage,
+            mut viewsheds,
+            names,
+            positions,
+            map,
+            player_entity,
+            mut log,
+        ) = data;
+
+        let mut cured = Vec::new();
+        for (entity, status, _turn) in (&entities, &mut status_effects, &turns).join() {
+            for effect in status.effects.iter_mut() {
+                match effect.kind {
+                    StatusKind::Poison => {
+                        SufferDamage::new_damage(&mut inflict_damage, entity, effect.potency, false);
+                    }
+                    StatusKind::Regeneration => {
+                        if let Some(stats) = combat_stats.get_mut(entity) {
+                            stats.heal(effect.potency);
+                        }
+                    }
+                    _ => {}
+                }
+                effect.turns -= 1;
+            }
+
+            // Blindness shrinks what can be seen, so the view has to be worked out again while
+            // it lasts and once it wears off.
+            if status.has(StatusKind::Blindness) {
+                if let Some(viewshed) = viewsheds.get_mut(entity) {
+                    viewshed.dirty = true;
+                }
+            }
+
+            let visible = entity == *player_entity
+                || positions.get(entity).is_some_and(|pos| map.visible_tiles[map.xy_idx(pos.get_x(), pos.get_y())]);
+            for effect in status.effects.iter().filter(|e| e.turns < 1) {
+                if entity == *player_entity {
+                    log.entries.push(format!("You are no longer {}.", effect.kind.adjective()));
+                } else if visible {
+                    if let Some(name) = names.get(entity) {
+                        log.entries.push(format!("{} is no longer {}.", &name.name, effect.kind.adjective()));
+                    }
+                }
+            }
+            status.effects.retain(|e| e.turns > 0);
+            if status.effects.is_empty() {
+                cured.push(entity);
+            }
+        }
+
+        for entity in cured {
+            status_effects.remove(entity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spawner::components::StatusEffect;
+    use crate::test_fixture::test_world;
+
+    fn effect(kind: StatusKind, turns: i32, potency: i32) -> StatusEffect {
+        StatusEffect { kind, turns, potency }
+    }
+
+    #[test]
+    fn poison_stacks_and_wears_off() {
+        let mut ecs = test_world();
+        let player = ecs.create_entity()
+            .with(CombatStats { hp: 20, hp_max: 20, attack: 0, defense: 0, is_dead: false })
+            .build();
+        ecs.insert(player);
+
+        {
+            let mut status = ecs.write_storage::<StatusEffects>();
+            StatusEffects::inflict(&mut status, player, effect(StatusKind::Poison, 2, 1));
+            StatusEffects::inflict(&mut status, player, effect(StatusKind::Poison, 1, 2));
+            StatusEffects::inflict(&mut status, player, effect(StatusKind::Haste, 5, 1));
+            StatusEffects::inflict(&mut status, player, effect(StatusKind::Slow, 5, 1));
+            let effects = &status.get(player).unwrap().effects;
+            assert_eq!(effects, &vec![effect(StatusKind::Poison, 2, 3)]);
+        }
+
+        for _turn in 0..2 {
+            ecs.write_storage::<MyTurn>().insert(player, MyTurn {}).unwrap();
+            StatusEffectSystem {}.run_now(&ecs);
+            ecs.maintain();
+        }
+        let damage = ecs.read_storage::<SufferDamage>();
+        assert_eq!(damage.get(player).unwrap().amount, vec![(3, false), (3, false)]);
+        assert!(ecs.read_storage::<StatusEffects>().get(player).is_none());
+        assert_eq!(ecs.fetch::<GameLog>().entries.last().unwrap(), "You are no longer poisoned.");
+    }
+}
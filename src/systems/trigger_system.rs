@@ -1,8 +1,10 @@
 use specs::prelude::*;
 
 use crate::gamelog::GameLog;
-use crate::map_builders::{common::xy_idx, map::Map};
-use crate::spawner::components::{EntryTrigger, Hidden, InflictsDamage, Name, Position, SingleActivation};
+use crate::map_builders::{map::Map};
+use crate::spawner::components::{
+    EntryTrigger, Hidden, InflictsDamage, InflictsStatus, Name, Position, SingleActivation, StatusEffects,
+};
 use crate::systems::particle_system::{OnDeathAction, ParticleBuilder};
 use crate::components::{EntityMoved, SufferDamage};
 
@@ -21,15 +23,17 @@ impl<'a> System<'a> for TriggerSystem {
                         WriteStorage<'a, SufferDamage>,
                         ReadStorage<'a, SingleActivation>,
                         Entities<'a>,
-                        WriteExpect<'a, GameLog>);
+                        WriteExpect<'a, GameLog>,
+                        ReadStorage<'a, InflictsStatus>,
+                        WriteStorage<'a, StatusEffects>);
 
     fn run(&mut self, data : Self::SystemData) {
-        let (map, mut entity_moved, position, entry_trigger, inflicts_damage, mut hidden, mut particle_builder, names, mut inflict_damage, single_activation, entities, mut log) = data;
+        let (map, mut entity_moved, position, entry_trigger, inflicts_damage, mut hidden, mut particle_builder, names, mut inflict_damage, single_activation, entities, mut log, inflicts_status, mut status_effects) = data;
 
         // Iterate the entities that moved and their final position
         let mut remove_entities : Vec<Entity> = Vec::new();
         for (entity, mut _entity_moved, pos) in (&entities, &mut entity_moved, &position).join() {
-            let idx = xy_idx(pos.get_x(), pos.get_y());
+            let idx = map.xy_idx(pos.get_x(), pos.get_y());
             for entity_id in map.tile_content[idx].iter() {
                 if entity != *entity_id { // Do not bother to check yourself for being a trap!
                     let maybe_trigger = entry_trigger.get(*entity_id);
@@ -45,7 +49,12 @@ impl<'a> System<'a> for TriggerSystem {
                             let damage = inflicts_damage.get(*entity_id);
                             if let Some(damage) = damage {
                                 particle_builder.request(pos.get_x(), pos.get_y(), rltk::RGB::named(rltk::ORANGE), rltk::RGB::named(rltk::BLACK), rltk::to_cp437('‼'), 200.0, OnDeathAction::NoAction);
-                                SufferDamage::new_damage(&mut inflict_damage, entity, damage.damage);
+                                SufferDamage::new_damage(&mut inflict_damage, entity, damage.damage, false);
+                            }
+                            // If the trap poisons, blinds or the like, do that too
+                            let status = inflicts_status.get(*entity_id);
+                            if let Some(status) = status {
+                                StatusEffects::inflict(&mut status_effects, entity, status.effect);
                             }
                             hidden.remove(*entity_id); // The trap is no longer hidden
                             // If it is single activation, it needs to be removed
@@ -0,0 +1,85 @@
+use rltk::Point;
+
+use crate::map_builders::map::Map;
+
+/// The part of the console the Map is drawn into, above the log panel.
+pub const VIEW_WIDTH: i32 = 80;
+pub const VIEW_HEIGHT: i32 = 43;
+
+/// The Camera decides which part of the Map is on screen, and translates between map (world)
+/// coordinates and console (screen) coordinates. Everything drawn over the Map, and anything
+/// that reads the mouse to pick a tile, goes through it.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct Camera {
+    /// The map tile shown in the top left corner of the view.
+    pub origin: Point,
+    map_width: i32,
+    map_height: i32,
+}
+
+impl Camera {
+    /// A Camera centred on the given point. The view stops at the edges of the Map rather
+    /// than showing empty space beyond them, and a Map smaller than the view is drawn from
+    /// its own top left corner.
+    pub fn new(map: &Map, centre: Point) -> Camera {
+        let origin = |centre: i32, map_size: i32, view_size: i32| {
+            i32::clamp(centre - view_size / 2, 0, i32::max(map_size - view_size, 0))
+        };
+        Camera {
+            origin: Point::new(origin(centre.x, map.width, VIEW_WIDTH), origin(centre.y, map.height, VIEW_HEIGHT)),
+            map_width: map.width,
+            map_height: map.height,
+        }
+    }
+
+    /// Where the map tile (x, y) is drawn on the console, or None if it is outside the view.
+    pub fn to_screen(self, x: i32, y: i32) -> Option<Point> {
+        let (screen_x, screen_y) = (x - self.origin.x, y - self.origin.y);
+        if (0..VIEW_WIDTH).contains(&screen_x) && (0..VIEW_HEIGHT).contains(&screen_y) {
+            Some(Point::new(screen_x, screen_y))
+        } else {
+            None
+        }
+    }
+
+    /// The map tile drawn at console position (x, y), such as the one under the mouse, or
+    /// None if no tile of the Map is drawn there.
+    pub fn to_world(self, x: i32, y: i32) -> Option<Point> {
+        let (world_x, world_y) = (x + self.origin.x, y + self.origin.y);
+        let in_view = (0..VIEW_WIDTH).contains(&x) && (0..VIEW_HEIGHT).contains(&y);
+        if in_view && (0..self.map_width).contains(&world_x) && (0..self.map_height).contains(&world_y) {
+            Some(Point::new(world_x, world_y))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn camera_follows_the_player_but_stays_on_the_map() {
+        let big = Map::new(1, 120, 80);
+        let camera = Camera::new(&big, Point::new(60, 40));
+        assert_eq!(camera.origin, Point::new(20, 19));
+        assert_eq!(camera.to_screen(60, 40), Some(Point::new(40, 21)));
+        assert_eq!(camera.to_world(40, 21), Some(Point::new(60, 40)));
+        assert_eq!(camera.to_screen(10, 40), None);
+
+        // Near a corner the view stops at the edge of the Map.
+        let corner = Camera::new(&big, Point::new(115, 2));
+        assert_eq!(corner.origin, Point::new(40, 0));
+        assert_eq!(corner.to_screen(115, 2), Some(Point::new(75, 2)));
+        assert_eq!(corner.to_world(79, 42), Some(Point::new(119, 42)));
+
+        // A small Map is not scrolled at all, and the screen beyond it holds no tiles.
+        let small = Map::new(1, 40, 24);
+        let still = Camera::new(&small, Point::new(35, 20));
+        assert_eq!(still.origin, Point::new(0, 0));
+        assert_eq!(still.to_world(35, 20), Some(Point::new(35, 20)));
+        assert_eq!(still.to_world(50, 20), None);
+        assert_eq!(still.to_world(10, 45), None);
+    }
+}
@@ -1,14 +1,18 @@
 use rltk::{Point, RGB, Rltk, VirtualKeyCode};
 use specs::prelude::*;
 
+pub mod camera;
 pub mod main_menu;
+use camera::Camera;
 pub use main_menu::MainMenuSelection;
 
-use crate::components::InBackpack;
+use crate::components::{Encumbrance, EncumbranceTier, InBackpack};
 use crate::gamelog::GameLog;
-use crate::map_builders::{map::Map, common::xy_idx};
-use crate::spawner::components::{CombatStats, Equipped, Hidden, HungerState, HungerClock, Name, Position, Renderable, Viewshed};
-use crate::spawner::player::Player;
+use crate::map_builders::{map::Map};
+use crate::spawner::components::{CombatStats, DefenseBonus, EquipmentSlot, Equipped, Experience, GeneralStats, Hidden, HungerState, HungerClock, MeleePowerBonus, Name, Position, Renderable, StatusEffects, Viewshed};
+use crate::spawner::item::{Charges, Cursed, Durability, Enchantment, Stackable};
+use crate::spawner::player::{LevelUpChoice, Player};
+use crate::systems::melee_combat_system::armour_class;
 use crate::systems::particle_system::Particles;
 use crate::state_machine::{State, RunState};
 
@@ -30,10 +34,15 @@ impl<'a> System<'a> for UISystem<'_> {
                         ReadStorage<'a, Hidden>,
                         ReadExpect<'a, Particles>,
                         ReadExpect<'a, GameLog>,
-                        WriteExpect<'a, RunState>);
+                        WriteExpect<'a, RunState>,
+                        ReadStorage<'a, GeneralStats>,
+                        ReadStorage<'a, Experience>,
+                        ReadStorage<'a, StatusEffects>,
+                        ReadExpect<'a, Point>,
+                        ReadStorage<'a, Encumbrance>);
 
     fn run(&mut self, data : Self::SystemData) {
-        let (map, names, positions, renderables, combat_stats, players, hunger, hidden, particles, gamelog, runstate) = data;
+        let (map, names, positions, renderables, combat_stats, players, hunger, hidden, particles, gamelog, runstate, attributes, experience, status_effects, player_pos, encumbrance) = data;
 
         let newrunstate = *runstate;
 
@@ -41,7 +50,8 @@ impl<'a> System<'a> for UISystem<'_> {
             RunState::MainMenu{..} => {}
             _ => { 
                    
-                map.draw_map(0, &mut self.ctx);
+                let camera = Camera::new(&map, *player_pos);
+                map.draw_map(0, &camera, self.ctx);
                 
                 self.ctx.draw_box(0, 43, 79, 6, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
                 
@@ -62,6 +72,34 @@ impl<'a> System<'a> for UISystem<'_> {
                     }
                 }
 
+                // Status effects are shown as icons, to the left of the hunger indicator.
+                for (_player, status) in (&players, &status_effects).join() {
+                    for (i, effect) in status.effects.iter().enumerate() {
+                        let (glyph, fg) = effect.kind.icon();
+                        self.ctx.set(69 - 2 * i as i32, 42, fg, RGB::named(rltk::BLACK), rltk::to_cp437(glyph));
+                    }
+                }
+
+                // A heavy load is shown to the left of the status icons.
+                for (_player, load, status) in (&players, &encumbrance, status_effects.maybe()).join() {
+                    let icons = status.map_or(0, |s| s.effects.len() as i32);
+                    let x = 60 - 2 * icons;
+                    match load.tier {
+                        EncumbranceTier::Unburdened => {}
+                        EncumbranceTier::Burdened => self.ctx.print_color(x, 42, RGB::named(rltk::ORANGE), RGB::named(rltk::BLACK), "Burdened"),
+                        EncumbranceTier::Strained => self.ctx.print_color(x, 42, RGB::named(rltk::RED), RGB::named(rltk::BLACK), "Strained"),
+                    }
+                }
+
+                for (_player, attr) in (&players, &attributes).join() {
+                    let line = format!(" STR {}  DEX {}  CON {}  INT {} ", attr.strength, attr.dexterity, attr.constitution, attr.intelligence);
+                    self.ctx.print_color(2, 49, RGB::named(rltk::CYAN), RGB::named(rltk::BLACK), &line);
+                }
+                for (_player, xp) in (&players, &experience).join() {
+                    let line = format!(" Level {}  XP {} / {} ", xp.level, xp.xp, Experience::next_level_at(xp.level));
+                    self.ctx.print_color(38, 49, RGB::named(rltk::CYAN), RGB::named(rltk::BLACK), &line);
+                }
+
                 // Draw mouse cursor
                 let mouse_pos = self.ctx.mouse_pos();
                 self.ctx.set_bg(mouse_pos.0, mouse_pos.1, RGB::named(rltk::MAGENTA));
@@ -75,27 +113,31 @@ impl<'a> System<'a> for UISystem<'_> {
                 let mut data =  (&positions, &renderables, !&hidden).join().collect::<Vec<_>>();
                 data.sort_by(|&a, &b| b.1.render_order.cmp(&a.1.render_order) );
                 for (pos, render, _hidden) in data.iter() {
-                    let idx = xy_idx(pos.get_x(), pos.get_y());
-                    if map.visible_tiles[idx] || render.render_order == 0 { 
-                        self.ctx.set(pos.get_x(), pos.get_y(), render.fg, render.bg, render.glyph) 
+                    let idx = map.xy_idx(pos.get_x(), pos.get_y());
+                    if let Some(screen) = camera.to_screen(pos.get_x(), pos.get_y()) {
+                        if map.visible_tiles[idx] || render.render_order == 0 {
+                            self.ctx.set(screen.x, screen.y, render.fg, render.bg, render.glyph)
+                        }
                     }
                 }
 
                 for particle in particles.particle.values() {
                     if particle.render_order < 2 {
                         let (x, y) = particle.pos;
-                        self.ctx.set(x, y, particle.fg, particle.bg, particle.glyph);
+                        if let Some(screen) = camera.to_screen(x, y) {
+                            self.ctx.set(screen.x, screen.y, particle.fg, particle.bg, particle.glyph);
+                        }
                     }
                 }
 
                 // Tooltips Code
                 let mouse_pos = self.ctx.mouse_pos();
-                self.ctx.print (70, 49, format!("( {}, {} )", mouse_pos.0, mouse_pos.1));
-                if mouse_pos.0 >= map.width || mouse_pos.1 >= map.height { return; }
+                let Some(mouse_at) = camera.to_world(mouse_pos.0, mouse_pos.1) else { return; };
+                self.ctx.print (70, 49, format!("( {}, {} )", mouse_at.x, mouse_at.y));
                 let mut tooltip : Vec<String> = Vec::new();
                 for (name, position, _hidden) in (&names, &positions, !&hidden).join() {
-                    let idx = xy_idx(position.get_x(), position.get_y());
-                    if position.get_x() == mouse_pos.0 && position.get_y() == mouse_pos.1 && map.visible_tiles[idx] {
+                    let idx = map.xy_idx(position.get_x(), position.get_y());
+                    if position.get_x() == mouse_at.x && position.get_y() == mouse_at.y && map.visible_tiles[idx] {
                         tooltip.push(name.name.to_string());
                     }
                 }
@@ -156,6 +198,28 @@ pub fn game_over(ctx : &mut Rltk) -> GameOverResult {
     }
 }
 
+/// The name an item is listed under in the menus. A stack shows how many items it holds; a
+/// wand shows the charges it has left, and a weapon or a piece of armour how much wear it can
+/// still take, and whether it is known to be cursed.
+fn item_label(ecs: &World, item: Entity, name: &Name) -> String {
+    let mut label = Stackable::label(&name.name, ecs.read_storage::<Stackable>().get(item));
+    if let Some(charges) = ecs.read_storage::<Charges>().get(item) {
+        label = format!("{} ({}/{})", label, charges.charges, charges.max_charges);
+    }
+    if let Some(durability) = ecs.read_storage::<Durability>().get(item) {
+        label = match durability.is_broken() {
+            true => format!("{} [broken]", label),
+            false => format!("{} [{}/{}]", label, durability.durability, durability.max_durability),
+        };
+    }
+    let known = ecs.read_storage::<Equipped>().contains(item)
+        || ecs.read_storage::<Enchantment>().get(item).is_some_and(|e| e.known);
+    if known && ecs.read_storage::<Cursed>().contains(item) {
+        label = format!("{} (cursed)", label);
+    }
+    label
+}
+
 pub fn show_inventory(gs : &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
     let player_entity = gs.ecs.fetch::<Entity>();
     let names = gs.ecs.read_storage::<Name>();
@@ -165,9 +229,16 @@ pub fn show_inventory(gs : &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Optio
     let inventory = (&backpack, &names).join().filter(|item| item.0.owner == *player_entity );
     let count = inventory.count();
 
+    // The title shows how much is carried, against how much can be carried unburdened.
+    let carried = gs.ecs.read_storage::<Encumbrance>().get(*player_entity).map_or(0.0, |e| e.carried);
+    let title = match gs.ecs.read_storage::<GeneralStats>().get(*player_entity) {
+        Some(attributes) => format!("Inventory ({:.1}/{:.0} lbs)", carried, attributes.carry_capacity()),
+        None => "Inventory".to_string(),
+    };
+
     let mut y = (25 - (count / 2)) as i32;
-    ctx.draw_box(15, y-2, 31, (count+3) as i32, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
-    ctx.print_color(18, y-2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Inventory");
+    ctx.draw_box(15, y-2, 40, (count+3) as i32, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    ctx.print_color(18, y-2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), &title);
     ctx.print_color(18, y+count as i32+1, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "ESCAPE to cancel");
 
     let mut equippable : Vec<Entity> = Vec::new();
@@ -177,7 +248,7 @@ pub fn show_inventory(gs : &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Optio
         ctx.set(18, y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), 97+j as rltk::FontCharType);
         ctx.set(19, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437(')'));
 
-        ctx.print(21, y, &name.name.to_string());
+        ctx.print(21, y, item_label(&gs.ecs, entity, name));
         equippable.push(entity);
         y += 1;
         j += 1;
@@ -200,6 +271,39 @@ pub fn show_inventory(gs : &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Optio
     }
 }
 
+/// Offers the LevelUpChoices. There is no cancelling: a choice has to be made.
+pub fn level_up_menu(gs : &mut State, ctx : &mut Rltk) -> (ItemMenuResult, Option<LevelUpChoice>) {
+    let level = {
+        let player_entity = gs.ecs.fetch::<Entity>();
+        gs.ecs.read_storage::<Experience>().get(*player_entity).map_or(1, |xp| xp.level)
+    };
+    let count = LevelUpChoice::ALL.len();
+
+    let y = (25 - (count / 2)) as i32;
+    ctx.draw_box(15, y-2, 50, (count+3) as i32, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    ctx.print_color(18, y-2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), format!("Welcome to level {}! Choose:", level + 1));
+
+    for (j, choice) in LevelUpChoice::ALL.iter().enumerate() {
+        let row = y + j as i32;
+        ctx.set(17, row, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437('('));
+        ctx.set(18, row, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), 97+j as rltk::FontCharType);
+        ctx.set(19, row, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437(')'));
+
+        ctx.print(21, row, choice.describe());
+    }
+
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => {
+            let selection = rltk::letter_to_option(key);
+            if selection > -1 && selection < count as i32 {
+                return (ItemMenuResult::Selected, Some(LevelUpChoice::ALL[selection as usize]));
+            }
+            (ItemMenuResult::NoResponse, None)
+        }
+    }
+}
+
 pub fn drop_item_menu(gs : &mut State, ctx : &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
     let player_entity = gs.ecs.fetch::<Entity>();
     let names = gs.ecs.read_storage::<Name>();
@@ -210,7 +314,7 @@ pub fn drop_item_menu(gs : &mut State, ctx : &mut Rltk) -> (ItemMenuResult, Opti
     let count = inventory.count();
 
     let mut y = (25 - (count / 2)) as i32;
-    ctx.draw_box(15, y-2, 31, (count+3) as i32, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    ctx.draw_box(15, y-2, 40, (count+3) as i32, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
     ctx.print_color(18, y-2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Drop Which Item?");
     ctx.print_color(18, y+count as i32+1, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "ESCAPE to cancel");
 
@@ -221,7 +325,7 @@ pub fn drop_item_menu(gs : &mut State, ctx : &mut Rltk) -> (ItemMenuResult, Opti
         ctx.set(18, y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), 97+j as rltk::FontCharType);
         ctx.set(19, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437(')'));
 
-        ctx.print(21, y, &name.name.to_string());
+        ctx.print(21, y, item_label(&gs.ecs, entity, name));
         equippable.push(entity);
         y += 1;
         j += 1;
@@ -244,6 +348,30 @@ pub fn drop_item_menu(gs : &mut State, ctx : &mut Rltk) -> (ItemMenuResult, Opti
     }
 }
 
+/// Asks how many of a stack to drop, starting from quantity: the arrow keys change it, Enter
+/// drops that many and Escape cancels. Returns the quantity as it now stands.
+pub fn drop_quantity_menu(gs : &mut State, ctx : &mut Rltk, item : Entity, quantity : i32) -> (ItemMenuResult, i32) {
+    let names = gs.ecs.read_storage::<Name>();
+    let count = gs.ecs.read_storage::<Stackable>().get(item).map_or(1, |stack| stack.count);
+    let name = names.get(item).map_or("", |name| name.name.as_str());
+
+    ctx.draw_box(15, 23, 40, 4, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    ctx.print_color(18, 23, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Drop How Many?");
+    ctx.print(18, 25, format!("< {} > of {} {}", quantity, count, name));
+    ctx.print_color(18, 27, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "ENTER to drop, ESCAPE to cancel");
+
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, quantity),
+        Some(key) => match key {
+            VirtualKeyCode::Escape => (ItemMenuResult::Cancel, quantity),
+            VirtualKeyCode::Return => (ItemMenuResult::Selected, quantity),
+            VirtualKeyCode::Left | VirtualKeyCode::Down => (ItemMenuResult::NoResponse, i32::max(1, quantity - 1)),
+            VirtualKeyCode::Right | VirtualKeyCode::Up => (ItemMenuResult::NoResponse, i32::min(count, quantity + 1)),
+            _ => (ItemMenuResult::NoResponse, quantity),
+        },
+    }
+}
+
 pub fn remove_item_menu(gs : &mut State, ctx : &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
     let player_entity = gs.ecs.fetch::<Entity>();
     let names = gs.ecs.read_storage::<Name>();
@@ -254,7 +382,7 @@ pub fn remove_item_menu(gs : &mut State, ctx : &mut Rltk) -> (ItemMenuResult, Op
     let count = inventory.count();
 
     let mut y = (25 - (count / 2)) as i32;
-    ctx.draw_box(15, y-2, 31, (count+3) as i32, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    ctx.draw_box(15, y-2, 40, (count+3) as i32, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
     ctx.print_color(18, y-2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Remove Which Item?");
     ctx.print_color(18, y+count as i32+1, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "ESCAPE to cancel");
 
@@ -265,7 +393,7 @@ pub fn remove_item_menu(gs : &mut State, ctx : &mut Rltk) -> (ItemMenuResult, Op
         ctx.set(18, y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), 97+j as rltk::FontCharType);
         ctx.set(19, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437(')'));
 
-        ctx.print(21, y, &name.name.to_string());
+        ctx.print(21, y, item_label(&gs.ecs, entity, name));
         equippable.push(entity);
         y += 1;
         j += 1;
@@ -288,11 +416,85 @@ pub fn remove_item_menu(gs : &mut State, ctx : &mut Rltk) -> (ItemMenuResult, Op
     }
 }
 
+/// The equipment screen lists every slot and what is worn in it, with the armour class and
+/// melee power that all of it adds up to. Picking a worn item takes it off.
+pub fn show_equipment(gs : &mut State, ctx : &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
+    let player_entity = gs.ecs.fetch::<Entity>();
+    let names = gs.ecs.read_storage::<Name>();
+    let equipped = gs.ecs.read_storage::<Equipped>();
+    let defense_bonus = gs.ecs.read_storage::<DefenseBonus>();
+    let melee_power_bonus = gs.ecs.read_storage::<MeleePowerBonus>();
+    let combat_stats = gs.ecs.read_storage::<CombatStats>();
+    let attributes = gs.ecs.read_storage::<GeneralStats>();
+    let encumbrance = gs.ecs.read_storage::<Encumbrance>();
+    let durability = gs.ecs.read_storage::<Durability>();
+    let entities = gs.ecs.entities();
+
+    // One row for every place something can be worn, two of them for rings.
+    let mut rows : Vec<(EquipmentSlot, Option<Entity>)> = Vec::new();
+    for slot in EquipmentSlot::WORN {
+        let worn : Vec<Entity> = (&entities, &equipped).join()
+            .filter(|(_e, equipped_by)| equipped_by.owner == *player_entity && equipped_by.slot == slot)
+            .map(|(e, _equipped_by)| e)
+            .collect();
+        for i in 0..slot.capacity().max(worn.len()) {
+            rows.push((slot, worn.get(i).copied()));
+        }
+    }
+    let count = rows.len();
+
+    let mut y = (25 - (count / 2)) as i32;
+    ctx.draw_box(15, y-2, 40, (count+4) as i32, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    ctx.print_color(18, y-2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Equipment");
+    ctx.print_color(18, y+count as i32+2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "ESCAPE to cancel");
+
+    for (j, (slot, item)) in rows.iter().enumerate() {
+        ctx.set(17, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437('('));
+        ctx.set(18, y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), 97+j as rltk::FontCharType);
+        ctx.set(19, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437(')'));
+        ctx.print_color(21, y, RGB::named(rltk::CYAN), RGB::named(rltk::BLACK), slot.name());
+        match item.and_then(|item| names.get(item).map(|name| (item, name))) {
+            Some((item, name)) => ctx.print(29, y, item_label(&gs.ecs, item, name)),
+            None => ctx.print_color(29, y, RGB::named(rltk::GREY), RGB::named(rltk::BLACK), "-"),
+        }
+        y += 1;
+    }
+
+    if let Some(stats) = combat_stats.get(*player_entity) {
+        let armour_class = armour_class(*player_entity, stats, &defense_bonus, &equipped, &attributes, &encumbrance, &durability);
+        let power : i32 = (&melee_power_bonus, &equipped, durability.maybe()).join()
+            .filter(|(_power, equipped_by, item_durability)| equipped_by.owner == *player_entity && Durability::works(*item_durability))
+            .map(|(power, _equipped_by, _durability)| power.power)
+            .sum();
+        ctx.print_color(21, y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK),
+            format!("Armour class {}   Melee power {:+}", armour_class, power));
+    }
+
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => {
+            match key {
+                VirtualKeyCode::Escape => { (ItemMenuResult::Cancel, None) }
+                _ => { 
+                    let selection = rltk::letter_to_option(key);
+                    if selection > -1 && selection < count as i32 {
+                        if let Some(item) = rows[selection as usize].1 {
+                            return (ItemMenuResult::Selected, Some(item));
+                        }
+                    }  
+                    (ItemMenuResult::NoResponse, None)
+                }
+            }
+        }
+    }
+}
+
 pub fn ranged_target(gs : &mut State, ctx : &mut Rltk, range : i32) -> (ItemMenuResult, Option<Point>) {
     let players = gs.ecs.read_storage::<Player>();
     let entities = gs.ecs.entities();
     let player_pos = gs.ecs.fetch::<Point>();
     let viewsheds = gs.ecs.read_storage::<Viewshed>();
+    let camera = Camera::new(&gs.ecs.fetch::<Map>(), *player_pos);
 
     ctx.print_color(5, 0, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Select Target:");
 
@@ -305,7 +507,9 @@ pub fn ranged_target(gs : &mut State, ctx : &mut Rltk, range : i32) -> (ItemMenu
             for idx in visible.visible_tiles.iter() {
                 let distance = rltk::DistanceAlg::Pythagoras.distance2d(*player_pos, *idx);
                 if distance <= range as f32 {
-                    ctx.set_bg(idx.x, idx.y, RGB::named(rltk::BLUE));
+                    if let Some(screen) = camera.to_screen(idx.x, idx.y) {
+                        ctx.set_bg(screen.x, screen.y, RGB::named(rltk::BLUE));
+                    }
                     available_cells.push(idx);
                 }
             }
@@ -315,12 +519,11 @@ pub fn ranged_target(gs : &mut State, ctx : &mut Rltk, range : i32) -> (ItemMenu
 
         // Draw mouse cursor
         let mouse_pos = ctx.mouse_pos();
-        let mut valid_target = false;
-        for idx in available_cells.iter() { if idx.x == mouse_pos.0 && idx.y == mouse_pos.1 { valid_target = true; } }
-        if valid_target {
+        let target = camera.to_world(mouse_pos.0, mouse_pos.1).filter(|at| available_cells.contains(&at));
+        if let Some(target) = target {
             ctx.set_bg(mouse_pos.0, mouse_pos.1, RGB::named(rltk::CYAN));
             if ctx.left_click {
-                return (ItemMenuResult::Selected, Some(Point::new(mouse_pos.0, mouse_pos.1)));
+                return (ItemMenuResult::Selected, Some(target));
             }
         } else {
             ctx.set_bg(mouse_pos.0, mouse_pos.1, RGB::named(rltk::RED));
@@ -2,8 +2,8 @@ use rltk::{field_of_view, Point};
 use specs::prelude::*;
 
 use crate::gamelog::GameLog;
-use crate::spawner::components::{Viewshed, Position, Hidden, Name};
-use crate::map_builders::{map::Map, common::xy_idx};
+use crate::spawner::components::{Viewshed, Position, Hidden, Name, StatusEffects, StatusKind, Door};
+use crate::map_builders::{map::Map};
 use crate::spawner::player::Player;
 
 pub struct VisibilitySystem {}
@@ -17,16 +17,31 @@ impl<'a> System<'a> for VisibilitySystem {
                         WriteStorage<'a, Hidden>,
                         WriteExpect<'a, rltk::RandomNumberGenerator>,
                         WriteExpect<'a, GameLog>,
-                        ReadStorage<'a, Name>,);
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, StatusEffects>,
+                        ReadStorage<'a, Door>,);
 
     fn run(&mut self, data : Self::SystemData) {
         let (mut map, entities, mut viewshed, pos, player, 
-            mut hidden, mut rng, mut log, names) = data;
+            mut hidden, mut rng, mut log, names, status_effects, doors) = data;
+
+        // Closed doors hide whatever is behind them.
+        map.view_blocked.clear();
+        for (door, door_pos) in (&doors, &pos).join() {
+            if !door.open {
+                let idx = map.xy_idx(door_pos.get_x(), door_pos.get_y());
+                map.view_blocked.insert(idx);
+            }
+        }
+
         for (viewshed, pos, ent) in (&mut viewshed, &pos, &entities).join() {
             if viewshed.dirty {
                 viewshed.dirty = false;
                 viewshed.visible_tiles.clear();
-                viewshed.visible_tiles = field_of_view(Point::new(pos.get_x(), pos.get_y()), viewshed.range, &*map);
+                // The blind only know what is right next to them.
+                let blind = status_effects.get(ent).is_some_and(|s| s.has(StatusKind::Blindness));
+                let range = if blind { 1 } else { viewshed.range };
+                viewshed.visible_tiles = field_of_view(Point::new(pos.get_x(), pos.get_y()), range, &*map);
                 viewshed.visible_tiles.retain(|p| p.x >= 0 && p.x < map.width && p.y >= 0 && p.y < map.height );
             
                 // If this is the player, reveal what they can see
@@ -34,7 +49,7 @@ impl<'a> System<'a> for VisibilitySystem {
                 if let Some(_p) = _p {
                     for t in map.visible_tiles.iter_mut() { *t = false };
                     for vis in viewshed.visible_tiles.iter() {
-                        let idx = xy_idx(vis.x, vis.y);
+                        let idx = map.xy_idx(vis.x, vis.y);
                         map.revealed_tiles[idx] = true;
                         map.visible_tiles[idx] = true;
 
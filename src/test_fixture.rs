@@ -0,0 +1,57 @@
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+use specs::saveload::SimpleMarkerAllocator;
+use std::ops::Range;
+
+use crate::components::{register_components, InBackpack, SerializeMe};
+use crate::gamelog::GameLog;
+use crate::map_builders::map::{Map, TileType};
+use crate::spawner::components::{register_spawns, EquipmentSlot, Equipped, Position};
+use crate::spawner::item::Identification;
+use crate::spawner::raws::spawn_named_entity;
+use crate::systems::particle_system::ParticleBuilder;
+use crate::systems::pathing_system::PathingMaps;
+
+/// A World with every component registered and the resources the systems fetch: a
+/// RandomNumberGenerator and an Identification both seeded with 1, an empty GameLog, a
+/// ParticleBuilder, PathingMaps and a depth 1 Map that is solid wall. Tests carve out the map
+/// they need and add their own player.
+pub fn test_world() -> World {
+    let mut ecs = World::new();
+    register_components(&mut ecs);
+    register_spawns(&mut ecs);
+    ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+    ecs.insert(RandomNumberGenerator::seeded(1));
+    ecs.insert(GameLog { entries: Vec::new() });
+    ecs.insert(ParticleBuilder::new());
+    ecs.insert(PathingMaps::default());
+    ecs.insert(Identification::new(1));
+    ecs.insert(Map::new(1, 80, 43));
+    ecs
+}
+
+/// Turns the tiles of row y whose x is in xs into Floor.
+pub fn floor_row(ecs: &World, xs: Range<i32>, y: i32) {
+    let mut map = ecs.fetch_mut::<Map>();
+    for x in xs {
+        let idx = map.xy_idx(x, y);
+        map.tiles[idx] = TileType::Floor;
+    }
+}
+
+/// Spawns the raw called name straight into owner's backpack.
+pub fn carry(ecs: &mut World, owner: Entity, name: &str) -> Entity {
+    let item = spawn_named_entity(ecs, name, 0, 0).unwrap();
+    ecs.write_storage::<Position>().remove(item);
+    ecs.write_storage::<InBackpack>().insert(item, InBackpack { owner }).unwrap();
+    item
+}
+
+/// Spawns the raw called name already equipped by owner in slot, without the checks the
+/// ItemUseSystem makes on the way.
+pub fn wield(ecs: &mut World, owner: Entity, name: &str, slot: EquipmentSlot) -> Entity {
+    let item = spawn_named_entity(ecs, name, 0, 0).unwrap();
+    ecs.write_storage::<Position>().remove(item);
+    ecs.write_storage::<Equipped>().insert(item, Equipped { owner, slot }).unwrap();
+    item
+}